@@ -1,33 +1,61 @@
-use criterion::{criterion_group, criterion_main, Criterion};
-use dmap::formats::dmap::Record;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use dmap::formats::dmap::{GenericRecord, ReadOptions, Record, ValidationMode};
 use dmap::formats::fitacf::FitacfRecord;
 use dmap::formats::grid::GridRecord;
 use dmap::formats::iqdat::IqdatRecord;
 use dmap::formats::map::MapRecord;
 use dmap::formats::rawacf::RawacfRecord;
 use dmap::formats::snd::SndRecord;
-use std::fs::File;
+use dmap::types::DmapField;
+use dmap::{parse_one, read_file_unchecked, read_file_with, write_fitacf, write_rawacf};
+use indexmap::IndexMap;
+use numpy::ndarray::Array1;
+use std::fs::{remove_file, File};
+use std::io::Cursor;
+use std::path::PathBuf;
 
 fn criterion_benchmark(c: &mut Criterion) {
-    c.bench_function("Read IQDAT", |b| b.iter(|| read_iqdat()));
-    c.bench_function("Read RAWACF", |b| b.iter(|| read_rawacf()));
-    c.bench_function("Read FITACF", |b| b.iter(|| read_fitacf()));
-    c.bench_function("Read GRID", |b| b.iter(|| read_grid()));
-    c.bench_function("Read SND", |b| b.iter(|| read_snd()));
-    c.bench_function("Read MAP", |b| b.iter(|| read_map()));
+    c.bench_function("Read IQDAT", |b| b.iter(read_iqdat));
+    c.bench_function("Read RAWACF", |b| b.iter(read_rawacf));
+    c.bench_function("Read FITACF", |b| b.iter(read_fitacf));
+    c.bench_function("Read GRID", |b| b.iter(read_grid));
+    c.bench_function("Read SND", |b| b.iter(read_snd));
+    c.bench_function("Read MAP", |b| b.iter(read_map));
+    // Reads through `read_file`, which memory-maps the file with `--features mmap`
+    c.bench_function("Read FITACF from path", |b| b.iter(read_fitacf_path));
+    c.bench_function("Read RAWACF from path", |b| b.iter(read_rawacf_path));
+    // Parses each record from its own buffer, for comparison with parsing from one shared buffer
+    c.bench_function("Iterate RAWACF", |b| b.iter(iterate_rawacf));
+    c.bench_function("Read FITACF bz2", |b| b.iter(read_fitacf_bz2));
+    c.bench_function("Read RAWACF bz2", |b| b.iter(read_rawacf_bz2));
+    c.bench_function("Read FITACF lenient", |b| b.iter(read_fitacf_lenient));
+    c.bench_function("Read FITACF unchecked", |b| b.iter(read_fitacf_unchecked));
+
+    // A single record with large vector fields, like a MAP file with few records
+    let large_record = large_record_bytes();
+    c.bench_function("Parse large record", |b| {
+        b.iter(|| parse_one(&mut Cursor::new(large_record.as_slice())).unwrap())
+    });
+
+    let fitacf = read_fitacf_path();
+    c.bench_function("Write FITACF", |b| {
+        b.iter_batched(|| fitacf.clone(), write_fitacf_path, BatchSize::LargeInput)
+    });
+    let rawacf = read_rawacf_path();
+    c.bench_function("Write RAWACF", |b| {
+        b.iter_batched(|| rawacf.clone(), write_rawacf_path, BatchSize::LargeInput)
+    });
+    // Serializes each record into its own buffer, for comparison with reusing one buffer
+    c.bench_function("Serialize FITACF", |b| b.iter(|| serialize_fitacf(&fitacf)));
+    c.bench_function("Serialize FITACF into buffer", |b| {
+        b.iter(|| serialize_fitacf_into(&fitacf))
+    });
     // c.bench_function("Read Full-size RAWACF", |b| {
     //     b.iter(|| read_fullsize_rawacf())
     // });
     // c.bench_function("Read Full-size FITACF", |b| {
     //     b.iter(|| read_fullsize_fitacf())
     // });
-
-    // let records = read_iqdat();
-    // c.bench_with_input(
-    //     BenchmarkId::new("Write IQDAT", "IQDAT Records"),
-    //     &records,
-    //     |b, s| b.iter(|| write_iqdat(s)),
-    // );
 }
 
 fn read_fitacf() -> Vec<FitacfRecord> {
@@ -40,12 +68,85 @@ fn read_rawacf() -> Vec<RawacfRecord> {
     RawacfRecord::read_records(file).unwrap()
 }
 
+fn read_fitacf_path() -> Vec<FitacfRecord> {
+    FitacfRecord::read_file(&PathBuf::from("tests/test_files/test.fitacf")).unwrap()
+}
+
+fn read_rawacf_path() -> Vec<RawacfRecord> {
+    RawacfRecord::read_file(&PathBuf::from("tests/test_files/test.rawacf")).unwrap()
+}
+
+fn iterate_rawacf() -> Vec<RawacfRecord> {
+    RawacfRecord::iter_records(&PathBuf::from("tests/test_files/test.rawacf"))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+fn large_record_bytes() -> Vec<u8> {
+    let mut fields = IndexMap::from([("stid".to_string(), DmapField::from(65_i16))]);
+    for i in 0..32 {
+        let data = Array1::from_iter((0..100_000).map(|x| x as f32 * i as f32)).into_dyn();
+        fields.insert(format!("vector{i}"), DmapField::from(data));
+    }
+    GenericRecord::new(&mut fields).unwrap().to_bytes().unwrap()
+}
+
+fn read_fitacf_bz2() -> Vec<FitacfRecord> {
+    FitacfRecord::read_file(&PathBuf::from("tests/test_files/test.fitacf.bz2")).unwrap()
+}
+
+fn read_rawacf_bz2() -> Vec<RawacfRecord> {
+    RawacfRecord::read_file(&PathBuf::from("tests/test_files/test.rawacf.bz2")).unwrap()
+}
+
+fn read_fitacf_lenient() -> Vec<FitacfRecord> {
+    let opts = ReadOptions {
+        validation: ValidationMode::Lenient,
+        ..Default::default()
+    };
+    read_file_with(&PathBuf::from("tests/test_files/test.fitacf"), &opts).unwrap()
+}
+
+fn read_fitacf_unchecked() -> Vec<FitacfRecord> {
+    read_file_unchecked(&PathBuf::from("tests/test_files/test.fitacf")).unwrap()
+}
+
+fn write_fitacf_path(recs: Vec<FitacfRecord>) {
+    let outfile = PathBuf::from("tests/test_files/bench_write.fitacf");
+    write_fitacf(recs, &outfile).unwrap();
+    remove_file(&outfile).unwrap();
+}
+
+fn write_rawacf_path(recs: Vec<RawacfRecord>) {
+    let outfile = PathBuf::from("tests/test_files/bench_write.rawacf");
+    write_rawacf(recs, &outfile).unwrap();
+    remove_file(&outfile).unwrap();
+}
+
+fn serialize_fitacf(recs: &[FitacfRecord]) -> usize {
+    recs.iter().map(|rec| rec.to_bytes().unwrap().len()).sum()
+}
+
+fn serialize_fitacf_into(recs: &[FitacfRecord]) -> usize {
+    let mut buf = vec![];
+    recs.iter()
+        .map(|rec| {
+            buf.clear();
+            rec.to_bytes_into(&mut buf).unwrap();
+            buf.len()
+        })
+        .sum()
+}
+
+#[allow(dead_code)]
 fn read_fullsize_rawacf() -> Vec<RawacfRecord> {
     let file = File::open("tests/test_files/20210607.1801.00.cly.a.rawacf.mean")
         .expect("Test file not found");
     RawacfRecord::read_records(file).unwrap()
 }
 
+#[allow(dead_code)]
 fn read_fullsize_fitacf() -> Vec<FitacfRecord> {
     let file =
         File::open("tests/test_files/20210607.1801.00.cly.a.fitacf").expect("Test file not found");
@@ -57,12 +158,6 @@ fn read_iqdat() -> Vec<IqdatRecord> {
     IqdatRecord::read_records(file).unwrap()
 }
 
-// fn write_iqdat(records: &Vec<RawDmapRecord>) {
-//     let file = File::open("tests/test_files/test.iqdat").expect("Test file not found");
-//     dmap::read_records(file).unwrap();
-//     dmap::to_file("tests/test_files/temp.iqdat", records).unwrap();
-// }
-
 fn read_grid() -> Vec<GridRecord> {
     let file = File::open("tests/test_files/test.grid").expect("Test file not found");
     GridRecord::read_records(file).unwrap()
@@ -0,0 +1,218 @@
+//! Command-line tools for DMAP files.
+//!
+//! `dmap dump [--full] [--record N] FILE` prints the scalars and vector shapes of each record
+//! of a DMAP file of any format, like RST's `dmapdump`. `dmap convert IN OUT` rewrites a file,
+//! compressing or decompressing it according to the extension of `OUT`, and
+//! `dmap cat IN... -o OUT` concatenates files of the same format. Compressed files are read
+//! transparently.
+
+use dmap::error::DmapError;
+use dmap::formats::dmap::{GenericRecord, Record};
+use dmap::formats::FileFormat;
+use dmap::types::DmapField;
+use dmap::{concat_files, read_all, read_any, read_record_any, sniff_format, write_dmap};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+const USAGE: &str = "Usage:
+  dmap dump [--full] [--record N] FILE
+  dmap convert IN OUT
+  dmap cat IN... -o OUT
+
+Commands:
+  dump       Prints the fields of each record of FILE, detecting its format and compression
+  convert    Rewrites IN to OUT, compressed according to the extension of OUT
+  cat        Concatenates the records of each IN, which must have the same format, into OUT
+
+The format named by the extension of OUT, e.g. `.rawacf` in `out.rawacf.bz2`, must match the
+format of the input files. OUT must not already exist.
+
+Options:
+  --full        Print the data of vector fields, not just their shapes
+  --record N    Print only record N, counting from 0
+  -o OUT        The file to write the concatenated records to";
+
+/// A subcommand and its arguments.
+enum Command {
+    Dump {
+        infile: PathBuf,
+        full: bool,
+        record: Option<usize>,
+    },
+    Convert {
+        infile: PathBuf,
+        outfile: PathBuf,
+    },
+    Cat {
+        infiles: Vec<PathBuf>,
+        outfile: PathBuf,
+    },
+}
+
+impl Command {
+    /// Parses the command-line arguments, excluding the program name.
+    fn parse(args: &[String]) -> Result<Command, String> {
+        let (command, args) = args.split_first().ok_or("No command given")?;
+        let mut files: Vec<PathBuf> = vec![];
+        let mut full = false;
+        let mut record = None;
+        let mut outfile = None;
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--full" if command == "dump" => full = true,
+                "--record" if command == "dump" => {
+                    let value = args.next().ok_or("--record requires a value")?;
+                    record = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("Invalid record number {value}"))?,
+                    );
+                }
+                "-o" if command == "cat" => {
+                    outfile = Some(PathBuf::from(args.next().ok_or("-o requires a value")?));
+                }
+                x if x.starts_with('-') => Err(format!("Unknown option {x} for {command}"))?,
+                x => files.push(PathBuf::from(x)),
+            }
+        }
+        match (command.as_str(), files.len()) {
+            ("dump", 1) => Ok(Command::Dump {
+                infile: files.remove(0),
+                full,
+                record,
+            }),
+            ("convert", 2) => Ok(Command::Convert {
+                outfile: files.remove(1),
+                infile: files.remove(0),
+            }),
+            ("cat", n) if n > 0 => Ok(Command::Cat {
+                infiles: files,
+                outfile: outfile.ok_or("No output file given with -o")?,
+            }),
+            ("dump" | "convert" | "cat", _) => Err(format!("Wrong number of files for {command}")),
+            _ => Err(format!("Unknown command {command}")),
+        }
+    }
+
+    fn run(&self) -> Result<(), DmapError> {
+        match self {
+            Command::Dump {
+                infile,
+                full,
+                record,
+            } => dump(infile, *full, *record),
+            Command::Convert { infile, outfile } => {
+                let (format, recs) = read_all(infile.clone())?;
+                check_output(outfile, format)?;
+                write_dmap(recs, outfile)
+            }
+            Command::Cat { infiles, outfile } => {
+                let format = sniff_format(&infiles[0])?;
+                for infile in &infiles[1..] {
+                    let other = sniff_format(infile)?;
+                    if other != format {
+                        Err(DmapError::InvalidRecord(format!(
+                            "{} is {other} but {} is {format}",
+                            infile.display(),
+                            infiles[0].display()
+                        )))?
+                    }
+                }
+                check_output(outfile, format)?;
+                concat_files::<GenericRecord>(infiles, outfile)
+            }
+        }
+    }
+}
+
+/// Checks that `outfile` does not exist, and that the format named by its extension, if any, is
+/// `format`. Compression extensions are skipped, so `out.rawacf.bz2` names `FileFormat::Rawacf`.
+fn check_output(outfile: &Path, format: FileFormat) -> Result<(), DmapError> {
+    if outfile.exists() {
+        Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", outfile.display()),
+        ))?
+    }
+    let mut path = outfile.to_path_buf();
+    if matches!(path.extension(), Some(ext) if ext == "bz2" || ext == "gz" || ext == "zst") {
+        path.set_extension("");
+    }
+    let named = path
+        .extension()
+        .and_then(|ext| ext.to_str()?.parse::<FileFormat>().ok());
+    match named {
+        Some(x) if x != format => Err(DmapError::InvalidRecord(format!(
+            "Cannot write {format} records to {}, which is named as {x}",
+            outfile.display()
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Writes record `index` to `out`, with the data of each vector field if `full`.
+fn print_record(
+    out: &mut impl Write,
+    index: usize,
+    rec: &GenericRecord,
+    full: bool,
+) -> io::Result<()> {
+    writeln!(out, "Record {index}")?;
+    if !full {
+        return writeln!(out, "{}", rec.summary());
+    }
+    for (name, field) in rec.data() {
+        match field {
+            DmapField::Scalar(x) => writeln!(out, "scalar {name} {x}")?,
+            DmapField::Vector(x) => {
+                writeln!(out, "vector {name} {} {:?}", x.get_type(), x.shape())?;
+                writeln!(out, "{x:#}")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Prints the records of `infile`, or only record `record` if given.
+fn dump(infile: &PathBuf, full: bool, record: Option<usize>) -> Result<(), DmapError> {
+    let mut out = io::stdout().lock();
+    writeln!(out, "Format: {}", sniff_format(infile)?)?;
+    match record {
+        Some(index) => {
+            let rec = read_record_any(infile, index)?;
+            print_record(&mut out, index, &rec, full)?;
+        }
+        None => {
+            for (index, rec) in read_any(infile.clone())?.iter().enumerate() {
+                print_record(&mut out, index, rec, full)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if matches!(args.first().map(String::as_str), Some("-h" | "--help")) {
+        println!("{USAGE}");
+        return ExitCode::SUCCESS;
+    }
+    let command = match Command::parse(&args) {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("{e}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    match command.run() {
+        Ok(()) => ExitCode::SUCCESS,
+        // The output was closed early, e.g. by piping into `head`
+        Err(DmapError::Io(e)) if e.kind() == io::ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,372 @@
+//! Conversion of records to and from Arrow record batches, enabled with the `arrow` feature.
+//!
+//! Each record becomes one row of the batch. Scalar fields map to columns of the corresponding
+//! primitive type (or `Utf8` for strings), and vector fields map to `List` columns of the
+//! corresponding primitive type. Vectors with more than one dimension map to nested lists, one
+//! level per dimension, so e.g. a `[23, 2]` vector becomes a list of 23 lists of 2 elements. The
+//! columns follow the order of the format's field table: required scalars, optional scalars,
+//! required vectors, then optional vectors.
+//!
+//! Optional fields are nullable columns, with a null in each row whose record does not contain
+//! the field. For example, FITACF records of beams with no good ranges do not contain `slist` or
+//! any of the per-range vectors, so those rows are null in each of the columns. Required fields
+//! are non-nullable, as every valid record contains them.
+//!
+//! When converting a batch back into records, null entries are left out of the record, and each
+//! list entry becomes a vector with one dimension per level of nesting.
+
+use crate::error::DmapError;
+use crate::formats::dmap::Record;
+use crate::formats::fitacf::{fitacf_fields, FitacfRecord};
+use crate::types::{get_field_as, DmapField, FieldValue, Fields, Type};
+use crate::write_fitacf;
+use arrow_array::types::{
+    Float32Type, Float64Type, Int16Type, Int32Type, Int64Type, Int8Type, UInt16Type, UInt32Type,
+    UInt64Type, UInt8Type,
+};
+use arrow_array::{
+    Array, ArrayRef, ArrowPrimitiveType, ListArray, PrimitiveArray, RecordBatch, StringArray,
+};
+use arrow_buffer::{NullBuffer, OffsetBuffer};
+use arrow_schema::{DataType, Field, Schema};
+use indexmap::IndexMap;
+use numpy::ndarray::{ArrayD, IxDyn};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Gets the field `name` of each record as a `T`, or `None` for records without the field.
+fn column<'a, T: FieldValue<'a>>(
+    recs: &'a [IndexMap<String, DmapField>],
+    name: &str,
+) -> Result<Vec<Option<T>>, DmapError> {
+    recs.iter()
+        .map(|rec| match rec.get(name) {
+            Some(_) => get_field_as::<T>(rec, name).map(Some),
+            None => Ok(None),
+        })
+        .collect()
+}
+
+/// Builds a primitive column from the scalar field `name` of each record.
+fn scalar_column<'a, P: ArrowPrimitiveType>(
+    recs: &'a [IndexMap<String, DmapField>],
+    name: &str,
+) -> Result<ArrayRef, DmapError>
+where
+    P::Native: FieldValue<'a>,
+{
+    let values = column::<P::Native>(recs, name)?;
+    Ok(Arc::new(values.into_iter().collect::<PrimitiveArray<P>>()))
+}
+
+/// Builds a list column from the vector field `name` of each record, nesting one level of lists
+/// per dimension of the vectors.
+///
+/// Returns `DmapError` if the vectors of different records have different numbers of dimensions.
+fn vector_column<'a, P: ArrowPrimitiveType>(
+    recs: &'a [IndexMap<String, DmapField>],
+    name: &str,
+) -> Result<ArrayRef, DmapError>
+where
+    &'a ArrayD<P::Native>: FieldValue<'a>,
+{
+    let vectors = column::<&ArrayD<P::Native>>(recs, name)?;
+    let ndim = vectors
+        .iter()
+        .flatten()
+        .map(|x| x.ndim())
+        .max()
+        .unwrap_or(1);
+    if vectors.iter().flatten().any(|x| x.ndim() != ndim) {
+        Err(DmapError::InvalidVector(format!(
+            "Vector field {name} has a different number of dimensions in different records"
+        )))?
+    }
+    let data = vectors.iter().flatten().flat_map(|x| x.iter().copied());
+    let mut array: ArrayRef = Arc::new(PrimitiveArray::<P>::from_iter_values(data));
+
+    // Wrap the elements in lists from the innermost dimension outwards. Each record has as many
+    // lists at dimension `dim` as the product of the outer dimensions.
+    for dim in (0..ndim).rev() {
+        let mut lengths: Vec<usize> = vec![];
+        for vector in vectors.iter() {
+            match vector {
+                Some(x) => {
+                    let num_lists: usize = x.shape()[..dim].iter().product();
+                    lengths.extend(std::iter::repeat(x.shape()[dim]).take(num_lists));
+                }
+                None if dim == 0 => lengths.push(0),
+                None => {}
+            }
+        }
+        let nulls = (dim == 0)
+            .then(|| NullBuffer::from(vectors.iter().map(Option::is_some).collect::<Vec<_>>()));
+        let item = Arc::new(Field::new("item", array.data_type().clone(), true));
+        array = Arc::new(
+            ListArray::try_new(item, OffsetBuffer::from_lengths(lengths), array, nulls).map_err(
+                |e| DmapError::InvalidVector(format!("Cannot build column {name}: {e}")),
+            )?,
+        );
+    }
+    Ok(array)
+}
+
+/// Builds the column for the field `name` of `Type` `data_type` from each record.
+fn field_column(
+    recs: &[IndexMap<String, DmapField>],
+    name: &str,
+    data_type: &Type,
+    is_vector: bool,
+) -> Result<ArrayRef, DmapError> {
+    match (data_type, is_vector) {
+        (Type::Char, false) => scalar_column::<Int8Type>(recs, name),
+        (Type::Short, false) => scalar_column::<Int16Type>(recs, name),
+        (Type::Int, false) => scalar_column::<Int32Type>(recs, name),
+        (Type::Long, false) => scalar_column::<Int64Type>(recs, name),
+        (Type::Uchar, false) => scalar_column::<UInt8Type>(recs, name),
+        (Type::Ushort, false) => scalar_column::<UInt16Type>(recs, name),
+        (Type::Uint, false) => scalar_column::<UInt32Type>(recs, name),
+        (Type::Ulong, false) => scalar_column::<UInt64Type>(recs, name),
+        (Type::Float, false) => scalar_column::<Float32Type>(recs, name),
+        (Type::Double, false) => scalar_column::<Float64Type>(recs, name),
+        (Type::String, false) => Ok(Arc::new(StringArray::from(column::<&str>(recs, name)?))),
+        (Type::Char, true) => vector_column::<Int8Type>(recs, name),
+        (Type::Short, true) => vector_column::<Int16Type>(recs, name),
+        (Type::Int, true) => vector_column::<Int32Type>(recs, name),
+        (Type::Long, true) => vector_column::<Int64Type>(recs, name),
+        (Type::Uchar, true) => vector_column::<UInt8Type>(recs, name),
+        (Type::Ushort, true) => vector_column::<UInt16Type>(recs, name),
+        (Type::Uint, true) => vector_column::<UInt32Type>(recs, name),
+        (Type::Ulong, true) => vector_column::<UInt64Type>(recs, name),
+        (Type::Float, true) => vector_column::<Float32Type>(recs, name),
+        (Type::Double, true) => vector_column::<Float64Type>(recs, name),
+        (Type::String, true) => Err(DmapError::InvalidVector(format!(
+            "Vector field {name} cannot have type {data_type}"
+        ))),
+    }
+}
+
+/// Converts `recs` into a `RecordBatch` with one row per record and one column per field of
+/// `fields`, as described in the module documentation.
+///
+/// Returns `DmapError` if a field of a record does not have the `Type` listed in `fields`.
+pub fn records_to_arrow(
+    recs: &[IndexMap<String, DmapField>],
+    fields: &Fields,
+) -> Result<RecordBatch, DmapError> {
+    let columns = [
+        (&fields.scalars_required, false, false),
+        (&fields.scalars_optional, false, true),
+        (&fields.vectors_required, true, false),
+        (&fields.vectors_optional, true, true),
+    ];
+    let mut schema_fields: Vec<Field> = vec![];
+    let mut arrays: Vec<ArrayRef> = vec![];
+    for (group, is_vector, nullable) in columns {
+        for (name, data_type) in group.iter() {
+            let array = field_column(recs, name, data_type, is_vector)?;
+            schema_fields.push(Field::new(*name, array.data_type().clone(), nullable));
+            arrays.push(array);
+        }
+    }
+    RecordBatch::try_new(Arc::new(Schema::new(schema_fields)), arrays)
+        .map_err(|e| DmapError::InvalidRecord(format!("Cannot build record batch: {e}")))
+}
+
+/// Reads a FITACF file into a `RecordBatch` with one row per record and one column per FITACF
+/// field.
+pub fn read_fitacf_arrow(infile: &PathBuf) -> Result<RecordBatch, DmapError> {
+    let recs: Vec<_> = FitacfRecord::read_file(infile)?
+        .into_iter()
+        .map(|rec| rec.inner())
+        .collect();
+    records_to_arrow(&recs, fitacf_fields())
+}
+
+/// Downcasts the column `name` to a concrete array type.
+fn downcast<'a, A: 'static>(column: &'a dyn Array, name: &str) -> Result<&'a A, DmapError> {
+    column.as_any().downcast_ref::<A>().ok_or_else(|| {
+        DmapError::InvalidRecord(format!(
+            "Column {name} of type {} cannot be read",
+            column.data_type()
+        ))
+    })
+}
+
+/// Inserts the non-null entries of the primitive column `name` into the corresponding records.
+fn add_scalars<P: ArrowPrimitiveType>(
+    recs: &mut [IndexMap<String, DmapField>],
+    name: &str,
+    column: &dyn Array,
+) -> Result<(), DmapError>
+where
+    DmapField: From<P::Native>,
+{
+    let values = downcast::<PrimitiveArray<P>>(column, name)?;
+    for (rec, value) in recs.iter_mut().zip(values.iter()) {
+        if let Some(x) = value {
+            rec.insert(name.to_string(), DmapField::from(x));
+        }
+    }
+    Ok(())
+}
+
+/// Flattens a list entry, which is nested one level per dimension, into its shape and its
+/// elements in row-major order.
+fn flatten_list<P: ArrowPrimitiveType>(
+    list: &dyn Array,
+    name: &str,
+) -> Result<(Vec<usize>, Vec<P::Native>), DmapError> {
+    if let DataType::List(_) = list.data_type() {
+        let lists = downcast::<ListArray>(list, name)?;
+        let mut inner_shape: Option<Vec<usize>> = None;
+        let mut data = vec![];
+        for x in lists.iter() {
+            let x = x.ok_or_else(|| {
+                DmapError::InvalidVector(format!("Column {name} contains null nested lists"))
+            })?;
+            let (shape, elements) = flatten_list::<P>(&x, name)?;
+            if *inner_shape.get_or_insert_with(|| shape.clone()) != shape {
+                Err(DmapError::InvalidVector(format!(
+                    "Column {name} contains nested lists of different lengths"
+                )))?
+            }
+            data.extend(elements);
+        }
+        let mut shape = vec![lists.len()];
+        shape.extend(inner_shape.unwrap_or_default());
+        Ok((shape, data))
+    } else {
+        let values = downcast::<PrimitiveArray<P>>(list, name)?;
+        if values.null_count() > 0 {
+            Err(DmapError::InvalidVector(format!(
+                "Column {name} contains null vector elements"
+            )))?
+        }
+        Ok((vec![values.len()], values.values().to_vec()))
+    }
+}
+
+/// Inserts the non-null entries of the list column `name` into the corresponding records as
+/// vectors.
+fn add_vectors<P: ArrowPrimitiveType>(
+    recs: &mut [IndexMap<String, DmapField>],
+    name: &str,
+    column: &dyn Array,
+) -> Result<(), DmapError>
+where
+    DmapField: From<ArrayD<P::Native>>,
+{
+    let lists = downcast::<ListArray>(column, name)?;
+    for (rec, list) in recs.iter_mut().zip(lists.iter()) {
+        if let Some(x) = list {
+            let (shape, data) = flatten_list::<P>(&x, name)?;
+            let vector = ArrayD::from_shape_vec(IxDyn(&shape), data)
+                .map_err(|e| DmapError::InvalidVector(format!("Cannot read column {name}: {e}")))?;
+            rec.insert(name.to_string(), DmapField::from(vector));
+        }
+    }
+    Ok(())
+}
+
+/// Inserts the entries of the list column `name`, with elements of type `item_type`, into the
+/// corresponding records. Nested lists are descended to find the element type.
+fn add_vector_column(
+    recs: &mut [IndexMap<String, DmapField>],
+    name: &str,
+    column: &dyn Array,
+    item_type: &DataType,
+) -> Result<(), DmapError> {
+    match item_type {
+        DataType::List(x) => add_vector_column(recs, name, column, x.data_type()),
+        DataType::Int8 => add_vectors::<Int8Type>(recs, name, column),
+        DataType::Int16 => add_vectors::<Int16Type>(recs, name, column),
+        DataType::Int32 => add_vectors::<Int32Type>(recs, name, column),
+        DataType::Int64 => add_vectors::<Int64Type>(recs, name, column),
+        DataType::UInt8 => add_vectors::<UInt8Type>(recs, name, column),
+        DataType::UInt16 => add_vectors::<UInt16Type>(recs, name, column),
+        DataType::UInt32 => add_vectors::<UInt32Type>(recs, name, column),
+        DataType::UInt64 => add_vectors::<UInt64Type>(recs, name, column),
+        DataType::Float32 => add_vectors::<Float32Type>(recs, name, column),
+        DataType::Float64 => add_vectors::<Float64Type>(recs, name, column),
+        x => Err(DmapError::InvalidVector(format!(
+            "Column {name} has unsupported element type {x}"
+        ))),
+    }
+}
+
+/// Converts each row of `batch` into a record with one field per non-null column entry, as
+/// described in the module documentation.
+///
+/// The fields have the types of the columns, and are not checked against any format. Returns
+/// `DmapError` if a column has a type with no DMAP equivalent.
+pub fn records_from_arrow(
+    batch: &RecordBatch,
+) -> Result<Vec<IndexMap<String, DmapField>>, DmapError> {
+    let mut recs: Vec<IndexMap<String, DmapField>> = vec![IndexMap::new(); batch.num_rows()];
+    let schema = batch.schema();
+    for (field, column) in schema.fields().iter().zip(batch.columns()) {
+        let name = field.name().as_str();
+        let column = column.as_ref();
+        match column.data_type() {
+            DataType::Int8 => add_scalars::<Int8Type>(&mut recs, name, column)?,
+            DataType::Int16 => add_scalars::<Int16Type>(&mut recs, name, column)?,
+            DataType::Int32 => add_scalars::<Int32Type>(&mut recs, name, column)?,
+            DataType::Int64 => add_scalars::<Int64Type>(&mut recs, name, column)?,
+            DataType::UInt8 => add_scalars::<UInt8Type>(&mut recs, name, column)?,
+            DataType::UInt16 => add_scalars::<UInt16Type>(&mut recs, name, column)?,
+            DataType::UInt32 => add_scalars::<UInt32Type>(&mut recs, name, column)?,
+            DataType::UInt64 => add_scalars::<UInt64Type>(&mut recs, name, column)?,
+            DataType::Float32 => add_scalars::<Float32Type>(&mut recs, name, column)?,
+            DataType::Float64 => add_scalars::<Float64Type>(&mut recs, name, column)?,
+            DataType::Utf8 => {
+                let values = downcast::<StringArray>(column, name)?;
+                for (rec, value) in recs.iter_mut().zip(values.iter()) {
+                    if let Some(x) = value {
+                        rec.insert(name.to_string(), DmapField::from(x.to_string()));
+                    }
+                }
+            }
+            DataType::List(item) => add_vector_column(&mut recs, name, column, item.data_type())?,
+            x => Err(DmapError::InvalidRecord(format!(
+                "Column {name} has unsupported type {x}"
+            )))?,
+        }
+    }
+    Ok(recs)
+}
+
+/// Checks that `batch` has a column for each required field of `fields`.
+///
+/// Returns `DmapError::InvalidRecord` listing the missing columns, if any.
+fn check_required_columns(batch: &RecordBatch, fields: &Fields) -> Result<(), DmapError> {
+    let schema = batch.schema();
+    let missing: Vec<&str> = fields
+        .scalars_required
+        .iter()
+        .chain(fields.vectors_required.iter())
+        .map(|(name, _)| *name)
+        .filter(|name| schema.column_with_name(name).is_none())
+        .collect();
+    if !missing.is_empty() {
+        Err(DmapError::InvalidRecord(format!(
+            "Missing required columns {missing:?}"
+        )))?
+    }
+    Ok(())
+}
+
+/// Writes each row of `batch` to `outfile` as a FITACF record.
+///
+/// The fields of each row are coerced to the FITACF field types where possible, so columns may
+/// have e.g. a wider integer type than the field. Returns `DmapError::InvalidRecord` listing the
+/// missing columns if `batch` lacks any required FITACF field, or `DmapError` if a row is not a
+/// valid FITACF record.
+pub fn write_fitacf_arrow(batch: &RecordBatch, outfile: &PathBuf) -> Result<(), DmapError> {
+    check_required_columns(batch, fitacf_fields())?;
+    let recs = records_from_arrow(batch)?
+        .iter_mut()
+        .map(FitacfRecord::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+    write_fitacf(recs, outfile)
+}
@@ -0,0 +1,198 @@
+//! Field-by-field comparison of records, for regression-testing changes to processing.
+
+use crate::error::DmapError;
+use crate::formats::dmap::Record;
+use crate::types::{DmapField, DmapScalar, DmapVec};
+use indexmap::IndexMap;
+use numpy::ndarray::ArrayD;
+use std::path::PathBuf;
+
+/// A difference between a field of two records.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldDiff {
+    /// The field is only in the first record
+    OnlyInFirst(String),
+    /// The field is only in the second record
+    OnlyInSecond(String),
+    /// The field is a scalar in one record and a vector in the other, or has a different `Type`.
+    /// The kind and `Type` in each record are described as e.g. `"scalar SHORT"`.
+    Type {
+        name: String,
+        first: String,
+        second: String,
+    },
+    /// The vector field has different dimensions
+    Shape {
+        name: String,
+        first: Vec<usize>,
+        second: Vec<usize>,
+    },
+    /// The scalar field has different values
+    Scalar {
+        name: String,
+        first: DmapScalar,
+        second: DmapScalar,
+    },
+    /// The vector field has `num_different` elements with different values
+    Vector { name: String, num_different: usize },
+}
+
+/// The differences between the records at index `index` of two files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordDiff {
+    /// The index of the record within the files
+    pub index: usize,
+    /// The fields which differ
+    pub fields: Vec<FieldDiff>,
+}
+
+/// Whether two floating point values are equal to within `tolerance`. NaNs are equal to each
+/// other.
+fn floats_equal(x: f64, y: f64, tolerance: f64) -> bool {
+    (x.is_nan() && y.is_nan()) || x == y || (x - y).abs() <= tolerance
+}
+
+/// Counts the elements which differ between two arrays of the same shape.
+fn count_different<T: PartialEq>(first: &ArrayD<T>, second: &ArrayD<T>) -> usize {
+    first
+        .iter()
+        .zip(second.iter())
+        .filter(|(x, y)| x != y)
+        .count()
+}
+
+/// Counts the elements which differ by more than `tolerance` between two floating point arrays
+/// of the same shape.
+fn count_different_floats<T: Copy + Into<f64>>(
+    first: &ArrayD<T>,
+    second: &ArrayD<T>,
+    tolerance: f64,
+) -> usize {
+    first
+        .iter()
+        .zip(second.iter())
+        .filter(|(&x, &y)| !floats_equal(x.into(), y.into(), tolerance))
+        .count()
+}
+
+/// Describes the kind and `Type` of `field`, e.g. `"vector FLOAT"`.
+pub(crate) fn describe(field: &DmapField) -> String {
+    match field {
+        DmapField::Scalar(x) => format!("scalar {}", x.get_type()),
+        DmapField::Vector(x) => format!("vector {}", x.get_type()),
+    }
+}
+
+/// Compares two fields named `name`, returning their difference if any.
+fn diff_field(
+    name: &str,
+    first: &DmapField,
+    second: &DmapField,
+    tolerance: f64,
+) -> Option<FieldDiff> {
+    let type_diff = || FieldDiff::Type {
+        name: name.to_string(),
+        first: describe(first),
+        second: describe(second),
+    };
+    match (first, second) {
+        (DmapField::Scalar(x), DmapField::Scalar(y)) => {
+            let equal = match (x, y) {
+                (DmapScalar::Float(a), DmapScalar::Float(b)) => {
+                    floats_equal(*a as f64, *b as f64, tolerance)
+                }
+                (DmapScalar::Double(a), DmapScalar::Double(b)) => floats_equal(*a, *b, tolerance),
+                _ if x.get_type() != y.get_type() => return Some(type_diff()),
+                _ => x == y,
+            };
+            (!equal).then(|| FieldDiff::Scalar {
+                name: name.to_string(),
+                first: x.clone(),
+                second: y.clone(),
+            })
+        }
+        (DmapField::Vector(x), DmapField::Vector(y)) => {
+            if x.get_type() != y.get_type() {
+                return Some(type_diff());
+            }
+            if x.shape() != y.shape() {
+                return Some(FieldDiff::Shape {
+                    name: name.to_string(),
+                    first: x.shape().to_vec(),
+                    second: y.shape().to_vec(),
+                });
+            }
+            let num_different = match (x, y) {
+                (DmapVec::Char(a), DmapVec::Char(b)) => count_different(a, b),
+                (DmapVec::Short(a), DmapVec::Short(b)) => count_different(a, b),
+                (DmapVec::Int(a), DmapVec::Int(b)) => count_different(a, b),
+                (DmapVec::Long(a), DmapVec::Long(b)) => count_different(a, b),
+                (DmapVec::Uchar(a), DmapVec::Uchar(b)) => count_different(a, b),
+                (DmapVec::Ushort(a), DmapVec::Ushort(b)) => count_different(a, b),
+                (DmapVec::Uint(a), DmapVec::Uint(b)) => count_different(a, b),
+                (DmapVec::Ulong(a), DmapVec::Ulong(b)) => count_different(a, b),
+                (DmapVec::Float(a), DmapVec::Float(b)) => count_different_floats(a, b, tolerance),
+                (DmapVec::Double(a), DmapVec::Double(b)) => count_different_floats(a, b, tolerance),
+                _ => unreachable!("vectors have the same type"),
+            };
+            (num_different > 0).then(|| FieldDiff::Vector {
+                name: name.to_string(),
+                num_different,
+            })
+        }
+        _ => Some(type_diff()),
+    }
+}
+
+/// Compares the fields of two records. Floating point values are equal if they differ by no more
+/// than `tolerance`.
+///
+/// Fields in both records are listed in the order of `first`, followed by the fields only in
+/// `second`.
+pub fn diff_records(
+    first: &IndexMap<String, DmapField>,
+    second: &IndexMap<String, DmapField>,
+    tolerance: f64,
+) -> Vec<FieldDiff> {
+    let mut diffs: Vec<FieldDiff> = first
+        .iter()
+        .filter_map(|(name, x)| match second.get(name) {
+            Some(y) => diff_field(name, x, y, tolerance),
+            None => Some(FieldDiff::OnlyInFirst(name.clone())),
+        })
+        .collect();
+    diffs.extend(
+        second
+            .keys()
+            .filter(|name| !first.contains_key(*name))
+            .map(|name| FieldDiff::OnlyInSecond(name.clone())),
+    );
+    diffs
+}
+
+/// Reads two files of type `T` and compares them record by record, returning the differences
+/// for each record which differs. Floating point values are equal if they differ by no more than
+/// `tolerance`, or exactly equal if `tolerance` is `None`.
+///
+/// If one file has more records than the other, the extra records are reported with every field
+/// only in one file. Returns `DmapError` if either file cannot be read.
+pub fn diff_files<T: for<'a> Record<'a>>(
+    first: &PathBuf,
+    second: &PathBuf,
+    tolerance: Option<f64>,
+) -> Result<Vec<RecordDiff>, DmapError> {
+    let first_recs = T::read_file(first)?;
+    let second_recs = T::read_file(second)?;
+    let empty = IndexMap::new();
+    let num_recs = first_recs.len().max(second_recs.len());
+    Ok((0..num_recs)
+        .filter_map(|index| {
+            let fields = diff_records(
+                first_recs.get(index).map_or(&empty, |rec| rec.data()),
+                second_recs.get(index).map_or(&empty, |rec| rec.data()),
+                tolerance.unwrap_or(0.0),
+            );
+            (!fields.is_empty()).then_some(RecordDiff { index, fields })
+        })
+        .collect())
+}
@@ -1,5 +1,6 @@
 //! Error type for `dmap`.
 use pyo3::exceptions::{PyIOError, PyValueError};
+use pyo3::prelude::*;
 use pyo3::PyErr;
 use thiserror::Error;
 
@@ -15,7 +16,7 @@ pub enum DmapError {
     Io(#[from] std::io::Error),
 
     /// Invalid key for a DMAP type. Valid keys are defined [here](https://github.com/SuperDARN/rst/blob/main/codebase/general/src.lib/dmap.1.25/include/dmap.h)
-    #[error("{0}")]
+    #[error("Invalid type key {0}")]
     InvalidKey(i8),
 
     /// An issue with parsing a record. This is a broad error that is returned by higher-level
@@ -31,10 +32,104 @@ pub enum DmapError {
     #[error("{0}")]
     InvalidVector(String),
 
+    /// Error interpreting a field of a record. `record` is the index of the record within the
+    /// buffer or file being read and `byte` is the offset from the start of the buffer or file to
+    /// the start of the field. `reason` describes the problem without repeating the location.
+    #[error("Record {record}, field '{field}' at byte {byte}: {reason}")]
+    InvalidField {
+        record: usize,
+        field: String,
+        byte: u64,
+        reason: String,
+    },
+
+    /// Error interpreting the header of a record, such as an invalid code or numbers of scalars
+    /// and vectors which disagree with the fields found. `record` is the index of the record
+    /// within the buffer or file being read and `byte` is the offset from the start of the buffer
+    /// or file to the start of the record.
+    #[error("Record {record} at byte {byte}: {reason}")]
+    InvalidHeader {
+        record: usize,
+        byte: u64,
+        reason: String,
+    },
+
+    /// The final record declares a size of `expected` bytes, but only `available` bytes remain in
+    /// the buffer or file being read, e.g. because the file was cut short while being transferred.
+    /// `byte` is the offset from the start of the buffer or file to the start of the record, so
+    /// the records before it can still be read.
+    #[error("Record at byte {byte} is truncated: its size is {expected} bytes but only {available} remain")]
+    TruncatedFile {
+        expected: usize,
+        available: usize,
+        byte: u64,
+    },
+
     /// Errors when reading in multiple records
     #[error("First error: {1}\nRecords with errors: {0:?}")]
-    BadRecords(Vec<usize>, String)
+    BadRecords(Vec<usize>, Box<DmapError>)
+}
+
+impl DmapError {
+    /// Locates an `InvalidField`, `InvalidHeader` or `TruncatedFile` error within record `record`,
+    /// which starts at byte `offset` of the buffer or file being read. Other variants are returned
+    /// unchanged.
+    pub(crate) fn in_record(self, record: usize, offset: u64) -> Self {
+        match self {
+            DmapError::InvalidField {
+                field,
+                byte,
+                reason,
+                ..
+            } => DmapError::InvalidField {
+                record,
+                field,
+                byte: byte + offset,
+                reason,
+            },
+            DmapError::InvalidHeader { byte, reason, .. } => DmapError::InvalidHeader {
+                record,
+                byte: byte + offset,
+                reason,
+            },
+            DmapError::TruncatedFile {
+                expected,
+                available,
+                byte,
+            } => DmapError::TruncatedFile {
+                expected,
+                available,
+                byte: byte + offset,
+            },
+            e => e,
+        }
+    }
+}
+
+// pyo3 0.22 checks its own `gil-refs` feature inside the macro expansion
+#[allow(unexpected_cfgs)]
+mod exceptions {
+    use pyo3::create_exception;
+    use pyo3::exceptions::{PyIOError, PyValueError};
+
+    create_exception!(
+        dmap,
+        BadRecordsError,
+        PyValueError,
+        "Raised when some records of a batch being read or written are invalid. The `records` \
+        attribute lists the indices of the invalid records, and `message` describes the first \
+        error."
+    );
+    create_exception!(
+        dmap,
+        TruncatedFileError,
+        PyIOError,
+        "Raised when the final record of a file is cut short. The `byte` attribute is the offset \
+        of the truncated record, so the records before it are intact, and `expected` and \
+        `available` are the declared size of the record and the number of bytes remaining."
+    );
 }
+pub use exceptions::{BadRecordsError, TruncatedFileError};
 
 impl From<DmapError> for PyErr {
     fn from(value: DmapError) -> Self {
@@ -42,6 +137,36 @@ impl From<DmapError> for PyErr {
         match value {
             DmapError::CorruptStream(..) => PyIOError::new_err(msg),
             DmapError::Io(..) => PyIOError::new_err(msg),
+            // the message includes the record index and byte offset of the field
+            DmapError::InvalidField { .. } => PyValueError::new_err(msg),
+            DmapError::InvalidHeader { .. } => PyValueError::new_err(msg),
+            DmapError::BadRecords(records, first) => Python::with_gil(|py| {
+                let err = BadRecordsError::new_err(msg);
+                let exc = err.value_bound(py);
+                match exc
+                    .setattr("records", records)
+                    .and_then(|_| exc.setattr("message", first.to_string()))
+                {
+                    Ok(_) => err,
+                    Err(e) => e,
+                }
+            }),
+            DmapError::TruncatedFile {
+                expected,
+                available,
+                byte,
+            } => Python::with_gil(|py| {
+                let err = TruncatedFileError::new_err(msg);
+                let exc = err.value_bound(py);
+                match exc
+                    .setattr("expected", expected)
+                    .and_then(|_| exc.setattr("available", available))
+                    .and_then(|_| exc.setattr("byte", byte))
+                {
+                    Ok(_) => err,
+                    Err(e) => e,
+                }
+            }),
             _ => PyValueError::new_err(msg),
         }
     }
@@ -0,0 +1,168 @@
+//! Helpers for selecting a subset of the records of a file.
+//!
+//! The functions which take an `IndexMap` can be used as the predicate for
+//! `Record::read_file_filtered`, so that unwanted records are dropped as the file is read.
+
+use crate::error::DmapError;
+use crate::formats::dmap::{GenericRecord, Record};
+use crate::types::{get_field_as, DmapField};
+use indexmap::IndexMap;
+use time::format_description::FormatItem;
+use time::macros::format_description;
+use time::{Date, Month, OffsetDateTime, PrimitiveDateTime, Time};
+
+/// The format of `origin.time`, which RST writes with `ctime`, e.g. `Mon Jun  7 18:01:00 2021`.
+const CTIME_FORMAT: &[FormatItem<'static>] = format_description!(
+    "[weekday repr:short] [month repr:short] [day padding:space] [hour]:[minute]:[second] [year]"
+);
+
+/// Builds a UT time from the year, month, day, hour and minute fields `keys` of `data`, plus
+/// `second` seconds into the minute.
+///
+/// Returns `DmapError` if any of the fields is missing, has the wrong type, or is out of range.
+fn field_time(
+    data: &IndexMap<String, DmapField>,
+    keys: [&str; 5],
+    second: f64,
+) -> Result<OffsetDateTime, DmapError> {
+    let [year, month, day, hour, minute] = keys;
+    let component = |key: &str| -> Result<u8, DmapError> {
+        let value = get_field_as::<i16>(data, key)?;
+        u8::try_from(value)
+            .map_err(|_| DmapError::InvalidScalar(format!("Field {key} is out of range: {value}")))
+    };
+    let invalid = |e: time::error::ComponentRange| {
+        DmapError::InvalidScalar(format!("Invalid record time: {e}"))
+    };
+    let nanoseconds = (second * 1e9).round();
+    if !(0.0..60e9).contains(&nanoseconds) {
+        return Err(DmapError::InvalidScalar(format!(
+            "Invalid record time: second {second} is out of range"
+        )));
+    }
+    let nanoseconds = nanoseconds as u64;
+    let date = Date::from_calendar_date(
+        get_field_as::<i16>(data, year)? as i32,
+        Month::try_from(component(month)?).map_err(invalid)?,
+        component(day)?,
+    )
+    .map_err(invalid)?;
+    let time = Time::from_hms_nano(
+        component(hour)?,
+        component(minute)?,
+        (nanoseconds / 1_000_000_000) as u8,
+        (nanoseconds % 1_000_000_000) as u32,
+    )
+    .map_err(invalid)?;
+    Ok(PrimitiveDateTime::new(date, time).assume_utc())
+}
+
+/// Gets the UT time of a record.
+///
+/// The time is read from the `time.yr`, `time.mo`, `time.dy`, `time.hr`, `time.mt`, `time.sc`
+/// and `time.us` fields, or from `start.year`, `start.month`, `start.day`, `start.hour`,
+/// `start.minute` and `start.second` for grid and map records.
+///
+/// Returns `DmapError` if the record does not have either set of time fields, or they do not
+/// give a valid time.
+pub fn record_time(data: &IndexMap<String, DmapField>) -> Result<OffsetDateTime, DmapError> {
+    if data.contains_key("time.yr") {
+        let microsecond = get_field_as::<i32>(data, "time.us")?;
+        if !(0..1_000_000).contains(&microsecond) {
+            return Err(DmapError::InvalidScalar(format!(
+                "Field time.us is out of range: {microsecond}"
+            )));
+        }
+        let second = get_field_as::<i16>(data, "time.sc")? as f64 + microsecond as f64 * 1e-6;
+        field_time(
+            data,
+            ["time.yr", "time.mo", "time.dy", "time.hr", "time.mt"],
+            second,
+        )
+    } else {
+        field_time(
+            data,
+            [
+                "start.year",
+                "start.month",
+                "start.day",
+                "start.hour",
+                "start.minute",
+            ],
+            get_field_as::<f64>(data, "start.second")?,
+        )
+    }
+}
+
+/// Gets the UT time of a record. The same as `record_time`.
+pub fn record_timestamp(data: &IndexMap<String, DmapField>) -> Result<OffsetDateTime, DmapError> {
+    record_time(data)
+}
+
+/// Gets the UT end time of a record.
+///
+/// For grid and map records this is read from the `end.year`, `end.month`, `end.day`,
+/// `end.hour`, `end.minute` and `end.second` fields. Other records have no duration, so this is
+/// the same as `record_time`.
+///
+/// Returns `DmapError` if the record does not have the time fields, or they do not give a valid
+/// time.
+pub fn record_end_time(data: &IndexMap<String, DmapField>) -> Result<OffsetDateTime, DmapError> {
+    if !data.contains_key("end.year") {
+        return record_time(data);
+    }
+    field_time(
+        data,
+        ["end.year", "end.month", "end.day", "end.hour", "end.minute"],
+        get_field_as::<f64>(data, "end.second")?,
+    )
+}
+
+/// Parses the `origin.time` field of a record, the UT time at which the record was produced.
+///
+/// Returns `DmapError` if the record has no `origin.time` string, or it is not in the `ctime`
+/// format written by RST, e.g. `Mon Jun  7 18:01:00 2021`.
+pub fn parse_origin_time(data: &IndexMap<String, DmapField>) -> Result<OffsetDateTime, DmapError> {
+    let origin = get_field_as::<&str>(data, "origin.time")?;
+    PrimitiveDateTime::parse(origin.trim(), CTIME_FORMAT)
+        .map(PrimitiveDateTime::assume_utc)
+        .map_err(|e| DmapError::InvalidScalar(format!("Cannot parse origin.time '{origin}': {e}")))
+}
+
+/// Keeps the records with a time (see `record_time`) in the window `start <= time < end`.
+///
+/// Records without valid time fields are dropped.
+pub fn filter_by_time<'a, T: Record<'a>>(
+    records: Vec<T>,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+) -> Vec<T> {
+    records
+        .into_iter()
+        .filter(|rec| matches!(record_time(rec.data()), Ok(t) if start <= t && t < end))
+        .collect()
+}
+
+/// Removes records which are byte-identical to the record before them, as produced by some
+/// acquisition glitches.
+///
+/// Records are compared by their serialized bytes (see `Record::to_bytes`). Returns the remaining
+/// records and the number removed.
+pub fn dedup_records(
+    records: Vec<IndexMap<String, DmapField>>,
+) -> Result<(Vec<IndexMap<String, DmapField>>, usize), DmapError> {
+    let mut deduped: Vec<IndexMap<String, DmapField>> = vec![];
+    let mut previous: Option<Vec<u8>> = None;
+    let mut num_removed = 0;
+    for data in records {
+        let rec = GenericRecord { data };
+        let bytes = rec.to_bytes()?;
+        if previous.as_ref() == Some(&bytes) {
+            num_removed += 1;
+        } else {
+            deduped.push(rec.inner());
+            previous = Some(bytes);
+        }
+    }
+    Ok((deduped, num_removed))
+}
@@ -4,15 +4,377 @@
 //! checking that certain fields are or are not present, or have a given type.
 
 use crate::error::DmapError;
-use crate::types::{parse_scalar, parse_vector, read_data, DmapField, DmapType, DmapVec, Fields};
-use bzip2::read::BzDecoder;
+use crate::formats::RADAR_HEADER_SCALARS;
+use crate::types::{
+    parse_scalar, parse_vector, read_data, read_record_header, skip_scalar, skip_vector,
+    swap_record_endianness, DmapField, DmapType, DmapVec, Endianness, Fields, Type, DMAP_CODE,
+};
+use bzip2::read::MultiBzDecoder;
+use bzip2::write::BzEncoder;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use indexmap::map::Entry;
 use indexmap::IndexMap;
+use log::{debug, trace};
 use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::collections::HashMap;
 use std::ffi::OsStr;
-use std::fmt::Debug;
-use std::fs::File;
-use std::io::{Cursor, Read};
-use std::path::PathBuf;
+use std::fmt::{Debug, Display};
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, Cursor, ErrorKind, Read, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Instant;
+use zstd::stream::read::Decoder as ZstdDecoder;
+use zstd::stream::write::Encoder as ZstdEncoder;
+
+/// Opens `infile` for reading, decompressing the contents if the extension is `.bz2`, `.gz`
+/// or `.zst`. Compressed files may hold several concatenated streams, as written by appending.
+pub(crate) fn open_file(infile: &PathBuf) -> Result<Box<dyn Read>, DmapError> {
+    let file = File::open(infile)?;
+    match infile.extension() {
+        Some(ext) if ext == OsStr::new("bz2") => Ok(Box::new(MultiBzDecoder::new(file))),
+        Some(ext) if ext == OsStr::new("gz") => Ok(Box::new(MultiGzDecoder::new(file))),
+        Some(ext) if ext == OsStr::new("zst") => Ok(Box::new(ZstdDecoder::new(file)?)),
+        _ => Ok(Box::new(file)),
+    }
+}
+
+/// Records of at least this many bytes have their vector fields parsed in parallel, which pays
+/// off for files with few, very large records such as MAP files.
+const PARALLEL_VECTOR_SIZE: i32 = 1 << 20;
+
+/// Finds the byte where each of the `num_vectors` vector fields starting at `start` in `buffer`
+/// begins, and the byte after the last vector.
+fn vector_starts(
+    buffer: &[u8],
+    start: usize,
+    num_vectors: i32,
+) -> Result<(Vec<usize>, usize), DmapError> {
+    let mut starts: Vec<usize> = vec![];
+    let mut position = start;
+    for _ in 0..num_vectors {
+        starts.push(position);
+        skip_vector(buffer, &mut position)?;
+    }
+    Ok((starts, position))
+}
+
+/// Finds the number of scalar and vector fields which exactly fill `buffer[start..end]`, trying
+/// each number of scalars in turn. Returns `None` if no split of the bytes into at least one
+/// scalar followed by at least one vector fits.
+///
+/// This is only used to explain records which cannot be parsed with their declared counts.
+fn count_fields(buffer: &[u8], start: usize, end: usize) -> Option<(i32, i32)> {
+    let record = &buffer[..end];
+    let mut scalars_end = start;
+    let mut num_scalars = 0;
+    loop {
+        skip_scalar(record, &mut scalars_end).ok()?;
+        num_scalars += 1;
+        let mut position = scalars_end;
+        let mut num_vectors = 0;
+        while position < end && skip_vector(record, &mut position).is_ok() {
+            num_vectors += 1;
+        }
+        if position == end && num_vectors > 0 {
+            return Some((num_scalars, num_vectors));
+        }
+    }
+}
+
+/// Each field of a record type with its expected `Type`, whether it is a vector and whether it is
+/// required, in the order that fields are written: required scalars, optional scalars, required
+/// vectors, then optional vectors.
+fn schema<'b>(
+    fields_for_type: &'b Fields,
+) -> impl Iterator<Item = (&'b str, &'b Type, bool, bool)> {
+    let tag = |fields: &'b [(&'b str, Type)], is_vector: bool, required: bool| {
+        fields
+            .iter()
+            .map(move |(name, expected_type)| (*name, expected_type, is_vector, required))
+    };
+    tag(&fields_for_type.scalars_required, false, true)
+        .chain(tag(&fields_for_type.scalars_optional, false, false))
+        .chain(tag(&fields_for_type.vectors_required, true, true))
+        .chain(tag(&fields_for_type.vectors_optional, true, false))
+}
+
+/// Checks that `data` has no fields which are not part of the record type.
+fn check_supported(
+    data: &IndexMap<String, DmapField>,
+    fields_for_type: &Fields,
+) -> Result<(), DmapError> {
+    let unsupported_keys: Vec<&String> = data
+        .keys()
+        .filter(|&k| !fields_for_type.all_fields.contains(&&**k))
+        .collect();
+    if !unsupported_keys.is_empty() {
+        Err(DmapError::InvalidRecord(format!(
+            "Unsupported fields {:?}, fields supported are {:?}",
+            unsupported_keys, fields_for_type.all_fields
+        )))?
+    }
+    Ok(())
+}
+
+/// Gets the field `name` of `data`, checking that it is a vector if `is_vector` and a scalar
+/// otherwise. Returns `None` if the field is absent and not `required`.
+fn get_schema_field<'b>(
+    data: &'b IndexMap<String, DmapField>,
+    name: &str,
+    is_vector: bool,
+    required: bool,
+) -> Result<Option<&'b DmapField>, DmapError> {
+    match data.get(name) {
+        Some(DmapField::Scalar(_)) if is_vector => Err(DmapError::InvalidRecord(format!(
+            "Field {name} is a scalar, expected vector"
+        ))),
+        Some(DmapField::Vector(_)) if !is_vector => Err(DmapError::InvalidRecord(format!(
+            "Field {name} is a vector, expected scalar"
+        ))),
+        Some(x) => Ok(Some(x)),
+        None if required => Err(DmapError::InvalidRecord(format!(
+            "Field {name} missing: fields {:?}",
+            data.keys()
+        ))),
+        None => Ok(None),
+    }
+}
+
+/// Appends `fields` to `buf`, with the scalars in the order of `fields` followed by the vectors
+/// in the order of `fields`. Returns the number of scalar and vector fields written.
+fn fields_into<'b>(
+    fields: impl Iterator<Item = (&'b str, &'b DmapField)> + Clone,
+    buf: &mut Vec<u8>,
+) -> (i32, i32) {
+    let mut num_scalars: i32 = 0;
+    let mut num_vectors: i32 = 0;
+
+    // Do a first pass, to get all the scalar fields
+    for (name, val) in fields.clone() {
+        if let x @ DmapField::Scalar(_) = val {
+            buf.extend(name.as_bytes());
+            buf.extend([0]); // null-terminate string
+            buf.append(&mut x.as_bytes());
+            num_scalars += 1;
+        }
+    }
+    // Do a second pass to convert all the vector fields
+    for (name, val) in fields {
+        if let x @ DmapField::Vector(_) = val {
+            buf.extend(name.as_bytes());
+            buf.extend([0]); // null-terminate string
+            buf.append(&mut x.as_bytes());
+            num_vectors += 1;
+        }
+    }
+    (num_scalars, num_vectors)
+}
+
+/// Appends the fields of `data` to `buf`, with the scalars in the order of `data` followed by the
+/// vectors in the order of `data`. Returns the number of scalar and vector fields written.
+fn ordered_fields_into(data: &IndexMap<String, DmapField>, buf: &mut Vec<u8>) -> (i32, i32) {
+    fields_into(data.iter().map(|(name, x)| (name.as_str(), x)), buf)
+}
+
+/// Appends the fields of `data` named in `names` to `buf`, with the scalars in the order of
+/// `names` followed by the vectors in the order of `names`. Returns the number of scalar and
+/// vector fields written.
+///
+/// Returns `DmapError` if a field in `names` is not in `data` or is named more than once.
+fn listed_fields_into(
+    data: &IndexMap<String, DmapField>,
+    names: &[&str],
+    buf: &mut Vec<u8>,
+) -> Result<(i32, i32), DmapError> {
+    let mut fields: Vec<(&str, &DmapField)> = vec![];
+    for (i, &name) in names.iter().enumerate() {
+        if names[..i].contains(&name) {
+            Err(DmapError::InvalidRecord(format!(
+                "Field {name} is listed more than once"
+            )))?
+        }
+        let field = data.get(name).ok_or_else(|| {
+            DmapError::InvalidRecord(format!("Field {name} missing: fields {:?}", data.keys()))
+        })?;
+        fields.push((name, field));
+    }
+    Ok(fields_into(fields.into_iter(), buf))
+}
+
+/// Appends the fields of `data` to `buf` in the canonical order of `fields_for_type`, checking
+/// that each field has the right kind and that required fields are present. Returns the number
+/// of scalar and vector fields written.
+fn schema_fields_into(
+    data: &IndexMap<String, DmapField>,
+    fields_for_type: &Fields,
+    buf: &mut Vec<u8>,
+) -> Result<(i32, i32), DmapError> {
+    let mut num_scalars: i32 = 0;
+    let mut num_vectors: i32 = 0;
+
+    for (field, _, is_vector, required) in schema(fields_for_type) {
+        if let Some(x) = get_schema_field(data, field, is_vector, required)? {
+            buf.extend(field.as_bytes());
+            buf.extend([0]); // null-terminate string
+            buf.append(&mut x.as_bytes());
+            if is_vector {
+                num_vectors += 1;
+            } else {
+                num_scalars += 1;
+            }
+        }
+    }
+    Ok((num_scalars, num_vectors))
+}
+
+/// Appends a record to `buf`: the record header, followed by the fields appended by
+/// `write_fields`, which returns the number of scalar and vector fields it wrote.
+///
+/// If `write_fields` fails, `buf` is left as it was.
+fn record_into(
+    buf: &mut Vec<u8>,
+    write_fields: impl FnOnce(&mut Vec<u8>) -> Result<(i32, i32), DmapError>,
+) -> Result<(), DmapError> {
+    let start = buf.len();
+    buf.extend([0; 16]); // code, length, num_scalars, num_vectors, filled in below
+    let (num_scalars, num_vectors) = match write_fields(buf) {
+        Ok(x) => x,
+        Err(e) => {
+            buf.truncate(start);
+            return Err(e);
+        }
+    };
+    let size = (buf.len() - start) as i32;
+    buf[start..start + 4].copy_from_slice(&DMAP_CODE.to_le_bytes());
+    buf[start + 4..start + 8].copy_from_slice(&size.to_le_bytes());
+    buf[start + 8..start + 12].copy_from_slice(&num_scalars.to_le_bytes());
+    buf[start + 12..start + 16].copy_from_slice(&num_vectors.to_le_bytes());
+    Ok(())
+}
+
+/// Adds the field `name`, which starts at byte `byte`, to `fields`.
+///
+/// Returns `DmapError::InvalidField` if `fields` already has a field `name`, as a record must not
+/// repeat a field.
+fn insert_unique(
+    fields: &mut IndexMap<String, DmapField>,
+    name: String,
+    value: DmapField,
+    byte: u64,
+) -> Result<(), DmapError> {
+    match fields.entry(name) {
+        Entry::Occupied(x) => Err(DmapError::InvalidField {
+            record: 0,
+            field: x.key().clone(),
+            byte,
+            reason: "Duplicate field".to_string(),
+        }),
+        Entry::Vacant(x) => {
+            x.insert(value);
+            Ok(())
+        }
+    }
+}
+
+/// The byte offset where each record starts, paired with the result of parsing the record.
+type ParseResults<T> = Vec<(usize, Result<Option<T>, DmapError>)>;
+
+/// Splits an in-memory buffer of DMAP data into records and parses each of them, returning the
+/// byte offset into `buffer` where each record starts alongside the result of parsing it. The
+/// result is `None` for records which `predicate` rejects.
+///
+/// Splitting stops at the first record with a size too small or extending past the end of
+/// `buffer`, as the start of the next record cannot be found.
+fn parse_records_partial<'a, T, F>(
+    buffer: &[u8],
+    predicate: F,
+) -> Result<ParseResults<T>, DmapError>
+where
+    T: Record<'a>,
+    F: Fn(&IndexMap<String, DmapField>) -> bool + Sync,
+{
+    let mut offsets: Vec<usize> = vec![];
+    let mut slices: Vec<_> = vec![];
+    let mut rec_start: usize = 0;
+    while rec_start + 2 * i32::size() < buffer.len() {
+        let rec_size = read_record_header(&buffer[rec_start..])?.1;
+        trace!(
+            "Record {} at byte {rec_start} has size {rec_size}",
+            offsets.len()
+        );
+        offsets.push(rec_start);
+        if rec_size <= 2 * i32::size() as i32 || rec_start + rec_size as usize > buffer.len() {
+            // error-checking the size is conducted in T::parse_record()
+            slices.push(Cursor::new(&buffer[rec_start..]));
+            break;
+        }
+        let rec_end = rec_start + rec_size as usize;
+        slices.push(Cursor::new(&buffer[rec_start..rec_end]));
+        rec_start = rec_end;
+    }
+    let mut dmap_results: Vec<Result<Option<T>, DmapError>> = vec![];
+    dmap_results.par_extend(
+        slices
+            .par_iter_mut()
+            .map(|cursor| T::parse_record(cursor).map(|rec| predicate(rec.data()).then_some(rec))),
+    );
+    Ok(offsets
+        .into_iter()
+        .zip(dmap_results)
+        .enumerate()
+        .map(|(i, (offset, res))| (offset, res.map_err(|e| e.in_record(i, offset as u64))))
+        .collect())
+}
+
+/// Parses an in-memory buffer of DMAP data, keeping only the records for which `predicate` returns
+/// `true`, each paired with the byte offset into `buffer` where the record starts.
+fn parse_records_filtered<'a, T, F>(
+    buffer: &[u8],
+    predicate: F,
+) -> Result<Vec<(usize, T)>, DmapError>
+where
+    T: Record<'a>,
+    F: Fn(&IndexMap<String, DmapField>) -> bool + Sync,
+{
+    let start = Instant::now();
+    let mut dmap_records: Vec<(usize, T)> = vec![];
+    let mut bad_recs: Vec<usize> = vec![];
+    let mut dmap_errors: Vec<DmapError> = vec![];
+    let results = parse_records_partial(buffer, predicate)?;
+    for (i, (offset, rec)) in results.into_iter().enumerate() {
+        match rec {
+            Ok(Some(x)) => dmap_records.push((offset, x)),
+            Ok(None) => {}
+            Err(e) => {
+                dmap_errors.push(e);
+                bad_recs.push(i);
+            },
+        }
+    }
+    debug!(
+        "Parsed {} records from {} bytes in {:?}, {} invalid",
+        dmap_records.len(),
+        buffer.len(),
+        start.elapsed(),
+        dmap_errors.len()
+    );
+    // Only the final record can be truncated, so if it is the only bad record the ones before it
+    // are intact
+    if let [DmapError::TruncatedFile { .. }] = dmap_errors.as_slice() {
+        return Err(dmap_errors.swap_remove(0));
+    }
+    if !dmap_errors.is_empty() {
+        return Err(DmapError::BadRecords(
+            bad_recs,
+            Box::new(dmap_errors.swap_remove(0)),
+        ));
+    }
+    Ok(dmap_records)
+}
 
 pub trait Record<'a>:
     Debug + Send + TryFrom<&'a mut IndexMap<String, DmapField>, Error = DmapError>
@@ -20,6 +382,50 @@ pub trait Record<'a>:
     /// Gets the underlying data of the Record.
     fn inner(self) -> IndexMap<String, DmapField>;
 
+    /// Borrows the underlying data of the Record.
+    fn data(&self) -> &IndexMap<String, DmapField>;
+
+    /// Describes the name, `Type` and kind of each field, and the shape of each vector field,
+    /// without printing vector data.
+    fn summary(&self) -> String {
+        summarize(self.data())
+    }
+
+    /// Whether `self` and `other` have the same fields, with equal scalars and vectors which are
+    /// equal to within tolerance, as defined by `DmapVec::approx_eq`.
+    ///
+    /// Unlike `==`, this tolerates small differences in floating point vectors, such as those
+    /// from a round trip through another precision or from comparing against reference output.
+    /// The order of the fields does not matter.
+    fn approx_eq(&self, other: &Self, rel_tol: f32, abs_tol: f32) -> bool {
+        let (data, other_data) = (self.data(), other.data());
+        data.len() == other_data.len()
+            && data
+                .iter()
+                .all(|(name, x)| match (x, other_data.get(name)) {
+                    (DmapField::Vector(a), Some(DmapField::Vector(b))) => {
+                        a.approx_eq(b, rel_tol, abs_tol)
+                    }
+                    (a, b) => Some(a) == b,
+                })
+    }
+
+    /// Copies the radar header scalars of the record, such as `stid`, `time.*`, `bmnum` and
+    /// `cp`, which are shared by the IQDAT, RAWACF, FITACF and SND formats.
+    ///
+    /// The result contains no format-specific fields, so it can be extended with the fields of
+    /// another format to construct a record of that format.
+    fn shared_header(&self) -> IndexMap<String, DmapField> {
+        RADAR_HEADER_SCALARS
+            .iter()
+            .filter_map(|&name| {
+                self.data()
+                    .get(name)
+                    .map(|value| (name.to_string(), value.clone()))
+            })
+            .collect()
+    }
+
     /// Reads from dmap_data and parses into a collection of Records.
     ///
     /// Returns `DmapError` if dmap_data cannot be read or contains invalid data.
@@ -28,73 +434,266 @@ pub trait Record<'a>:
         Self: Sized,
         Self: Send,
     {
+        let start = Instant::now();
         let mut buffer: Vec<u8> = vec![];
         dmap_data.read_to_end(&mut buffer)?;
+        debug!("Read {} bytes in {:?}", buffer.len(), start.elapsed());
+        Self::read_records_from_bytes(buffer)
+    }
 
-        let mut slices: Vec<_> = vec![];
-        let mut rec_start: usize = 0;
-        let mut rec_size: usize;
-        let mut rec_end: usize;
-        while ((rec_start + 2 * i32::size()) as u64) < buffer.len() as u64 {
-            rec_size = i32::from_le_bytes(buffer[rec_start + 4..rec_start + 8].try_into().unwrap())
-                as usize; // advance 4 bytes, skipping the "code" field
-            rec_end = rec_start + rec_size; // error-checking the size is conducted in Self::parse_record()
-            slices.push(Cursor::new(buffer[rec_start..rec_end].to_vec()));
-            rec_start = rec_end;
-        }
-        let mut dmap_results: Vec<Result<Self, DmapError>> = vec![];
-        dmap_results.par_extend(
-            slices
-                .par_iter_mut()
-                .map(|cursor| Self::parse_record(cursor)),
-        );
+    /// Reads from a boxed reader and parses into a collection of Records.
+    ///
+    /// This is `read_records` for callers which compose readers at runtime, e.g. choosing a
+    /// decompressor based on a file extension, or reading from a source other than the local
+    /// filesystem. Networking is left to the caller: any object implementing `Read`, such as a
+    /// `reqwest::blocking::Response` or a buffered `object_store` download, can be boxed and read
+    /// without first staging it to disk. The data must be uncompressed, so wrap the reader in the
+    /// appropriate decoder (e.g. `bzip2::read::BzDecoder`) first if needed.
+    ///
+    /// Returns `DmapError` if dmap_data cannot be read or contains invalid data.
+    fn read_records_boxed(dmap_data: Box<dyn Read + '_>) -> Result<Vec<Self>, DmapError>
+    where
+        Self: Sized,
+        Self: Send,
+    {
+        Self::read_records(dmap_data)
+    }
 
+    /// Reads from dmap_data and parses into a collection of Records, continuing past invalid
+    /// records.
+    ///
+    /// Returns the valid records, along with the index and error of each invalid record. Unlike
+    /// `read_records`, one invalid record does not prevent the others from being read, unless its
+    /// size is invalid, as the start of the next record cannot then be found.
+    fn read_records_recover(mut dmap_data: impl Read) -> (Vec<Self>, Vec<(usize, DmapError)>)
+    where
+        Self: Sized,
+        Self: Send,
+    {
+        let mut buffer: Vec<u8> = vec![];
+        if let Err(e) = dmap_data.read_to_end(&mut buffer) {
+            return (vec![], vec![(0, DmapError::from(e))]);
+        }
+        let results = match parse_records_partial(&buffer, |_| true) {
+            Ok(x) => x,
+            Err(e) => return (vec![], vec![(0, e)]),
+        };
         let mut dmap_records: Vec<Self> = vec![];
-        let mut bad_recs: Vec<usize> = vec![];
-        let mut dmap_errors: Vec<DmapError> = vec![];
-        for (i, rec) in dmap_results.into_iter().enumerate() {
+        let mut dmap_errors: Vec<(usize, DmapError)> = vec![];
+        for (i, (_, rec)) in results.into_iter().enumerate() {
             match rec {
-                Ok(x) => dmap_records.push(x),
-                Err(e) => {
-                    dmap_errors.push(e);
-                    bad_recs.push(i);
-                },
+                Ok(Some(x)) => dmap_records.push(x),
+                Ok(None) => {}
+                Err(e) => dmap_errors.push((i, e)),
             }
         }
-        if dmap_errors.len() > 0 {
-            return Err(DmapError::BadRecords(bad_recs, dmap_errors[0].to_string()))
-        }
-        Ok(dmap_records)
+        (dmap_records, dmap_errors)
+    }
+
+    /// Parses an in-memory buffer of DMAP data into a collection of Records.
+    ///
+    /// Returns `DmapError` if `buffer` contains invalid data.
+    fn read_records_from_bytes(buffer: Vec<u8>) -> Result<Vec<Self>, DmapError>
+    where
+        Self: Sized,
+        Self: Send,
+    {
+        let indexed_recs = Self::read_indexed_records_from_bytes(buffer)?;
+        Ok(indexed_recs.into_iter().map(|(_, rec)| rec).collect())
+    }
+
+    /// Parses an in-memory buffer of DMAP data into a collection of Records, each paired with
+    /// the byte offset into `buffer` where the record starts.
+    ///
+    /// Returns `DmapError` if `buffer` contains invalid data.
+    fn read_indexed_records_from_bytes(buffer: Vec<u8>) -> Result<Vec<(usize, Self)>, DmapError>
+    where
+        Self: Sized,
+        Self: Send,
+    {
+        parse_records_filtered(&buffer, |_| true)
     }
 
     /// Read a DMAP file of type `Self`
+    ///
+    /// With the `mmap` feature, uncompressed files are memory-mapped and parsed in place rather
+    /// than read into a buffer first.
     fn read_file(infile: &PathBuf) -> Result<Vec<Self>, DmapError>
     where
         Self: Sized,
         Self: Send,
     {
-        let file = File::open(infile)?;
-        match infile.extension() {
-            Some(ext) if ext == OsStr::new("bz2") => {
-                let compressor = BzDecoder::new(file);
-                Self::read_records(compressor)
+        debug!("Reading {}", infile.display());
+        #[cfg(feature = "mmap")]
+        if !matches!(infile.extension(), Some(ext) if ext == "bz2" || ext == "gz" || ext == "zst") {
+            let file = File::open(infile)?;
+            // SAFETY: the map is only read while parsing, and modifying a file while it is being
+            // read is unsupported with the buffered reader too.
+            let mmap = unsafe { memmap2::Mmap::map(&file)? };
+            let indexed_recs = parse_records_filtered(&mmap, |_| true)?;
+            return Ok(indexed_recs.into_iter().map(|(_, rec)| rec).collect());
+        }
+        Self::read_records(open_file(infile)?)
+    }
+
+    /// Read a DMAP file of type `Self`, parsing the records on `pool` rather than the global rayon
+    /// pool.
+    ///
+    /// The calling thread blocks until the read is finished. See `ReadOptions::threads` to read
+    /// on a pool created for the read.
+    fn read_file_in_pool(infile: &PathBuf, pool: &ThreadPool) -> Result<Vec<Self>, DmapError>
+    where
+        Self: Sized,
+        Self: Send,
+    {
+        pool.install(|| Self::read_file(infile))
+    }
+
+    /// Read a DMAP file of type `Self`, pairing each record with the byte offset where it starts
+    /// in the (decompressed) file.
+    fn read_file_indexed(infile: &PathBuf) -> Result<Vec<(usize, Self)>, DmapError>
+    where
+        Self: Sized,
+        Self: Send,
+    {
+        let mut buffer: Vec<u8> = vec![];
+        open_file(infile)?.read_to_end(&mut buffer)?;
+        Self::read_indexed_records_from_bytes(buffer)
+    }
+
+    /// Read a DMAP file of type `Self`, stopping at the first invalid record.
+    ///
+    /// Returns the records before the first invalid record, and the byte offset in the
+    /// (decompressed) file where the invalid record starts, or `None` if every record is valid.
+    /// This recovers the intact records of a file which was truncated while being written.
+    fn read_file_lax(infile: &PathBuf) -> Result<(Vec<Self>, Option<usize>), DmapError>
+    where
+        Self: Sized,
+        Self: Send,
+    {
+        let mut buffer: Vec<u8> = vec![];
+        open_file(infile)?.read_to_end(&mut buffer)?;
+        let mut dmap_records: Vec<Self> = vec![];
+        for (offset, rec) in parse_records_partial(&buffer, |_| true)? {
+            match rec {
+                Ok(Some(x)) => dmap_records.push(x),
+                Ok(None) => {}
+                Err(_) => return Ok((dmap_records, Some(offset))),
             }
-            _ => Self::read_records(file),
         }
+        Ok((dmap_records, None))
     }
 
-    /// Reads a record starting from cursor position
-    fn parse_record(cursor: &mut Cursor<Vec<u8>>) -> Result<Self, DmapError>
+    /// Read a DMAP file of type `Self`, keeping only the records for which `predicate` returns
+    /// `true`.
+    ///
+    /// Every record is still parsed and checked, so corrupted records are errors even if
+    /// `predicate` would have rejected them.
+    fn read_file_filtered<F>(infile: &PathBuf, predicate: F) -> Result<Vec<Self>, DmapError>
+    where
+        Self: Sized,
+        Self: Send,
+        F: Fn(&IndexMap<String, DmapField>) -> bool + Sync,
+    {
+        let mut buffer: Vec<u8> = vec![];
+        open_file(infile)?.read_to_end(&mut buffer)?;
+        let indexed_recs = parse_records_filtered(&buffer, predicate)?;
+        Ok(indexed_recs.into_iter().map(|(_, rec)| rec).collect())
+    }
+
+    /// Lazily reads a DMAP file of type `Self`, parsing one record at a time.
+    ///
+    /// Unlike `read_file`, only one record is held in memory at a time, so the iterator can be
+    /// used with `filter`, `take` etc. on files too large to read at once.
+    /// Returns `DmapError` if `infile` cannot be opened.
+    fn iter_records(infile: &PathBuf) -> Result<RecordIter<Self>, DmapError>
+    where
+        Self: Sized,
+    {
+        Ok(RecordIter::new(open_file(infile)?))
+    }
+
+    /// Read at most the first `n` records of a DMAP file of type `Self`.
+    ///
+    /// Records are parsed lazily, so the rest of the file is never parsed. Returns `DmapError` if
+    /// `infile` cannot be opened or one of the first `n` records is invalid.
+    fn read_file_limited(infile: &PathBuf, n: usize) -> Result<Vec<Self>, DmapError>
+    where
+        Self: Sized,
+    {
+        if n == 0 {
+            return Ok(vec![]);
+        }
+        Self::iter_records(infile)?.take(n).collect()
+    }
+
+    /// Checks that every record of a DMAP file of type `Self` is valid, without keeping any
+    /// records in memory.
+    ///
+    /// Returns the number of records, or a `DmapError` with the index and starting byte of the
+    /// first invalid record.
+    fn validate_file(infile: &PathBuf) -> Result<usize, DmapError>
+    where
+        Self: Sized,
+    {
+        let mut records = Self::iter_records(infile)?;
+        let mut count = 0;
+        loop {
+            let offset = records.offset;
+            match records.next() {
+                None => return Ok(count),
+                Some(Ok(_)) => count += 1,
+                Some(Err(e @ DmapError::InvalidField { .. })) => Err(e)?,
+                Some(Err(e @ DmapError::InvalidHeader { .. })) => Err(e)?,
+                Some(Err(e @ DmapError::TruncatedFile { .. })) => Err(e)?,
+                Some(Err(e)) => Err(DmapError::InvalidRecord(format!(
+                    "Record {count} at byte {offset}: {e}"
+                )))?,
+            }
+        }
+    }
+
+    /// Reads a record starting from cursor position.
+    ///
+    /// The record is parsed from the borrowed buffer without copying it, except for big-endian
+    /// records, which are converted to little-endian in a copy.
+    fn parse_record(cursor: &mut Cursor<&[u8]>) -> Result<Self, DmapError>
     where
         Self: Sized,
     {
         let bytes_already_read = cursor.position();
-        let _code = read_data::<i32>(cursor).map_err(|e| {
+        let start = bytes_already_read as usize;
+        let buffer: &[u8] = cursor.get_ref();
+        if let Some(Ok((Endianness::Big, size))) = buffer.get(start..).map(read_record_header) {
+            let end = match usize::try_from(size) {
+                Ok(x) if x > 0 && start + x <= buffer.len() => start + x,
+                _ => buffer.len(),
+            };
+            let mut record = buffer[start..end].to_vec();
+            swap_record_endianness(&mut record).map_err(|e| {
+                DmapError::InvalidRecord(format!(
+                    "Cannot convert big-endian record at byte {start}: {e}"
+                ))
+            })?;
+            let mut record_cursor = Cursor::new(record.as_slice());
+            let rec = Self::parse_record(&mut record_cursor)
+                .map_err(|e| e.in_record(0, bytes_already_read))?;
+            cursor.set_position(bytes_already_read + record_cursor.position());
+            return Ok(rec);
+        }
+        let code = read_data::<i32>(cursor).map_err(|e| {
             DmapError::InvalidRecord(format!(
                 "Cannot interpret code at byte {}: {e}",
                 bytes_already_read
             ))
         })?;
+        if code != DMAP_CODE {
+            return Err(DmapError::InvalidHeader {
+                record: 0,
+                byte: bytes_already_read,
+                reason: format!("Invalid code {code}, expected {DMAP_CODE}"),
+            });
+        }
         let size = read_data::<i32>(cursor).map_err(|e| {
             DmapError::InvalidRecord(format!(
                 "Cannot interpret size at byte {}: {e}",
@@ -102,16 +701,19 @@ pub trait Record<'a>:
             ))
         })?;
 
-        // adding 8 bytes because code and size are part of the record.
-        if size as u64 > cursor.get_ref().len() as u64 - cursor.position() + 2 * i32::size() as u64
-        {
+        // the size includes the code, size and number of scalars and vectors
+        let remaining = cursor.get_ref().len() - start;
+        if size < 4 * i32::size() as i32 {
             return Err(DmapError::InvalidRecord(format!(
-                "Record size {size} at byte {} bigger than remaining buffer {}",
-                cursor.position() - i32::size() as u64,
-                cursor.get_ref().len() as u64 - cursor.position() + 2 * i32::size() as u64
+                "Record size {size} at byte {} is smaller than the record header",
+                start + i32::size()
             )));
-        } else if size <= 0 {
-            return Err(DmapError::InvalidRecord(format!("Record size {size} <= 0")));
+        } else if size as usize > remaining {
+            return Err(DmapError::TruncatedFile {
+                expected: size as usize,
+                available: remaining,
+                byte: bytes_already_read,
+            });
         }
 
         let num_scalars = read_data::<i32>(cursor).map_err(|e| {
@@ -141,32 +743,128 @@ pub trait Record<'a>:
                 "Number of scalars {num_scalars} plus vectors {num_vectors} greater than size '{size}'")));
         }
 
+        // if the fields cannot be parsed, check whether the declared counts are the cause
+        let buffer: &[u8] = cursor.get_ref();
+        let fields_start = cursor.position() as usize;
+        let wrong_counts =
+            |e: DmapError| match count_fields(buffer, fields_start, start + size as usize) {
+                Some((found_scalars, found_vectors))
+                    if (found_scalars, found_vectors) != (num_scalars, num_vectors) =>
+                {
+                    let mut counts: Vec<String> = vec![];
+                    if found_scalars != num_scalars {
+                        counts.push(format!(
+                            "expected {num_scalars} scalars, found {found_scalars}"
+                        ));
+                    }
+                    if found_vectors != num_vectors {
+                        counts.push(format!(
+                            "expected {num_vectors} vectors, found {found_vectors}"
+                        ));
+                    }
+                    DmapError::InvalidHeader {
+                        record: 0,
+                        byte: bytes_already_read,
+                        reason: format!("Wrong number of fields: {}", counts.join(", ")),
+                    }
+                }
+                _ => e,
+            };
+
         let mut fields: IndexMap<String, DmapField> = IndexMap::new();
         for _ in 0..num_scalars {
-            let (name, val) = parse_scalar(cursor)?;
-            fields.insert(name, val);
+            let field_start = cursor.position();
+            let (name, val) = parse_scalar(cursor).map_err(wrong_counts)?;
+            insert_unique(&mut fields, name, val, field_start)?;
         }
-        for _ in 0..num_vectors {
-            let (name, val) = parse_vector(cursor, size)?;
-            fields.insert(name, val);
+        let vector_bounds = if size >= PARALLEL_VECTOR_SIZE {
+            vector_starts(buffer, cursor.position() as usize, num_vectors).ok()
+        } else {
+            None
+        };
+        match vector_bounds {
+            Some((starts, end)) => {
+                let vectors: Vec<_> = starts
+                    .into_par_iter()
+                    .map(|start| {
+                        let mut vector_cursor = Cursor::new(buffer);
+                        vector_cursor.set_position(start as u64);
+                        (start, parse_vector(&mut vector_cursor, size))
+                    })
+                    .collect();
+                for (field_start, vector) in vectors {
+                    let (name, val) = vector.map_err(wrong_counts)?;
+                    insert_unique(&mut fields, name, val, field_start as u64)?;
+                }
+                cursor.set_position(end as u64);
+            }
+            // parse serially, which also reports the exact error for malformed vectors
+            None => {
+                for _ in 0..num_vectors {
+                    let field_start = cursor.position();
+                    let (name, val) = parse_vector(cursor, size).map_err(wrong_counts)?;
+                    insert_unique(&mut fields, name, val, field_start)?;
+                }
+            }
         }
 
         if cursor.position() - bytes_already_read != size as u64 {
-            return Err(DmapError::InvalidRecord(format!(
+            return Err(wrong_counts(DmapError::InvalidRecord(format!(
                 "Bytes read {} does not match the records size field {}",
                 cursor.position() - bytes_already_read,
                 size
-            )));
+            ))));
         }
 
         Self::new(&mut fields)
     }
 
+    /// Serializes the record to a JSON object mapping field names to fields, in the format
+    /// described in the `json` module.
+    #[cfg(feature = "serde")]
+    fn to_json(&self) -> Result<String, DmapError> {
+        serde_json::to_string(self.data())
+            .map_err(|e| DmapError::InvalidRecord(format!("Cannot serialize record to JSON: {e}")))
+    }
+
+    /// Deserializes a record from JSON produced by `to_json`.
+    ///
+    /// Returns `DmapError` if `json` is malformed or the fields are not valid for `Self`.
+    #[cfg(feature = "serde")]
+    fn from_json(json: &str) -> Result<Self, DmapError>
+    where
+        Self: Sized,
+    {
+        let mut fields: IndexMap<String, DmapField> = serde_json::from_str(json)
+            .map_err(|e| DmapError::InvalidRecord(format!("Invalid JSON record: {e}")))?;
+        Self::new(&mut fields)
+    }
+
     /// Creates a new object from the parsed scalars and vectors
     fn new(fields: &mut IndexMap<String, DmapField>) -> Result<Self, DmapError>
     where
         Self: Sized;
 
+    /// The names and types of the fields of `Self`, or `None` if `Self` accepts any fields.
+    fn fields() -> Option<&'static Fields<'static>>
+    where
+        Self: Sized;
+
+    /// Renames legacy spellings of fields in `fields` to their current names, before the fields
+    /// are checked. Does nothing for formats without legacy field names.
+    fn rename_legacy_fields(_fields: &mut IndexMap<String, DmapField>)
+    where
+        Self: Sized,
+    {
+    }
+
+    /// Creates a new object from `fields` without checking that they are valid for `Self`.
+    ///
+    /// Writing the record may fail or drop fields if `fields` are not valid.
+    fn new_unchecked(fields: IndexMap<String, DmapField>) -> Self
+    where
+        Self: Sized;
+
     /// Checks the validity of an `IndexMap` as a representation of a DMAP record.
     ///
     /// Validity checks include ensuring that no unfamiliar entries exist, that all required
@@ -177,82 +875,15 @@ pub trait Record<'a>:
         field_dict: &mut IndexMap<String, DmapField>,
         fields_for_type: &Fields,
     ) -> Result<(), DmapError> {
-        let unsupported_keys: Vec<&String> = field_dict
-            .keys()
-            .filter(|&k| !fields_for_type.all_fields.contains(&&**k))
-            .collect();
-        if !unsupported_keys.is_empty() {
-            Err(DmapError::InvalidRecord(format!(
-                "Unsupported fields {:?}, fields supported are {:?}",
-                unsupported_keys, fields_for_type.all_fields
-            )))?
-        }
-
-        for (field, expected_type) in fields_for_type.scalars_required.iter() {
-            match field_dict.get(&field.to_string()) {
-                Some(DmapField::Scalar(x)) if &x.get_type() == expected_type => {}
-                Some(DmapField::Scalar(x)) => Err(DmapError::InvalidRecord(format!(
-                    "Field {} has incorrect type {}, expected {}",
-                    field,
-                    x.get_type(),
-                    expected_type
-                )))?,
-                Some(_) => Err(DmapError::InvalidRecord(format!(
-                    "Field {} is a vector, expected scalar",
-                    field
-                )))?,
-                None => Err(DmapError::InvalidRecord(format!(
-                    "Field {field:?} ({:?}) missing: fields {:?}",
-                    &field.to_string(),
-                    field_dict.keys()
-                )))?,
-            }
-        }
-        for (field, expected_type) in fields_for_type.scalars_optional.iter() {
-            match field_dict.get(&field.to_string()) {
-                Some(DmapField::Scalar(x)) if &x.get_type() == expected_type => {}
-                Some(DmapField::Scalar(x)) => Err(DmapError::InvalidRecord(format!(
-                    "Field {} has incorrect type {}, expected {}",
-                    field,
-                    x.get_type(),
-                    expected_type
-                )))?,
-                Some(_) => Err(DmapError::InvalidRecord(format!(
-                    "Field {} is a vector, expected scalar",
-                    field
-                )))?,
-                None => {}
-            }
-        }
-        for (field, expected_type) in fields_for_type.vectors_required.iter() {
-            match field_dict.get(&field.to_string()) {
-                Some(DmapField::Scalar(_)) => Err(DmapError::InvalidRecord(format!(
-                    "Field {} is a scalar, expected vector",
-                    field
-                )))?,
-                Some(DmapField::Vector(x)) if &x.get_type() != expected_type => {
-                    Err(DmapError::InvalidRecord(format!(
-                        "Field {field} has incorrect type {:?}, expected {expected_type:?}",
-                        x.get_type()
-                    )))?
-                }
-                Some(&DmapField::Vector(_)) => {}
-                None => Err(DmapError::InvalidRecord(format!("Field {field} missing")))?,
-            }
-        }
-        for (field, expected_type) in fields_for_type.vectors_optional.iter() {
-            match field_dict.get(&field.to_string()) {
-                Some(&DmapField::Scalar(_)) => Err(DmapError::InvalidRecord(format!(
-                    "Field {} is a scalar, expected vector",
-                    field
-                )))?,
-                Some(DmapField::Vector(x)) if &x.get_type() != expected_type => {
+        check_supported(field_dict, fields_for_type)?;
+        for (field, expected_type, is_vector, required) in schema(fields_for_type) {
+            if let Some(x) = get_schema_field(field_dict, field, is_vector, required)? {
+                if &x.get_type() != expected_type {
                     Err(DmapError::InvalidRecord(format!(
                         "Field {field} has incorrect type {}, expected {expected_type}",
                         x.get_type()
                     )))?
                 }
-                _ => {}
             }
         }
         // This block checks that grouped vector fields have the same dimensionality
@@ -281,95 +912,78 @@ pub trait Record<'a>:
         Ok(())
     }
 
+    /// Lists every required scalar and vector field of `fields_for_type` which is missing from
+    /// `fields`, in the order the format lists them.
+    ///
+    /// Unlike `check_fields`, which stops at the first problem, this reports all missing fields
+    /// at once. Other problems, such as fields with the wrong type, are not checked.
+    fn missing_required_fields(
+        fields: &IndexMap<String, DmapField>,
+        fields_for_type: &Fields,
+    ) -> Vec<String> {
+        schema(fields_for_type)
+            .filter(|&(field, _, _, required)| required && !fields.contains_key(field))
+            .map(|(field, _, _, _)| field.to_string())
+            .collect()
+    }
+
     /// Attempts to massage the entries of an `IndexMap` into the proper types for a DMAP record.
     fn coerce<T: Record<'a>>(
         fields_dict: &mut IndexMap<String, DmapField>,
         fields_for_type: &Fields,
     ) -> Result<T, DmapError> {
-        let unsupported_keys: Vec<&String> = fields_dict
-            .keys()
-            .filter(|&k| !fields_for_type.all_fields.contains(&&**k))
-            .collect();
-        if !unsupported_keys.is_empty() {
-            Err(DmapError::InvalidRecord(format!(
-                "Unsupported fields {:?}, fields supported are {:?}",
-                unsupported_keys, fields_for_type.all_fields
-            )))?
-        }
-
-        for (field, expected_type) in fields_for_type.scalars_required.iter() {
-            match fields_dict.get(&field.to_string()) {
+        T::rename_legacy_fields(fields_dict);
+        check_supported(fields_dict, fields_for_type)?;
+        for (field, expected_type, is_vector, required) in schema(fields_for_type) {
+            let cast = match get_schema_field(fields_dict, field, is_vector, required)? {
                 Some(DmapField::Scalar(x)) if &x.get_type() != expected_type => {
-                    fields_dict.insert(
-                        field.to_string(),
-                        DmapField::Scalar(x.cast_as(expected_type)?),
-                    );
-                }
-                Some(&DmapField::Scalar(_)) => {}
-                Some(_) => Err(DmapError::InvalidRecord(format!(
-                    "Field {} is a vector, expected scalar",
-                    field
-                )))?,
-                None => Err(DmapError::InvalidRecord(format!(
-                    "Field {field:?} ({:?}) missing: fields {:?}",
-                    &field.to_string(),
-                    fields_dict.keys()
-                )))?,
-            }
-        }
-        for (field, expected_type) in fields_for_type.scalars_optional.iter() {
-            match fields_dict.get(&field.to_string()) {
-                Some(DmapField::Scalar(x)) if &x.get_type() == expected_type => {}
-                Some(DmapField::Scalar(x)) => {
-                    fields_dict.insert(
-                        field.to_string(),
-                        DmapField::Scalar(x.cast_as(expected_type)?),
-                    );
+                    DmapField::Scalar(x.cast_as(expected_type)?)
                 }
-                Some(_) => Err(DmapError::InvalidRecord(format!(
-                    "Field {} is a vector, expected scalar",
-                    field
-                )))?,
-                None => {}
-            }
-        }
-        for (field, expected_type) in fields_for_type.vectors_required.iter() {
-            match fields_dict.get(&field.to_string()) {
-                Some(&DmapField::Scalar(_)) => Err(DmapError::InvalidRecord(format!(
-                    "Field {} is a scalar, expected vector",
-                    field
-                )))?,
                 Some(DmapField::Vector(x)) if &x.get_type() != expected_type => {
-                    Err(DmapError::InvalidRecord(format!(
-                        "Field {field} has incorrect type {:?}, expected {expected_type:?}",
-                        x.get_type()
-                    )))?
+                    DmapField::Vector(x.cast_as(expected_type)?)
                 }
-                Some(&DmapField::Vector(_)) => {}
-                None => Err(DmapError::InvalidRecord(format!("Field {field} missing")))?,
-            }
-        }
-        for (field, expected_type) in fields_for_type.vectors_optional.iter() {
-            match fields_dict.get(&field.to_string()) {
-                Some(&DmapField::Scalar(_)) => Err(DmapError::InvalidRecord(format!(
-                    "Field {} is a scalar, expected vector",
-                    field
-                )))?,
-                Some(DmapField::Vector(x)) if &x.get_type() != expected_type => {
-                    Err(DmapError::InvalidRecord(format!(
-                        "Field {field} has incorrect type {}, expected {expected_type}",
-                        x.get_type()
-                    )))?
-                }
-                _ => {}
-            }
+                _ => continue,
+            };
+            fields_dict.insert(field.to_string(), cast);
         }
 
         T::new(fields_dict)
     }
 
     /// Attempts to copy `self` to a raw byte representation.
-    fn to_bytes(&self) -> Result<Vec<u8>, DmapError>;
+    ///
+    /// For record types with a schema, fields are written in the canonical order: required
+    /// scalars, optional scalars, required vectors, then optional vectors, each in the order the
+    /// format lists them. This does not depend on the order of the fields in `self`.
+    fn to_bytes(&self) -> Result<Vec<u8>, DmapError> {
+        let mut bytes = vec![];
+        self.to_bytes_into(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Appends the raw byte representation of `self` to `buf`, in the same form as `to_bytes`.
+    ///
+    /// Reusing one buffer for many records avoids allocating a new one for each. If `self` is
+    /// invalid, returns `DmapError` and leaves `buf` as it was.
+    fn to_bytes_into(&self, buf: &mut Vec<u8>) -> Result<(), DmapError>;
+
+    /// Attempts to copy `self` to a raw byte representation, keeping the order of the fields in
+    /// `self`.
+    ///
+    /// Records read from a file store their fields in the order of the file, so a record written
+    /// with this method is byte-for-byte identical to the one read. All scalars are still written
+    /// before all vectors, as DMAP requires. The fields are checked in the same way as `to_bytes`.
+    fn to_bytes_in_order(&self) -> Result<Vec<u8>, DmapError> {
+        if let Some(fields_for_type) = Self::fields() {
+            check_supported(self.data(), fields_for_type)?;
+            for (field, _, is_vector, required) in schema(fields_for_type) {
+                get_schema_field(self.data(), field, is_vector, required)?;
+            }
+        }
+        let mut bytes = vec![];
+        record_into(&mut bytes, |buf| Ok(ordered_fields_into(self.data(), buf)))?;
+        Ok(bytes)
+    }
 
     /// Converts the entries of an `IndexMap` into a raw byte representation, including metadata
     /// about the entries (DMAP key, name\[, dimensions\])
@@ -383,73 +997,427 @@ pub trait Record<'a>:
         fields_for_type: &Fields,
     ) -> Result<(i32, i32, Vec<u8>), DmapError> {
         let mut data_bytes: Vec<u8> = vec![];
-        let mut num_scalars: i32 = 0;
-        let mut num_vectors: i32 = 0;
-
-        for (field, _) in fields_for_type.scalars_required.iter() {
-            match data.get(&field.to_string()) {
-                Some(x @ DmapField::Scalar(_)) => {
-                    data_bytes.extend(field.as_bytes());
-                    data_bytes.extend([0]); // null-terminate string
-                    data_bytes.append(&mut x.as_bytes());
-                    num_scalars += 1;
-                }
-                Some(_) => Err(DmapError::InvalidScalar(format!(
-                    "Field {field} is a vector, expected scalar"
-                )))?,
-                None => Err(DmapError::InvalidRecord(format!(
-                    "Field {field} missing from record"
-                )))?,
+        let (num_scalars, num_vectors) =
+            schema_fields_into(data, fields_for_type, &mut data_bytes)?;
+        Ok((num_scalars, num_vectors, data_bytes))
+    }
+
+    /// Appends a record holding the entries of an `IndexMap` to `buf`, with the fields in the
+    /// canonical order of `fields_for_type`. If the entries are invalid, `buf` is left as it was.
+    fn data_to_bytes_into(
+        data: &IndexMap<String, DmapField>,
+        fields_for_type: &Fields,
+        buf: &mut Vec<u8>,
+    ) -> Result<(), DmapError> {
+        record_into(buf, |buf| schema_fields_into(data, fields_for_type, buf))
+    }
+}
+
+/// Describes each field of `data` on its own line, without the data of vector fields.
+///
+/// Scalar lines are `scalar <name> <Type> <value>`, e.g. `scalar stid SHORT 65`, and vector lines
+/// are `vector <name> <Type> <shape>`, e.g. `vector acfd FLOAT [75, 23, 2]`.
+pub(crate) fn summarize(data: &IndexMap<String, DmapField>) -> String {
+    data.iter()
+        .map(|(name, field)| match field {
+            DmapField::Scalar(x) => format!("scalar {name} {x}"),
+            DmapField::Vector(x) => format!("vector {name} {} {:?}", x.get_type(), x.shape()),
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// How to treat fields which are not part of the format being read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ValidationMode {
+    /// Unknown fields are an error
+    #[default]
+    Strict,
+    /// Unknown fields are kept in the record as they are stored in the file
+    Lenient,
+}
+
+/// Options for reading files which do not strictly follow the field definitions of their format.
+#[derive(Debug, Clone, Default)]
+pub struct ReadOptions {
+    /// Fields which may be stored with a different `Type` than the format expects, mapped
+    /// to that `Type`. Fields stored with their override type are cast to the expected type.
+    ///
+    /// Overrides only relax type checks: required fields must still be present.
+    pub type_overrides: HashMap<String, Type>,
+    /// Whether fields unknown to the format are an error, e.g. fields added by a newer version
+    /// of RST. Unknown fields are not written when the record is written.
+    pub validation: ValidationMode,
+    /// Parses the records on a new thread pool with this many threads, rather than on the global
+    /// rayon pool, so that a large read does not starve other work. `Some(0)` uses rayon's
+    /// default number of threads. See `Record::read_file_in_pool` to reuse an existing pool.
+    ///
+    /// The worker threads never touch Python objects, so they run whether or not the calling
+    /// thread holds the Python GIL; only the calling thread waits for the read to finish.
+    pub threads: Option<usize>,
+}
+
+/// How to treat an existing file when writing records to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WriteMode {
+    /// Records are appended to the end of the file, which is created if it does not exist.
+    /// Records appended to a compressed file are written as a new compressed stream.
+    #[default]
+    Append,
+    /// The contents of the file are replaced
+    Overwrite,
+    /// The file must not already exist
+    CreateNew,
+}
+
+/// The compression applied to DMAP data when it is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    /// The data is written as is
+    #[default]
+    None,
+    Bz2,
+    Gz,
+    Zstd,
+}
+
+impl Compression {
+    /// The compression named by the extension of `path`: `.bz2`, `.gz` or `.zst`. Paths with any
+    /// other extension are not compressed.
+    pub fn from_path(path: &Path) -> Compression {
+        match path.extension() {
+            Some(ext) if ext == OsStr::new("bz2") => Compression::Bz2,
+            Some(ext) if ext == OsStr::new("gz") => Compression::Gz,
+            Some(ext) if ext == OsStr::new("zst") => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+
+    /// Checks that `level` is a valid compression level: 1-9 for bzip2, 0-9 for gzip and 1-22
+    /// for zstd. Any level is accepted without compression, as it is ignored.
+    fn check_level(&self, level: Option<i32>) -> Result<(), std::io::Error> {
+        let valid = match (self, level) {
+            (Compression::Bz2, Some(x)) => (1..=9).contains(&x),
+            (Compression::Gz, Some(x)) => (0..=9).contains(&x),
+            (Compression::Zstd, Some(x)) => (1..=22).contains(&x),
+            _ => true,
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Invalid compression level {} for {self}",
+                    level.unwrap_or_default()
+                ),
+            ))
+        }
+    }
+}
+
+impl Display for Compression {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            Compression::None => "none",
+            Compression::Bz2 => "bz2",
+            Compression::Gz => "gz",
+            Compression::Zstd => "zst",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for Compression {
+    type Err = DmapError;
+
+    /// Parses the file extension of a compression, e.g. `"bz2"`, or `"none"`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Compression::None),
+            "bz2" => Ok(Compression::Bz2),
+            "gz" => Ok(Compression::Gz),
+            "zst" => Ok(Compression::Zstd),
+            _ => Err(DmapError::InvalidRecord(format!(
+                "Unknown compression {s}, expected one of none, bz2, gz, zst"
+            ))),
+        }
+    }
+}
+
+/// Options for writing records to a file.
+#[derive(Debug, Clone, Default)]
+pub struct WriteOptions {
+    /// Overrides the default compression level, which is the best compression for bzip2 and gzip
+    /// and the zstd default for zstd. Valid levels are 1-9 for bzip2, 0-9 for gzip and 1-22 for
+    /// zstd, and the level is ignored for uncompressed files.
+    pub level: Option<i32>,
+    /// How to treat an existing file
+    pub mode: WriteMode,
+    /// Write the fields of each record in the order they are stored, i.e. the order they were
+    /// read from a file, rather than the canonical order. See `Record::to_bytes_in_order`.
+    pub preserve_order: bool,
+    /// Serializes the records on a new thread pool with this many threads, rather than on the
+    /// global rayon pool. `Some(0)` uses rayon's default number of threads.
+    pub threads: Option<usize>,
+}
+
+/// Runs `f` on a new thread pool with `threads` threads, so that the parallel iterators within it
+/// use that pool, or on the global pool if `threads` is `None`.
+///
+/// Returns `DmapError` if the thread pool cannot be created.
+pub(crate) fn with_threads<R: Send>(
+    threads: Option<usize>,
+    f: impl FnOnce() -> Result<R, DmapError> + Send,
+) -> Result<R, DmapError> {
+    match threads {
+        None => f(),
+        Some(n) => ThreadPoolBuilder::new()
+            .num_threads(n)
+            .build()
+            .map_err(|e| DmapError::Io(std::io::Error::new(ErrorKind::Other, e)))?
+            .install(f),
+    }
+}
+
+/// An iterator which reads and parses one record at a time from a stream of DMAP data.
+///
+/// Records with invalid contents are yielded as errors and iteration continues with the next
+/// record. Iteration stops after an error reading the stream or an invalid record size, as the
+/// start of the next record cannot be found.
+pub struct RecordIter<T> {
+    reader: BufReader<Box<dyn Read>>,
+    offset: u64,
+    index: usize,
+    done: bool,
+    phantom: PhantomData<T>,
+}
+
+impl<T> RecordIter<T> {
+    /// Creates an iterator over the records in `reader`.
+    pub fn new(reader: impl Read + 'static) -> RecordIter<T> {
+        let reader: Box<dyn Read> = Box::new(reader);
+        RecordIter {
+            reader: BufReader::new(reader),
+            offset: 0,
+            index: 0,
+            done: false,
+            phantom: PhantomData,
+        }
+    }
+
+    /// The byte offset of the next record from the start of the (decompressed) stream.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Reads the bytes of the next record, or `None` if the stream is at its end.
+    fn read_next(&mut self) -> Result<Option<Vec<u8>>, DmapError> {
+        let header_size = 2 * i32::size();
+        let mut buffer = vec![0; header_size];
+        let mut bytes_read = 0;
+        while bytes_read < header_size {
+            match self.reader.read(&mut buffer[bytes_read..])? {
+                0 if bytes_read == 0 => return Ok(None),
+                0 => Err(DmapError::CorruptStream("Record header is truncated"))?,
+                n => bytes_read += n,
             }
         }
-        for (field, _) in fields_for_type.scalars_optional.iter() {
-            if let Some(x) = data.get(&field.to_string()) {
-                match x {
-                    DmapField::Scalar(_) => {
-                        data_bytes.extend(field.as_bytes());
-                        data_bytes.extend([0]); // null-terminate string
-                        data_bytes.append(&mut x.as_bytes());
-                        num_scalars += 1;
-                    }
-                    DmapField::Vector(_) => Err(DmapError::InvalidScalar(format!(
-                        "Field {field} is a vector, expected scalar"
-                    )))?,
-                }
+        let (_, size) = read_record_header(&buffer)?;
+        if size <= header_size as i32 {
+            Err(DmapError::InvalidRecord(format!(
+                "Record size {size} too small"
+            )))?
+        }
+        buffer.resize(size as usize, 0);
+        while bytes_read < buffer.len() {
+            match self.reader.read(&mut buffer[bytes_read..])? {
+                0 => Err(DmapError::TruncatedFile {
+                    expected: size as usize,
+                    available: bytes_read,
+                    byte: self.offset,
+                })?,
+                n => bytes_read += n,
             }
         }
-        for (field, _) in fields_for_type.vectors_required.iter() {
-            match data.get(&field.to_string()) {
-                Some(x @ DmapField::Vector(_)) => {
-                    data_bytes.extend(field.as_bytes());
-                    data_bytes.extend([0]); // null-terminate string
-                    data_bytes.append(&mut x.as_bytes());
-                    num_vectors += 1;
-                }
-                Some(_) => Err(DmapError::InvalidVector(format!(
-                    "Field {field} is a scalar, expected vector"
-                )))?,
-                None => Err(DmapError::InvalidRecord(format!(
-                    "Field {field} missing from record"
-                )))?,
+        trace!("Read record of size {size} at byte {}", self.offset);
+        self.offset += size as u64;
+        Ok(Some(buffer))
+    }
+}
+
+impl<'a, T: Record<'a>> Iterator for RecordIter<T> {
+    type Item = Result<T, DmapError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let (index, offset) = (self.index, self.offset);
+        match self.read_next() {
+            Ok(Some(bytes)) => {
+                self.index += 1;
+                Some(
+                    T::parse_record(&mut Cursor::new(bytes.as_slice()))
+                        .map_err(|e| e.in_record(index, offset)),
+                )
+            }
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
             }
         }
-        for (field, _) in fields_for_type.vectors_optional.iter() {
-            if let Some(x) = data.get(&field.to_string()) {
-                match x {
-                    DmapField::Vector(_) => {
-                        data_bytes.extend(field.as_bytes());
-                        data_bytes.extend([0]); // null-terminate string
-                        data_bytes.append(&mut x.as_bytes());
-                        num_vectors += 1;
-                    }
-                    DmapField::Scalar(_) => Err(DmapError::InvalidVector(format!(
-                        "Field {field} is a scalar, expected vector"
-                    )))?,
-                }
+    }
+}
+
+/// A stream of DMAP data being written, compressing the data if required.
+pub(crate) enum Sink<W: Write> {
+    Plain(W),
+    Bz2(BzEncoder<W>),
+    Gz(GzEncoder<W>),
+    Zstd(ZstdEncoder<'static, W>),
+}
+
+impl Sink<File> {
+    /// Opens `outfile` according to `opts.mode`, compressing the data written using bzip2, gzip
+    /// or zstd if the extension of `outfile` is `.bz2`, `.gz` or `.zst` respectively.
+    pub(crate) fn create(outfile: &PathBuf, opts: &WriteOptions) -> Result<Self, std::io::Error> {
+        let compression = Compression::from_path(outfile);
+        // Check the level before creating the file
+        compression.check_level(opts.level)?;
+        Sink::new(open_for_write(outfile, opts.mode)?, compression, opts.level)
+    }
+}
+
+/// Opens `outfile` for writing according to `mode`.
+pub(crate) fn open_for_write(outfile: &PathBuf, mode: WriteMode) -> Result<File, std::io::Error> {
+    let mut options = OpenOptions::new();
+    match mode {
+        WriteMode::Append => options.append(true).create(true),
+        WriteMode::Overwrite => options.write(true).truncate(true).create(true),
+        WriteMode::CreateNew => options.write(true).create_new(true),
+    };
+    options.open(outfile)
+}
+
+impl<W: Write> Sink<W> {
+    /// Wraps `writer`, compressing the data written with `compression` at compression level
+    /// `level`, or the default level for `compression` if `None`.
+    pub(crate) fn new(
+        writer: W,
+        compression: Compression,
+        level: Option<i32>,
+    ) -> Result<Self, std::io::Error> {
+        compression.check_level(level)?;
+        Ok(match compression {
+            Compression::None => Sink::Plain(writer),
+            Compression::Bz2 => Sink::Bz2(BzEncoder::new(
+                writer,
+                level.map_or(bzip2::Compression::best(), |x| {
+                    bzip2::Compression::new(x as u32)
+                }),
+            )),
+            Compression::Gz => Sink::Gz(GzEncoder::new(
+                writer,
+                level.map_or(flate2::Compression::best(), |x| {
+                    flate2::Compression::new(x as u32)
+                }),
+            )),
+            Compression::Zstd => Sink::Zstd(ZstdEncoder::new(
+                writer,
+                level.unwrap_or(zstd::DEFAULT_COMPRESSION_LEVEL),
+            )?),
+        })
+    }
+
+    /// Writes any data still held by the encoder, returning the underlying stream.
+    pub(crate) fn finish(self) -> Result<W, std::io::Error> {
+        match self {
+            Sink::Plain(mut x) => {
+                x.flush()?;
+                Ok(x)
             }
+            Sink::Bz2(x) => x.finish(),
+            Sink::Gz(x) => x.finish(),
+            Sink::Zstd(x) => x.finish(),
         }
+    }
+}
 
-        Ok((num_scalars, num_vectors, data_bytes))
+impl<W: Write> Write for Sink<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Sink::Plain(x) => x.write(buf),
+            Sink::Bz2(x) => x.write(buf),
+            Sink::Gz(x) => x.write(buf),
+            Sink::Zstd(x) => x.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Sink::Plain(x) => x.flush(),
+            Sink::Bz2(x) => x.flush(),
+            Sink::Gz(x) => x.flush(),
+            Sink::Zstd(x) => x.flush(),
+        }
+    }
+}
+
+/// A writer which serializes records to a stream one at a time, so that memory use does not
+/// grow with the number of records written.
+///
+/// Uncompressed streams are flushed after every record. Compressed streams are not, as flushing
+/// an encoder ends its current block and worsens compression; the encoder instead holds at most
+/// one block of data. `finish` must be called after the last record to complete the stream.
+pub struct DmapWriter<W: Write> {
+    sink: Sink<W>,
+    /// Holds each record while it is written, reused to avoid allocating for every record
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> DmapWriter<W> {
+    /// Creates a writer which writes uncompressed records to `writer`.
+    pub fn new(writer: W) -> DmapWriter<W> {
+        DmapWriter {
+            sink: Sink::Plain(writer),
+            buffer: vec![],
+        }
+    }
+
+    /// Serializes `rec` and writes it to the stream. Nothing is written if `rec` is invalid.
+    pub fn write_record<'a>(&mut self, rec: &impl Record<'a>) -> Result<(), DmapError> {
+        self.buffer.clear();
+        rec.to_bytes_into(&mut self.buffer)?;
+        self.sink.write_all(&self.buffer)?;
+        if let Sink::Plain(x) = &mut self.sink {
+            x.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Completes the stream, writing any compressed data still held by the encoder, and returns
+    /// the underlying writer.
+    pub fn finish(self) -> Result<W, DmapError> {
+        Ok(self.sink.finish()?)
+    }
+}
+
+impl DmapWriter<File> {
+    /// Creates a writer to `outfile`, which is opened according to `opts.mode`.
+    ///
+    /// If the extension of `outfile` is `.bz2`, `.gz` or `.zst`, the records are compressed as they
+    /// are written using bzip2, gzip or zstd respectively, with compression level `opts.level` if
+    /// given.
+    pub fn create(outfile: &PathBuf, opts: &WriteOptions) -> Result<DmapWriter<File>, DmapError> {
+        Ok(DmapWriter {
+            sink: Sink::create(outfile, opts)?,
+            buffer: vec![],
+        })
     }
 }
 
@@ -465,48 +1433,63 @@ impl GenericRecord {
     pub fn keys(&self) -> Vec<&String> {
         self.data.keys().collect()
     }
+
+    /// Returns `true` if the record has a field with name `key`.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.data.contains_key(key)
+    }
+
+    /// Adds the field `key` to the end of the record, or replaces its value in place if the record
+    /// already has a field `key`.
+    pub fn insert(&mut self, key: String, field: DmapField) {
+        self.data.insert(key, field);
+    }
+
+    /// Removes the field `key` from the record, keeping the order of the other fields. Returns
+    /// the field, if it existed.
+    pub fn remove(&mut self, key: &str) -> Option<DmapField> {
+        self.data.shift_remove(key)
+    }
+
+    /// Converts only the fields named in `fields` to a raw byte representation, in the order of
+    /// `fields`, for reproducing the field order of a file exactly.
+    ///
+    /// All scalars are still written before all vectors, as DMAP requires, so the order of
+    /// `fields` is kept among the scalars and among the vectors. Fields of the record which are
+    /// not in `fields` are omitted. Returns `DmapError` if a field in `fields` is not in the record
+    /// or is listed more than once.
+    pub fn write_fields(&self, fields: &[&str]) -> Result<Vec<u8>, DmapError> {
+        let mut bytes = vec![];
+        record_into(&mut bytes, |buf| {
+            listed_fields_into(&self.data, fields, buf)
+        })?;
+        Ok(bytes)
+    }
 }
 
+field_accessors!(GenericRecord, scalars: [], vectors: []);
+
 impl Record<'_> for GenericRecord {
     fn inner(self) -> IndexMap<String, DmapField> {
         self.data
     }
+    fn data(&self) -> &IndexMap<String, DmapField> {
+        &self.data
+    }
 
     fn new(fields: &mut IndexMap<String, DmapField>) -> Result<GenericRecord, DmapError> {
         Ok(GenericRecord {
             data: fields.to_owned(),
         })
     }
-    fn to_bytes(&self) -> Result<Vec<u8>, DmapError> {
-        let mut data_bytes: Vec<u8> = vec![];
-        let mut num_scalars: i32 = 0;
-        let mut num_vectors: i32 = 0;
-
-        // Do a first pass, to get all the scalar fields
-        for (name, val) in self.data.iter() {
-            if let x @ DmapField::Scalar(_) = val {
-                data_bytes.extend(name.as_bytes());
-                data_bytes.extend([0]); // null-terminate string
-                data_bytes.append(&mut x.as_bytes());
-                num_scalars += 1;
-            }
-        }
-        // Do a second pass to convert all the vector fields
-        for (name, val) in self.data.iter() {
-            if let x @ DmapField::Vector(_) = val {
-                data_bytes.extend(name.as_bytes());
-                data_bytes.extend([0]); // null-terminate string
-                data_bytes.append(&mut x.as_bytes());
-                num_vectors += 1;
-            }
-        }
-        let mut bytes: Vec<u8> = vec![];
-        bytes.extend((65537_i32).as_bytes()); // No idea why this is what it is, copied from backscatter
-        bytes.extend((data_bytes.len() as i32 + 16).as_bytes()); // +16 for code, length, num_scalars, num_vectors
-        bytes.extend(num_scalars.as_bytes());
-        bytes.extend(num_vectors.as_bytes());
-        bytes.append(&mut data_bytes); // consumes data_bytes
-        Ok(bytes)
+    fn fields() -> Option<&'static Fields<'static>> {
+        None
+    }
+    fn new_unchecked(fields: IndexMap<String, DmapField>) -> GenericRecord {
+        GenericRecord { data: fields }
+    }
+    fn to_bytes_into(&self, buf: &mut Vec<u8>) -> Result<(), DmapError> {
+        record_into(buf, |buf| Ok(ordered_fields_into(&self.data, buf)))
     }
 }
 
@@ -1,6 +1,6 @@
 use crate::error::DmapError;
 use crate::formats::dmap::Record;
-use crate::types::{DmapField, DmapType, Fields, Type};
+use crate::types::{drop_fields, DmapField, Fields, Type};
 use indexmap::IndexMap;
 use lazy_static::lazy_static;
 use std::convert::TryFrom;
@@ -179,11 +179,42 @@ lazy_static! {
     };
 }
 
+/// Returns the names and types of the fields of a FITACF record.
+pub fn fitacf_fields() -> &'static Fields<'static> {
+    &FITACF_FIELDS
+}
+
+/// Removes the cross-correlation (XCF) and elevation fields from a FITACF record, i.e. the `x_*`,
+/// `phi0*` and `elv*` vectors, to save space when they are not needed.
+///
+/// These fields are all optional, so a valid record is still valid afterwards.
+pub fn strip_xcf(record: &mut IndexMap<String, DmapField>) {
+    let xcf_fields: Vec<&str> = VECTOR_FIELDS_OPT
+        .iter()
+        .map(|(name, _)| *name)
+        .filter(|name| {
+            name.starts_with("x_") || name.starts_with("phi0") || name.starts_with("elv")
+        })
+        .collect();
+    drop_fields(record, &xcf_fields);
+}
+
+/// Struct containing the checked fields of a single FITACF record.
+///
+/// The only multi-dimensional vector field is `ltab`, with axes `[mplgs + 1, 2]`: the pair of
+/// pulses which form each lag. The range-indexed vectors, such as `v` and `p_l`, have one entry
+/// for each range gate listed in `slist`.
 #[derive(Debug, PartialEq, Clone)]
 pub struct FitacfRecord {
     pub data: IndexMap<String, DmapField>,
 }
 
+/// The scalar fields which are repeated on every row of the CSV representation of a record.
+static CSV_SCALARS: [&str; 10] = [
+    "time.yr", "time.mo", "time.dy", "time.hr", "time.mt", "time.sc", "time.us", "stid", "channel",
+    "bmnum",
+];
+
 impl FitacfRecord {
     pub fn get(&self, key: &String) -> Option<&DmapField> {
         self.data.get(key)
@@ -191,13 +222,189 @@ impl FitacfRecord {
     pub fn keys(&self) -> Vec<&String> {
         self.data.keys().collect()
     }
+
+    /// The header of the CSV representation of records: a column for each of the scalars that
+    /// identify the record, then a column for each range-indexed vector field.
+    pub fn csv_header() -> String {
+        let columns: Vec<&str> = CSV_SCALARS
+            .iter()
+            .chain(MATCHED_VECS[0].iter())
+            .copied()
+            .collect();
+        columns.join(",")
+    }
+
+    /// Converts the record into CSV rows, one per range gate in `slist`, with the columns given
+    /// by `csv_header`.
+    ///
+    /// Optional vectors which are absent from the record are empty cells. Records with no
+    /// `slist` have no range gates, so give no rows.
+    pub fn csv_rows(&self) -> Vec<String> {
+        let num_gates = match self.data.get("slist") {
+            Some(DmapField::Vector(x)) => x.shape().iter().product(),
+            _ => 0,
+        };
+        let scalars: Vec<String> = CSV_SCALARS
+            .iter()
+            .map(|&key| match self.data.get(key) {
+                Some(DmapField::Scalar(x)) => x.value_string(),
+                _ => String::new(),
+            })
+            .collect();
+        (0..num_gates)
+            .map(|gate| {
+                let mut row = scalars.clone();
+                row.extend(MATCHED_VECS[0].iter().map(|&key| match self.data.get(key) {
+                    Some(DmapField::Vector(x)) => x.element_string(gate).unwrap_or_default(),
+                    _ => String::new(),
+                }));
+                row.join(",")
+            })
+            .collect()
+    }
 }
+
+field_accessors!(
+    FitacfRecord,
+    builder: FitacfRecordBuilder,
+    scalars: [
+        radar_revision_major: "radar.revision.major" => i8,
+        radar_revision_minor: "radar.revision.minor" => i8,
+        origin_code: "origin.code" => i8,
+        origin_time: "origin.time" => &str,
+        origin_command: "origin.command" => &str,
+        cp: "cp" => i16,
+        stid: "stid" => i16,
+        time_yr: "time.yr" => i16,
+        time_mo: "time.mo" => i16,
+        time_dy: "time.dy" => i16,
+        time_hr: "time.hr" => i16,
+        time_mt: "time.mt" => i16,
+        time_sc: "time.sc" => i16,
+        time_us: "time.us" => i32,
+        txpow: "txpow" => i16,
+        nave: "nave" => i16,
+        atten: "atten" => i16,
+        lagfr: "lagfr" => i16,
+        smsep: "smsep" => i16,
+        ercod: "ercod" => i16,
+        stat_agc: "stat.agc" => i16,
+        stat_lopwr: "stat.lopwr" => i16,
+        noise_search: "noise.search" => f32,
+        noise_mean: "noise.mean" => f32,
+        channel: "channel" => i16,
+        bmnum: "bmnum" => i16,
+        bmazm: "bmazm" => f32,
+        scan: "scan" => i16,
+        offset: "offset" => i16,
+        rxrise: "rxrise" => i16,
+        intt_sc: "intt.sc" => i16,
+        intt_us: "intt.us" => i32,
+        txpl: "txpl" => i16,
+        mpinc: "mpinc" => i16,
+        mppul: "mppul" => i16,
+        mplgs: "mplgs" => i16,
+        nrang: "nrang" => i16,
+        frang: "frang" => i16,
+        rsep: "rsep" => i16,
+        xcf: "xcf" => i16,
+        tfreq: "tfreq" => i16,
+        mxpwr: "mxpwr" => i32,
+        lvmax: "lvmax" => i32,
+        combf: "combf" => &str,
+        fitacf_revision_major: "fitacf.revision.major" => i32,
+        fitacf_revision_minor: "fitacf.revision.minor" => i32,
+        noise_sky: "noise.sky" => f32,
+        noise_lag0: "noise.lag0" => f32,
+        noise_vel: "noise.vel" => f32,
+        mplgexs: "mplgexs" => i16,
+        ifmode: "ifmode" => i16,
+        algorithm: "algorithm" => &str,
+        tdiff: "tdiff" => f32,
+    ],
+    vectors: [
+        ptab: "ptab" => i16,
+        ltab: "ltab" => i16,
+        pwr0: "pwr0" => f32,
+        slist: "slist" => i16,
+        nlag: "nlag" => i16,
+        qflg: "qflg" => i8,
+        gflg: "gflg" => i8,
+        p_l: "p_l" => f32,
+        p_l_e: "p_l_e" => f32,
+        p_s: "p_s" => f32,
+        p_s_e: "p_s_e" => f32,
+        v: "v" => f32,
+        v_e: "v_e" => f32,
+        w_l: "w_l" => f32,
+        w_l_e: "w_l_e" => f32,
+        w_s: "w_s" => f32,
+        w_s_e: "w_s_e" => f32,
+        sd_l: "sd_l" => f32,
+        sd_s: "sd_s" => f32,
+        sd_phi: "sd_phi" => f32,
+        x_qflg: "x_qflg" => i8,
+        x_gflg: "x_gflg" => i8,
+        x_p_l: "x_p_l" => f32,
+        x_p_l_e: "x_p_l_e" => f32,
+        x_p_s: "x_p_s" => f32,
+        x_p_s_e: "x_p_s_e" => f32,
+        x_v: "x_v" => f32,
+        x_v_e: "x_v_e" => f32,
+        x_w_l: "x_w_l" => f32,
+        x_w_l_e: "x_w_l_e" => f32,
+        x_w_s: "x_w_s" => f32,
+        x_w_s_e: "x_w_s_e" => f32,
+        phi0: "phi0" => f32,
+        phi0_e: "phi0_e" => f32,
+        elv: "elv" => f32,
+        elv_fitted: "elv_fitted" => f32,
+        elv_error: "elv_error" => f32,
+        elv_low: "elv_low" => f32,
+        elv_high: "elv_high" => f32,
+        x_sd_l: "x_sd_l" => f32,
+        x_sd_s: "x_sd_s" => f32,
+        x_sd_phi: "x_sd_phi" => f32,
+    ],
+);
+/// Checks that each range-indexed vector field has one element per range gate in `slist`,
+/// naming the first field which does not.
+///
+/// A beam with no scatter has an empty `slist` and no range-indexed vectors, as `slist` is the
+/// only vector which may be stored with no elements.
+fn check_slist_lengths(fields: &IndexMap<String, DmapField>) -> Result<(), DmapError> {
+    let num_gates = match fields.get("slist") {
+        Some(DmapField::Vector(x)) => x.shape(),
+        _ => return Ok(()),
+    };
+    let is_empty = num_gates.iter().product::<usize>() == 0;
+    for &name in MATCHED_VECS[0].iter().filter(|&&name| name != "slist") {
+        match fields.get(name) {
+            Some(DmapField::Vector(_)) if is_empty => Err(DmapError::InvalidRecord(format!(
+                "Field {name} must be absent as slist is empty"
+            )))?,
+            Some(DmapField::Vector(x)) if x.shape() != num_gates => {
+                Err(DmapError::InvalidRecord(format!(
+                    "Field {name} has dimensions {:?}, expected {num_gates:?} to match slist",
+                    x.shape()
+                )))?
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
 impl Record<'_> for FitacfRecord {
     fn inner(self) -> IndexMap<String, DmapField> {
         self.data
     }
+    fn data(&self) -> &IndexMap<String, DmapField> {
+        &self.data
+    }
 
     fn new(fields: &mut IndexMap<String, DmapField>) -> Result<FitacfRecord, DmapError> {
+        check_slist_lengths(fields)?;
         match Self::check_fields(fields, &FITACF_FIELDS) {
             Ok(_) => {}
             Err(e) => Err(e)?,
@@ -207,17 +414,14 @@ impl Record<'_> for FitacfRecord {
             data: fields.to_owned(),
         })
     }
-    fn to_bytes(&self) -> Result<Vec<u8>, DmapError> {
-        let (num_scalars, num_vectors, mut data_bytes) =
-            Self::data_to_bytes(&self.data, &FITACF_FIELDS)?;
-
-        let mut bytes: Vec<u8> = vec![];
-        bytes.extend((65537_i32).as_bytes()); // No idea why this is what it is, copied from backscatter
-        bytes.extend((data_bytes.len() as i32 + 16).as_bytes()); // +16 for code, length, num_scalars, num_vectors
-        bytes.extend(num_scalars.as_bytes());
-        bytes.extend(num_vectors.as_bytes());
-        bytes.append(&mut data_bytes); // consumes data_bytes
-        Ok(bytes)
+    fn fields() -> Option<&'static Fields<'static>> {
+        Some(&FITACF_FIELDS)
+    }
+    fn new_unchecked(fields: IndexMap<String, DmapField>) -> FitacfRecord {
+        FitacfRecord { data: fields }
+    }
+    fn to_bytes_into(&self, buf: &mut Vec<u8>) -> Result<(), DmapError> {
+        Self::data_to_bytes_into(&self.data, &FITACF_FIELDS, buf)
     }
 }
 
@@ -1,6 +1,6 @@
 use crate::error::DmapError;
 use crate::formats::dmap::Record;
-use crate::types::{DmapField, DmapType, Fields, Type};
+use crate::types::{DmapField, Fields, Type};
 use indexmap::IndexMap;
 use lazy_static::lazy_static;
 use std::convert::TryFrom;
@@ -113,6 +113,11 @@ lazy_static! {
     };
 }
 
+/// Returns the names and types of the fields of a GRID record.
+pub fn grid_fields() -> &'static Fields<'static> {
+    &GRID_FIELDS
+}
+
 /// Struct containing the checked fields of a single GRID record.
 #[derive(Debug, PartialEq, Clone)]
 pub struct GridRecord {
@@ -128,10 +133,65 @@ impl GridRecord {
     }
 }
 
+field_accessors!(
+    GridRecord,
+    builder: GridRecordBuilder,
+    scalars: [
+        start_year: "start.year" => i16,
+        start_month: "start.month" => i16,
+        start_day: "start.day" => i16,
+        start_hour: "start.hour" => i16,
+        start_minute: "start.minute" => i16,
+        start_second: "start.second" => f64,
+        end_year: "end.year" => i16,
+        end_month: "end.month" => i16,
+        end_day: "end.day" => i16,
+        end_hour: "end.hour" => i16,
+        end_minute: "end.minute" => i16,
+        end_second: "end.second" => f64,
+    ],
+    vectors: [
+        stid: "stid" => i16,
+        channel: "channel" => i16,
+        nvec: "nvec" => i16,
+        freq: "freq" => f32,
+        major_revision: "major.revision" => i16,
+        minor_revision: "minor.revision" => i16,
+        program_id: "program.id" => i16,
+        noise_mean: "noise.mean" => f32,
+        noise_sd: "noise.sd" => f32,
+        gsct: "gsct" => i16,
+        v_min: "v.min" => f32,
+        v_max: "v.max" => f32,
+        p_min: "p.min" => f32,
+        p_max: "p.max" => f32,
+        w_min: "w.min" => f32,
+        w_max: "w.max" => f32,
+        ve_min: "ve.min" => f32,
+        ve_max: "ve.max" => f32,
+        vector_mlat: "vector.mlat" => f32,
+        vector_mlon: "vector.mlon" => f32,
+        vector_kvect: "vector.kvect" => f32,
+        vector_stid: "vector.stid" => i16,
+        vector_channel: "vector.channel" => i16,
+        vector_index: "vector.index" => i32,
+        vector_vel_median: "vector.vel.median" => f32,
+        vector_vel_sd: "vector.vel.sd" => f32,
+        vector_pwr_median: "vector.pwr.median" => f32,
+        vector_pwr_sd: "vector.pwr.sd" => f32,
+        vector_wdt_median: "vector.wdt.median" => f32,
+        vector_wdt_sd: "vector.wdt.sd" => f32,
+        vector_srng: "vector.srng" => f32,
+    ],
+);
+
 impl Record<'_> for GridRecord {
     fn inner(self) -> IndexMap<String, DmapField> {
         self.data
     }
+    fn data(&self) -> &IndexMap<String, DmapField> {
+        &self.data
+    }
 
     fn new(fields: &mut IndexMap<String, DmapField>) -> Result<GridRecord, DmapError> {
         match Self::check_fields(fields, &GRID_FIELDS) {
@@ -143,17 +203,14 @@ impl Record<'_> for GridRecord {
             data: fields.to_owned(),
         })
     }
-    fn to_bytes(&self) -> Result<Vec<u8>, DmapError> {
-        let (num_scalars, num_vectors, mut data_bytes) =
-            Self::data_to_bytes(&self.data, &GRID_FIELDS)?;
-
-        let mut bytes: Vec<u8> = vec![];
-        bytes.extend((65537_i32).as_bytes()); // No idea why this is what it is, copied from backscatter
-        bytes.extend((data_bytes.len() as i32 + 16).as_bytes()); // +16 for code, length, num_scalars, num_vectors
-        bytes.extend(num_scalars.as_bytes());
-        bytes.extend(num_vectors.as_bytes());
-        bytes.append(&mut data_bytes); // consumes data_bytes
-        Ok(bytes)
+    fn fields() -> Option<&'static Fields<'static>> {
+        Some(&GRID_FIELDS)
+    }
+    fn new_unchecked(fields: IndexMap<String, DmapField>) -> GridRecord {
+        GridRecord { data: fields }
+    }
+    fn to_bytes_into(&self, buf: &mut Vec<u8>) -> Result<(), DmapError> {
+        Self::data_to_bytes_into(&self.data, &GRID_FIELDS, buf)
     }
 }
 
@@ -1,6 +1,6 @@
 use crate::error::DmapError;
 use crate::formats::dmap::Record;
-use crate::types::{DmapField, DmapType, Fields, Type};
+use crate::types::{DmapField, Fields, Type};
 use indexmap::IndexMap;
 use lazy_static::lazy_static;
 use std::convert::TryFrom;
@@ -95,6 +95,11 @@ lazy_static! {
     };
 }
 
+/// Returns the names and types of the fields of a IQDAT record.
+pub fn iqdat_fields() -> &'static Fields<'static> {
+    &IQDAT_FIELDS
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct IqdatRecord {
     pub data: IndexMap<String, DmapField>,
@@ -109,10 +114,83 @@ impl IqdatRecord {
     }
 }
 
+field_accessors!(
+    IqdatRecord,
+    builder: IqdatRecordBuilder,
+    scalars: [
+        radar_revision_major: "radar.revision.major" => i8,
+        radar_revision_minor: "radar.revision.minor" => i8,
+        origin_code: "origin.code" => i8,
+        origin_time: "origin.time" => &str,
+        origin_command: "origin.command" => &str,
+        cp: "cp" => i16,
+        stid: "stid" => i16,
+        time_yr: "time.yr" => i16,
+        time_mo: "time.mo" => i16,
+        time_dy: "time.dy" => i16,
+        time_hr: "time.hr" => i16,
+        time_mt: "time.mt" => i16,
+        time_sc: "time.sc" => i16,
+        time_us: "time.us" => i32,
+        txpow: "txpow" => i16,
+        nave: "nave" => i16,
+        atten: "atten" => i16,
+        lagfr: "lagfr" => i16,
+        smsep: "smsep" => i16,
+        ercod: "ercod" => i16,
+        stat_agc: "stat.agc" => i16,
+        stat_lopwr: "stat.lopwr" => i16,
+        noise_search: "noise.search" => f32,
+        noise_mean: "noise.mean" => f32,
+        channel: "channel" => i16,
+        bmnum: "bmnum" => i16,
+        bmazm: "bmazm" => f32,
+        scan: "scan" => i16,
+        offset: "offset" => i16,
+        rxrise: "rxrise" => i16,
+        intt_sc: "intt.sc" => i16,
+        intt_us: "intt.us" => i32,
+        txpl: "txpl" => i16,
+        mpinc: "mpinc" => i16,
+        mppul: "mppul" => i16,
+        mplgs: "mplgs" => i16,
+        nrang: "nrang" => i16,
+        frang: "frang" => i16,
+        rsep: "rsep" => i16,
+        xcf: "xcf" => i16,
+        tfreq: "tfreq" => i16,
+        mxpwr: "mxpwr" => i32,
+        lvmax: "lvmax" => i32,
+        combf: "combf" => &str,
+        iqdata_revision_major: "iqdata.revision.major" => i32,
+        iqdata_revision_minor: "iqdata.revision.minor" => i32,
+        seqnum: "seqnum" => i32,
+        chnnum: "chnnum" => i32,
+        smpnum: "smpnum" => i32,
+        skpnum: "skpnum" => i32,
+        mplgexs: "mplgexs" => i16,
+        ifmode: "ifmode" => i16,
+    ],
+    vectors: [
+        ptab: "ptab" => i16,
+        ltab: "ltab" => i16,
+        tsc: "tsc" => i32,
+        tus: "tus" => i32,
+        tatten: "tatten" => i16,
+        tnoise: "tnoise" => f32,
+        toff: "toff" => i32,
+        tsze: "tsze" => i32,
+        data: "data" => i16,
+    ],
+);
+
 impl Record<'_> for IqdatRecord {
     fn inner(self) -> IndexMap<String, DmapField> {
         self.data
     }
+    fn data(&self) -> &IndexMap<String, DmapField> {
+        &self.data
+    }
 
     fn new(fields: &mut IndexMap<String, DmapField>) -> Result<IqdatRecord, DmapError> {
         match Self::check_fields(fields, &IQDAT_FIELDS) {
@@ -124,17 +202,14 @@ impl Record<'_> for IqdatRecord {
             data: fields.to_owned(),
         })
     }
-    fn to_bytes(&self) -> Result<Vec<u8>, DmapError> {
-        let (num_scalars, num_vectors, mut data_bytes) =
-            Self::data_to_bytes(&self.data, &IQDAT_FIELDS)?;
-
-        let mut bytes: Vec<u8> = vec![];
-        bytes.extend((65537_i32).as_bytes()); // No idea why this is what it is, copied from backscatter
-        bytes.extend((data_bytes.len() as i32 + 16).as_bytes()); // +16 for code, length, num_scalars, num_vectors
-        bytes.extend(num_scalars.as_bytes());
-        bytes.extend(num_vectors.as_bytes());
-        bytes.append(&mut data_bytes); // consumes data_bytes
-        Ok(bytes)
+    fn fields() -> Option<&'static Fields<'static>> {
+        Some(&IQDAT_FIELDS)
+    }
+    fn new_unchecked(fields: IndexMap<String, DmapField>) -> IqdatRecord {
+        IqdatRecord { data: fields }
+    }
+    fn to_bytes_into(&self, buf: &mut Vec<u8>) -> Result<(), DmapError> {
+        Self::data_to_bytes_into(&self.data, &IQDAT_FIELDS, buf)
     }
 }
 
@@ -1,6 +1,6 @@
 use crate::error::DmapError;
 use crate::formats::dmap::Record;
-use crate::types::{DmapField, DmapType, Fields, Type};
+use crate::types::{DmapField, Fields, Type};
 use indexmap::IndexMap;
 use lazy_static::lazy_static;
 
@@ -105,6 +105,9 @@ static VECTOR_FIELDS_OPT: [(&str, Type); 23] = [
     ("boundary.mlon", Type::Float),
 ];
 
+/// Legacy spelling of the `IMF.Kp` field, which is renamed to `IMF.Kp` when read.
+static LEGACY_KP_FIELD: &str = "IMT.Kp";
+
 lazy_static! {
     static ref MATCHED_VECS: Vec<Vec<&'static str>> = vec![
         vec![
@@ -167,6 +170,11 @@ lazy_static! {
     };
 }
 
+/// Returns the names and types of the fields of a MAP record.
+pub fn map_fields() -> &'static Fields<'static> {
+    &MAP_FIELDS
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct MapRecord {
     pub data: IndexMap<String, DmapField>,
@@ -181,12 +189,114 @@ impl MapRecord {
     }
 }
 
+field_accessors!(
+    MapRecord,
+    builder: MapRecordBuilder,
+    scalars: [
+        start_year: "start.year" => i16,
+        start_month: "start.month" => i16,
+        start_day: "start.day" => i16,
+        start_hour: "start.hour" => i16,
+        start_minute: "start.minute" => i16,
+        start_second: "start.second" => f64,
+        end_year: "end.year" => i16,
+        end_month: "end.month" => i16,
+        end_day: "end.day" => i16,
+        end_hour: "end.hour" => i16,
+        end_minute: "end.minute" => i16,
+        end_second: "end.second" => f64,
+        map_major_revision: "map.major.revision" => i16,
+        map_minor_revision: "map.minor.revision" => i16,
+        doping_level: "doping.level" => i16,
+        model_wt: "model.wt" => i16,
+        error_wt: "error.wt" => i16,
+        imf_flag: "IMF.flag" => i16,
+        hemisphere: "hemisphere" => i16,
+        fit_order: "fit.order" => i16,
+        latmin: "latmin" => f32,
+        chi_sqr: "chi.sqr" => f64,
+        chi_sqr_dat: "chi.sqr.dat" => f64,
+        rms_err: "rms.err" => f64,
+        lon_shft: "lon.shft" => f32,
+        lat_shft: "lat.shft" => f32,
+        mlt_start: "mlt.start" => f64,
+        mlt_end: "mlt.end" => f64,
+        mlt_av: "mlt.av" => f64,
+        pot_drop: "pot.drop" => f64,
+        pot_drop_err: "pot.drop.err" => f64,
+        pot_max: "pot.max" => f64,
+        pot_max_err: "pot.max.err" => f64,
+        pot_min: "pot.min" => f64,
+        pot_min_err: "pot.min.err" => f64,
+        source: "source" => &str,
+        imf_delay: "IMF.delay" => i16,
+        imf_bx: "IMF.Bx" => f64,
+        imf_by: "IMF.By" => f64,
+        imf_bz: "IMF.Bz" => f64,
+        imf_vx: "IMF.Vx" => f64,
+        imf_tilt: "IMF.tilt" => f64,
+        imf_kp: "IMF.Kp" => f64,
+        model_angle: "model.angle" => &str,
+        model_level: "model.level" => &str,
+        model_tilt: "model.tilt" => &str,
+        model_name: "model.name" => &str,
+        noigrf: "noigrf" => i16,
+    ],
+    vectors: [
+        stid: "stid" => i16,
+        channel: "channel" => i16,
+        nvec: "nvec" => i16,
+        freq: "freq" => f32,
+        major_revision: "major.revision" => i16,
+        minor_revision: "minor.revision" => i16,
+        program_id: "program.id" => i16,
+        noise_mean: "noise.mean" => f32,
+        noise_sd: "noise.sd" => f32,
+        gsct: "gsct" => i16,
+        v_min: "v.min" => f32,
+        v_max: "v.max" => f32,
+        p_min: "p.min" => f32,
+        p_max: "p.max" => f32,
+        w_min: "w.min" => f32,
+        w_max: "w.max" => f32,
+        ve_min: "ve.min" => f32,
+        ve_max: "ve.max" => f32,
+        vector_mlat: "vector.mlat" => f32,
+        vector_mlon: "vector.mlon" => f32,
+        vector_kvect: "vector.kvect" => f32,
+        vector_stid: "vector.stid" => i16,
+        vector_channel: "vector.channel" => i16,
+        vector_index: "vector.index" => i32,
+        vector_srng: "vector.srng" => f32,
+        vector_vel_median: "vector.vel.median" => f32,
+        vector_vel_sd: "vector.vel.sd" => f32,
+        vector_pwr_median: "vector.pwr.median" => f32,
+        vector_pwr_sd: "vector.pwr.sd" => f32,
+        vector_wdt_median: "vector.wdt.median" => f32,
+        vector_wdt_sd: "vector.wdt.sd" => f32,
+        n: "N" => f64,
+        n_1: "N+1" => f64,
+        n_2: "N+2" => f64,
+        n_3: "N+3" => f64,
+        model_mlat: "model.mlat" => f32,
+        model_mlon: "model.mlon" => f32,
+        model_kvect: "model.kvect" => f32,
+        model_vel_median: "model.vel.median" => f32,
+        boundary_mlat: "boundary.mlat" => f32,
+        boundary_mlon: "boundary.mlon" => f32,
+    ],
+);
+
 impl Record<'_> for MapRecord {
     fn inner(self) -> IndexMap<String, DmapField> {
         self.data
     }
+    fn data(&self) -> &IndexMap<String, DmapField> {
+        &self.data
+    }
 
     fn new(fields: &mut IndexMap<String, DmapField>) -> Result<MapRecord, DmapError> {
+        Self::rename_legacy_fields(fields);
         match Self::check_fields(fields, &MAP_FIELDS) {
             Ok(_) => {}
             Err(e) => Err(e)?,
@@ -196,17 +306,22 @@ impl Record<'_> for MapRecord {
             data: fields.to_owned(),
         })
     }
-    fn to_bytes(&self) -> Result<Vec<u8>, DmapError> {
-        let (num_scalars, num_vectors, mut data_bytes) =
-            Self::data_to_bytes(&self.data, &MAP_FIELDS)?;
-
-        let mut bytes: Vec<u8> = vec![];
-        bytes.extend((65537_i32).as_bytes()); // No idea why this is what it is, copied from backscatter
-        bytes.extend((data_bytes.len() as i32 + 16).as_bytes()); // +16 for code, length, num_scalars, num_vectors
-        bytes.extend(num_scalars.as_bytes());
-        bytes.extend(num_vectors.as_bytes());
-        bytes.append(&mut data_bytes); // consumes data_bytes
-        Ok(bytes)
+    fn fields() -> Option<&'static Fields<'static>> {
+        Some(&MAP_FIELDS)
+    }
+    /// Renames the legacy `IMT.Kp` field to `IMF.Kp`, unless `IMF.Kp` is already present.
+    fn rename_legacy_fields(fields: &mut IndexMap<String, DmapField>) {
+        if !fields.contains_key("IMF.Kp") {
+            if let Some((index, _, value)) = fields.shift_remove_full(LEGACY_KP_FIELD) {
+                fields.shift_insert(index, "IMF.Kp".to_string(), value);
+            }
+        }
+    }
+    fn new_unchecked(fields: IndexMap<String, DmapField>) -> MapRecord {
+        MapRecord { data: fields }
+    }
+    fn to_bytes_into(&self, buf: &mut Vec<u8>) -> Result<(), DmapError> {
+        Self::data_to_bytes_into(&self.data, &MAP_FIELDS, buf)
     }
 }
 
@@ -1,5 +1,118 @@
 //! The supported DMAP file formats.
 
+/// Implements typed accessors on a record type with a `data: IndexMap<String, DmapField>` member.
+///
+/// Every record type gets a generic `get_as` method, and one method per listed field that returns
+/// the value of the field or a `DmapError` if the field is missing or has a different type.
+/// Scalar fields are returned by value (`&str` for strings) and vector fields as `&ArrayD`.
+///
+/// If a `builder` type is named, it is defined with one setter per listed field taking the same
+/// types, so that records can be constructed without building the `IndexMap` by hand.
+macro_rules! field_accessors {
+    (
+        $record:ty,
+        builder: $builder:ident,
+        scalars: [$($scalar:ident: $scalar_key:literal => $scalar_type:ty),* $(,)?],
+        vectors: [$($vector:ident: $vector_key:literal => $vector_type:ty),* $(,)?] $(,)?
+    ) => {
+        field_accessors!(
+            $record,
+            scalars: [$($scalar: $scalar_key => $scalar_type),*],
+            vectors: [$($vector: $vector_key => $vector_type),*],
+        );
+
+        #[doc = concat!("Builds a [`", stringify!($record), "`] one field at a time.")]
+        ///
+        /// Each setter replaces any previous value of its field.
+        #[derive(Debug, Clone, Default, PartialEq)]
+        pub struct $builder {
+            data: indexmap::IndexMap<String, $crate::types::DmapField>,
+        }
+
+        impl $builder {
+            /// Creates a builder with no fields set.
+            pub fn new() -> Self {
+                Self::default()
+            }
+
+            /// Builds the record, checking the fields as when reading a file.
+            ///
+            /// Returns `DmapError` listing every required field which was not set, or if the
+            /// fields are otherwise invalid.
+            pub fn build(mut self) -> Result<$record, $crate::error::DmapError> {
+                use $crate::formats::dmap::Record;
+                if let Some(fields) = <$record>::fields() {
+                    let missing: Vec<&str> = fields
+                        .scalars_required
+                        .iter()
+                        .chain(fields.vectors_required.iter())
+                        .map(|(name, _)| *name)
+                        .filter(|name| !self.data.contains_key(*name))
+                        .collect();
+                    if !missing.is_empty() {
+                        Err($crate::error::DmapError::InvalidRecord(format!(
+                            "Missing required fields {missing:?}"
+                        )))?
+                    }
+                }
+                <$record>::new(&mut self.data)
+            }
+            $(
+                #[doc = concat!("Sets the `", $scalar_key, "` scalar field.")]
+                pub fn $scalar(mut self, value: $scalar_type) -> Self {
+                    self.data.insert($scalar_key.to_string(), value.into());
+                    self
+                }
+            )*
+            $(
+                #[doc = concat!("Sets the `", $vector_key, "` vector field.")]
+                pub fn $vector(mut self, value: numpy::ndarray::ArrayD<$vector_type>) -> Self {
+                    self.data.insert($vector_key.to_string(), value.into());
+                    self
+                }
+            )*
+        }
+
+        impl $record {
+            #[doc = concat!("Creates a [`", stringify!($builder), "`] for constructing a record.")]
+            pub fn builder() -> $builder {
+                $builder::new()
+            }
+        }
+    };
+    (
+        $record:ty,
+        scalars: [$($scalar:ident: $scalar_key:literal => $scalar_type:ty),* $(,)?],
+        vectors: [$($vector:ident: $vector_key:literal => $vector_type:ty),* $(,)?] $(,)?
+    ) => {
+        impl $record {
+            /// Gets the field `key` as type `T`.
+            ///
+            /// Returns `DmapError` if `key` is not in the record, or the field is not of type `T`.
+            pub fn get_as<'a, T: $crate::types::FieldValue<'a>>(
+                &'a self,
+                key: &str,
+            ) -> Result<T, $crate::error::DmapError> {
+                $crate::types::get_field_as(&self.data, key)
+            }
+            $(
+                #[doc = concat!("Gets the `", $scalar_key, "` scalar field.")]
+                pub fn $scalar(&self) -> Result<$scalar_type, $crate::error::DmapError> {
+                    self.get_as($scalar_key)
+                }
+            )*
+            $(
+                #[doc = concat!("Gets the `", $vector_key, "` vector field.")]
+                pub fn $vector(
+                    &self,
+                ) -> Result<&numpy::ndarray::ArrayD<$vector_type>, $crate::error::DmapError> {
+                    self.get_as($vector_key)
+                }
+            )*
+        }
+    };
+}
+
 /// The generic [Dmap file format](https://radar-software-toolkit-rst.readthedocs.io/en/latest/references/general/dmap_data/)
 pub mod dmap;
 
@@ -20,3 +133,102 @@ pub mod rawacf;
 
 /// The [SND file format](https://github.com/SuperDARN/rst/pull/315)
 pub mod snd;
+
+use crate::error::DmapError;
+use crate::formats::dmap::GenericRecord;
+use crate::formats::fitacf::fitacf_fields;
+use crate::formats::iqdat::iqdat_fields;
+use crate::formats::rawacf::rawacf_fields;
+use crate::formats::snd::snd_fields;
+use lazy_static::lazy_static;
+use std::fmt;
+use std::str::FromStr;
+
+lazy_static! {
+    /// The names of the radar header scalars, which are required by every per-beam format
+    /// (IQDAT, RAWACF, FITACF and SND).
+    pub(crate) static ref RADAR_HEADER_SCALARS: Vec<&'static str> = rawacf_fields()
+        .scalars_required
+        .iter()
+        .map(|(name, _)| *name)
+        .filter(|name| {
+            [iqdat_fields(), fitacf_fields(), snd_fields()]
+                .iter()
+                .all(|fields| fields.scalars_required.iter().any(|(x, _)| x == name))
+        })
+        .collect();
+}
+
+/// The DMAP file formats that can be distinguished from the fields of a record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FileFormat {
+    /// A record that does not match any of the specific formats
+    Dmap,
+    Iqdat,
+    Rawacf,
+    Fitacf,
+    Grid,
+    Map,
+    Snd,
+}
+
+impl FileFormat {
+    /// Classifies `rec` by checking for fields that are unique to each format.
+    pub fn classify(rec: &GenericRecord) -> FileFormat {
+        let has = |key: &str| rec.data.contains_key(key);
+        if has("acfd") || has("rawacf.revision.major") {
+            FileFormat::Rawacf
+        } else if has("data") && has("seqnum") {
+            FileFormat::Iqdat
+        } else if has("snd.revision.major") {
+            FileFormat::Snd
+        } else if has("fitacf.revision.major") {
+            FileFormat::Fitacf
+        } else if has("fit.order") {
+            FileFormat::Map
+        } else if has("nvec") && has("start.year") {
+            FileFormat::Grid
+        } else {
+            FileFormat::Dmap
+        }
+    }
+
+    /// The lowercase name of the format, e.g. `"rawacf"`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileFormat::Dmap => "dmap",
+            FileFormat::Iqdat => "iqdat",
+            FileFormat::Rawacf => "rawacf",
+            FileFormat::Fitacf => "fitacf",
+            FileFormat::Grid => "grid",
+            FileFormat::Map => "map",
+            FileFormat::Snd => "snd",
+        }
+    }
+}
+
+impl fmt::Display for FileFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for FileFormat {
+    type Err = DmapError;
+
+    /// Parses the lowercase name of a format, e.g. `"rawacf"`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dmap" => Ok(FileFormat::Dmap),
+            "iqdat" => Ok(FileFormat::Iqdat),
+            "rawacf" => Ok(FileFormat::Rawacf),
+            "fitacf" => Ok(FileFormat::Fitacf),
+            "grid" => Ok(FileFormat::Grid),
+            "map" => Ok(FileFormat::Map),
+            "snd" => Ok(FileFormat::Snd),
+            _ => Err(DmapError::InvalidRecord(format!(
+                "Unknown file format {s}, expected one of dmap, iqdat, rawacf, fitacf, grid, map, snd"
+            ))),
+        }
+    }
+}
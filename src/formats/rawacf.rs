@@ -1,8 +1,10 @@
 use crate::error::DmapError;
 use crate::formats::dmap::Record;
-use crate::types::{DmapField, DmapType, Fields, Type};
+use crate::types::{DmapField, Fields, Type};
 use indexmap::IndexMap;
 use lazy_static::lazy_static;
+use numpy::ndarray::{ArrayD, Axis};
+use numpy::Complex32;
 
 static SCALAR_FIELDS: [(&str, Type); 47] = [
     ("radar.revision.major", Type::Char),
@@ -66,6 +68,9 @@ static VECTOR_FIELDS: [(&str, Type); 5] = [
 
 static VECTOR_FIELDS_OPT: [(&str, Type); 1] = [("xcfd", Type::Float)];
 
+/// This defines the groups of vector fields that must have the same dimensionality.
+static MATCHED_VECS: [[&str; 2]; 1] = [["acfd", "xcfd"]];
+
 lazy_static! {
     static ref RAWACF_FIELDS: Fields<'static> = Fields {
         all_fields: {
@@ -80,11 +85,54 @@ lazy_static! {
         scalars_optional: SCALAR_FIELDS_OPT.to_vec(),
         vectors_required: VECTOR_FIELDS.to_vec(),
         vectors_optional: VECTOR_FIELDS_OPT.to_vec(),
-        vector_dim_groups: vec![],
+        vector_dim_groups: MATCHED_VECS.to_vec().iter().map(|x| x.to_vec()).collect(),
     };
 }
 
+/// Returns the names and types of the fields of a RAWACF record.
+///
+/// ```
+/// let fields = dmap::formats::rawacf::rawacf_fields();
+/// let names: Vec<&str> = fields.scalars_required.iter().map(|(name, _)| *name).collect();
+/// assert!(names.contains(&"stid"));
+/// ```
+pub fn rawacf_fields() -> &'static Fields<'static> {
+    &RAWACF_FIELDS
+}
+
+/// Converts an array of interleaved real and imaginary parts, such as `acfd` or `xcfd`, into an
+/// array of complex values. The last axis of `arr` holds the real and imaginary parts and is
+/// removed, so e.g. `acfd` of shape `[n, mplgs, 2]` becomes `[n, mplgs]`.
+///
+/// Returns `DmapError` if the last axis of `arr` does not have length 2.
+pub fn to_complex(arr: &ArrayD<f32>) -> Result<ArrayD<Complex32>, DmapError> {
+    match arr.shape().last() {
+        Some(2) => Ok(arr.map_axis(Axis(arr.ndim() - 1), |x| Complex32::new(x[0], x[1]))),
+        _ => Err(DmapError::InvalidVector(format!(
+            "Cannot convert array of shape {:?} to complex, last dimension must be 2",
+            arr.shape()
+        ))),
+    }
+}
+
+/// Gets the `acfd` field of `rec` as complex values, with shape `[len(slist), mplgs]`.
+pub fn acfd_complex(rec: &RawacfRecord) -> Result<ArrayD<Complex32>, DmapError> {
+    to_complex(rec.acfd()?)
+}
+
+/// Gets the `xcfd` field of `rec` as complex values, with shape `[len(slist), mplgs]`.
+///
+/// Returns `DmapError` if `rec` has no `xcfd` field.
+pub fn xcfd_complex(rec: &RawacfRecord) -> Result<ArrayD<Complex32>, DmapError> {
+    to_complex(rec.xcfd()?)
+}
+
 /// Struct containing the checked fields of a single RAWACF record.
+///
+/// The multi-dimensional vector fields have axes:
+/// * `ltab`: `[mplgs + 1, 2]`, the pair of pulses which form each lag
+/// * `acfd` and `xcfd`: `[len(slist), mplgs, 2]`, the range gates listed in `slist`, then the
+///   lags, then the real and imaginary parts
 #[derive(Debug, PartialEq, Clone)]
 pub struct RawacfRecord {
     pub data: IndexMap<String, DmapField>,
@@ -102,10 +150,77 @@ impl RawacfRecord {
     }
 }
 
+field_accessors!(
+    RawacfRecord,
+    builder: RawacfRecordBuilder,
+    scalars: [
+        radar_revision_major: "radar.revision.major" => i8,
+        radar_revision_minor: "radar.revision.minor" => i8,
+        origin_code: "origin.code" => i8,
+        origin_time: "origin.time" => &str,
+        origin_command: "origin.command" => &str,
+        cp: "cp" => i16,
+        stid: "stid" => i16,
+        time_yr: "time.yr" => i16,
+        time_mo: "time.mo" => i16,
+        time_dy: "time.dy" => i16,
+        time_hr: "time.hr" => i16,
+        time_mt: "time.mt" => i16,
+        time_sc: "time.sc" => i16,
+        time_us: "time.us" => i32,
+        txpow: "txpow" => i16,
+        nave: "nave" => i16,
+        atten: "atten" => i16,
+        lagfr: "lagfr" => i16,
+        smsep: "smsep" => i16,
+        ercod: "ercod" => i16,
+        stat_agc: "stat.agc" => i16,
+        stat_lopwr: "stat.lopwr" => i16,
+        noise_search: "noise.search" => f32,
+        noise_mean: "noise.mean" => f32,
+        channel: "channel" => i16,
+        bmnum: "bmnum" => i16,
+        bmazm: "bmazm" => f32,
+        scan: "scan" => i16,
+        offset: "offset" => i16,
+        rxrise: "rxrise" => i16,
+        intt_sc: "intt.sc" => i16,
+        intt_us: "intt.us" => i32,
+        txpl: "txpl" => i16,
+        mpinc: "mpinc" => i16,
+        mppul: "mppul" => i16,
+        mplgs: "mplgs" => i16,
+        nrang: "nrang" => i16,
+        frang: "frang" => i16,
+        rsep: "rsep" => i16,
+        xcf: "xcf" => i16,
+        tfreq: "tfreq" => i16,
+        mxpwr: "mxpwr" => i32,
+        lvmax: "lvmax" => i32,
+        combf: "combf" => &str,
+        rawacf_revision_major: "rawacf.revision.major" => i32,
+        rawacf_revision_minor: "rawacf.revision.minor" => i32,
+        thr: "thr" => f32,
+        mplgexs: "mplgexs" => i16,
+        ifmode: "ifmode" => i16,
+    ],
+    vectors: [
+        ptab: "ptab" => i16,
+        ltab: "ltab" => i16,
+        pwr0: "pwr0" => f32,
+        slist: "slist" => i16,
+        acfd: "acfd" => f32,
+        xcfd: "xcfd" => f32,
+    ],
+);
+
 impl Record<'_> for RawacfRecord {
     fn inner(self) -> IndexMap<String, DmapField> {
         self.data
     }
+    fn data(&self) -> &IndexMap<String, DmapField> {
+        &self.data
+    }
     fn new(fields: &mut IndexMap<String, DmapField>) -> Result<RawacfRecord, DmapError> {
         match Self::check_fields(fields, &RAWACF_FIELDS) {
             Ok(_) => {}
@@ -116,17 +231,14 @@ impl Record<'_> for RawacfRecord {
             data: fields.to_owned(),
         })
     }
-    fn to_bytes(&self) -> Result<Vec<u8>, DmapError> {
-        let (num_scalars, num_vectors, mut data_bytes) =
-            Self::data_to_bytes(&self.data, &RAWACF_FIELDS)?;
-
-        let mut bytes: Vec<u8> = vec![];
-        bytes.extend((65537_i32).as_bytes()); // No idea why this is what it is, copied from backscatter
-        bytes.extend((data_bytes.len() as i32 + 16).as_bytes()); // +16 for code, length, num_scalars, num_vectors
-        bytes.extend(num_scalars.as_bytes());
-        bytes.extend(num_vectors.as_bytes());
-        bytes.append(&mut data_bytes); // consumes data_bytes
-        Ok(bytes)
+    fn fields() -> Option<&'static Fields<'static>> {
+        Some(&RAWACF_FIELDS)
+    }
+    fn new_unchecked(fields: IndexMap<String, DmapField>) -> RawacfRecord {
+        RawacfRecord { data: fields }
+    }
+    fn to_bytes_into(&self, buf: &mut Vec<u8>) -> Result<(), DmapError> {
+        Self::data_to_bytes_into(&self.data, &RAWACF_FIELDS, buf)
     }
 }
 
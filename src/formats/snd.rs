@@ -1,6 +1,6 @@
 use crate::error::DmapError;
 use crate::formats::dmap::Record;
-use crate::types::{DmapField, DmapType, Fields, Type};
+use crate::types::{DmapField, Fields, Type};
 use indexmap::IndexMap;
 use lazy_static::lazy_static;
 use std::convert::TryFrom;
@@ -49,6 +49,8 @@ static SCALAR_FIELDS_OPT: [(&str, Type); 0] = [];
 
 static VECTOR_FIELDS: [(&str, Type); 0] = [];
 
+/// The range-gate vectors are only written for gates with valid data, and the elevation fields
+/// (`x_qflg`, `phi0`, `phi0_e`) are only written when `xcf` is set, following RST's `SndWrite`.
 static VECTOR_FIELDS_OPT: [(&str, Type); 10] = [
     ("slist", Type::Short),
     ("qflg", Type::Char),
@@ -62,6 +64,7 @@ static VECTOR_FIELDS_OPT: [(&str, Type); 10] = [
     ("phi0_e", Type::Float),
 ];
 
+/// This defines the groups of vector fields that must have the same dimensionality.
 static MATCHED_VECS: [[&str; 10]; 1] = [[
     "slist", "qflg", "gflg", "v", "v_e", "p_l", "w_l", "x_qflg", "phi0", "phi0_e",
 ]];
@@ -84,6 +87,11 @@ lazy_static! {
     };
 }
 
+/// Returns the names and types of the fields of a SND record.
+pub fn snd_fields() -> &'static Fields<'static> {
+    &SND_FIELDS
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct SndRecord {
     pub data: IndexMap<String, DmapField>,
@@ -97,10 +105,69 @@ impl SndRecord {
         self.data.keys().collect()
     }
 }
+
+field_accessors!(
+    SndRecord,
+    builder: SndRecordBuilder,
+    scalars: [
+        radar_revision_major: "radar.revision.major" => i8,
+        radar_revision_minor: "radar.revision.minor" => i8,
+        origin_code: "origin.code" => i8,
+        origin_time: "origin.time" => &str,
+        origin_command: "origin.command" => &str,
+        cp: "cp" => i16,
+        stid: "stid" => i16,
+        time_yr: "time.yr" => i16,
+        time_mo: "time.mo" => i16,
+        time_dy: "time.dy" => i16,
+        time_hr: "time.hr" => i16,
+        time_mt: "time.mt" => i16,
+        time_sc: "time.sc" => i16,
+        time_us: "time.us" => i32,
+        nave: "nave" => i16,
+        lagfr: "lagfr" => i16,
+        smsep: "smsep" => i16,
+        noise_search: "noise.search" => f32,
+        noise_mean: "noise.mean" => f32,
+        channel: "channel" => i16,
+        bmnum: "bmnum" => i16,
+        bmazm: "bmazm" => f32,
+        scan: "scan" => i16,
+        rxrise: "rxrise" => i16,
+        intt_sc: "intt.sc" => i16,
+        intt_us: "intt.us" => i32,
+        nrang: "nrang" => i16,
+        frang: "frang" => i16,
+        rsep: "rsep" => i16,
+        xcf: "xcf" => i16,
+        tfreq: "tfreq" => i16,
+        noise_sky: "noise.sky" => f32,
+        combf: "combf" => &str,
+        fitacf_revision_major: "fitacf.revision.major" => i32,
+        fitacf_revision_minor: "fitacf.revision.minor" => i32,
+        snd_revision_major: "snd.revision.major" => i16,
+        snd_revision_minor: "snd.revision.minor" => i16,
+    ],
+    vectors: [
+        slist: "slist" => i16,
+        qflg: "qflg" => i8,
+        gflg: "gflg" => i8,
+        v: "v" => f32,
+        v_e: "v_e" => f32,
+        p_l: "p_l" => f32,
+        w_l: "w_l" => f32,
+        x_qflg: "x_qflg" => i8,
+        phi0: "phi0" => f32,
+        phi0_e: "phi0_e" => f32,
+    ],
+);
 impl Record<'_> for SndRecord {
     fn inner(self) -> IndexMap<String, DmapField> {
         self.data
     }
+    fn data(&self) -> &IndexMap<String, DmapField> {
+        &self.data
+    }
 
     fn new(fields: &mut IndexMap<String, DmapField>) -> Result<SndRecord, DmapError> {
         match Self::check_fields(fields, &SND_FIELDS) {
@@ -112,17 +179,14 @@ impl Record<'_> for SndRecord {
             data: fields.to_owned(),
         })
     }
-    fn to_bytes(&self) -> Result<Vec<u8>, DmapError> {
-        let (num_scalars, num_vectors, mut data_bytes) =
-            Self::data_to_bytes(&self.data, &SND_FIELDS)?;
-
-        let mut bytes: Vec<u8> = vec![];
-        bytes.extend((65537_i32).as_bytes()); // No idea why this is what it is, copied from backscatter
-        bytes.extend((data_bytes.len() as i32 + 16).as_bytes()); // +16 for code, length, num_scalars, num_vectors
-        bytes.extend(num_scalars.as_bytes());
-        bytes.extend(num_vectors.as_bytes());
-        bytes.append(&mut data_bytes); // consumes data_bytes
-        Ok(bytes)
+    fn fields() -> Option<&'static Fields<'static>> {
+        Some(&SND_FIELDS)
+    }
+    fn new_unchecked(fields: IndexMap<String, DmapField>) -> SndRecord {
+        SndRecord { data: fields }
+    }
+    fn to_bytes_into(&self, buf: &mut Vec<u8>) -> Result<(), DmapError> {
+        Self::data_to_bytes_into(&self.data, &SND_FIELDS, buf)
     }
 }
 
@@ -0,0 +1,140 @@
+//! Random access to the records of an uncompressed DMAP file.
+//!
+//! A `DmapIndex` is built by walking only the code and size header fields of each record, so
+//! no scalars or vectors are parsed until a record is requested with `DmapIndex::read_record`.
+//!
+//! A manifest, built with `manifest`, instead holds the key scalars of every record, for
+//! searching an archive without keeping the records themselves.
+
+use crate::error::DmapError;
+use crate::filter::record_time;
+use crate::formats::dmap::Record;
+use crate::types::{get_field_as, read_record_header, DmapType};
+use std::fs::File;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use time::OffsetDateTime;
+
+/// The location of every record within a DMAP file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DmapIndex {
+    /// The `(offset, size)` in bytes of each record
+    pub records: Vec<(u64, u64)>,
+}
+
+impl DmapIndex {
+    /// Builds the index by reading the header of each record in `file`, starting from the
+    /// beginning of the file.
+    ///
+    /// Returns `DmapError` if a record header is truncated, a record declares a non-positive size,
+    /// or the final record extends past the end of the file.
+    pub fn build(file: &mut File) -> Result<DmapIndex, DmapError> {
+        let file_size = file.seek(SeekFrom::End(0))?;
+        let header_size = 2 * i32::size() as u64;
+        let mut header = vec![0; header_size as usize];
+        let mut records: Vec<(u64, u64)> = vec![];
+        let mut rec_start: u64 = 0;
+        while rec_start < file_size {
+            if rec_start + header_size > file_size {
+                return Err(DmapError::CorruptStream("Record header is truncated"));
+            }
+            file.seek(SeekFrom::Start(rec_start))?;
+            file.read_exact(&mut header)?;
+            let (_, size) = read_record_header(&header)?;
+            if size <= 0 {
+                return Err(DmapError::InvalidRecord(format!(
+                    "Record size {size} at byte {} <= 0",
+                    rec_start + i32::size() as u64
+                )));
+            }
+            if rec_start + size as u64 > file_size {
+                return Err(DmapError::TruncatedFile {
+                    expected: size as usize,
+                    available: (file_size - rec_start) as usize,
+                    byte: rec_start,
+                });
+            }
+            records.push((rec_start, size as u64));
+            rec_start += size as u64;
+        }
+        Ok(DmapIndex { records })
+    }
+
+    /// The number of records in the file.
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Whether the file contains no records.
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Reads and parses only record `i` of `file`.
+    ///
+    /// Returns `DmapError` if `i` is out of bounds or the record is invalid.
+    pub fn read_record<T: for<'a> Record<'a>>(
+        &self,
+        file: &mut File,
+        i: usize,
+    ) -> Result<T, DmapError> {
+        let (offset, size) = self.records.get(i).ok_or_else(|| {
+            DmapError::InvalidRecord(format!(
+                "Record {i} out of bounds for file with {} records",
+                self.records.len()
+            ))
+        })?;
+        file.seek(SeekFrom::Start(*offset))?;
+        let mut buffer = vec![0; *size as usize];
+        file.read_exact(&mut buffer)?;
+        T::parse_record(&mut Cursor::new(buffer.as_slice()))
+    }
+}
+
+/// The key scalars of one record of a file. Fields which the record does not have as a scalar
+/// are `None`, e.g. `bmnum` and `stid` for grid and map records.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordManifest {
+    /// The index of the record within the file
+    pub index: usize,
+    /// The offset in bytes of the record from the start of the decompressed file
+    pub byte_offset: u64,
+    /// The `stid` scalar
+    pub stid: Option<i16>,
+    /// The `bmnum` scalar
+    pub bmnum: Option<i16>,
+    /// The `channel` scalar
+    pub channel: Option<i16>,
+    /// The `cp` scalar
+    pub cp: Option<i16>,
+    /// The UT time of the record, as given by `record_time`
+    pub time: Option<OffsetDateTime>,
+}
+
+/// Reads the key scalars of each record of `infile`, a file of type `T`, dropping the rest of
+/// each record once it has been read.
+///
+/// Compressed files are decompressed as they are read. Returns `DmapError` if the file cannot be
+/// read or a record is invalid.
+pub fn manifest<T: for<'a> Record<'a>>(infile: &PathBuf) -> Result<Vec<RecordManifest>, DmapError> {
+    let mut records = T::iter_records(infile)?;
+    let mut manifest = vec![];
+    loop {
+        let byte_offset = records.offset();
+        let rec = match records.next() {
+            Some(rec) => rec?,
+            None => return Ok(manifest),
+        };
+        let data = rec.data();
+        let short = |key: &str| get_field_as::<i16>(data, key).ok();
+        manifest.push(RecordManifest {
+            index: manifest.len(),
+            byte_offset,
+            stid: short("stid"),
+            bmnum: short("bmnum"),
+            channel: short("channel"),
+            cp: short("cp"),
+            time: record_time(data).ok(),
+        });
+    }
+}
@@ -0,0 +1,266 @@
+//! JSON serialization of DMAP fields, enabled with the `serde` feature.
+//!
+//! Scalars are written with an explicit type tag, e.g. `{"type": "SHORT", "value": 5}`, and
+//! vectors with their type, shape and a flat array of the data in row-major order, e.g.
+//! `{"type": "FLOAT", "shape": [2, 2], "data": [1.0, 2.0, 3.0, 4.0]}`. Non-finite floating point
+//! values are written as the strings `"NaN"`, `"inf"` and `"-inf"`, so that every field
+//! round-trips losslessly.
+
+use crate::types::{DmapField, DmapScalar, DmapVec};
+use numpy::ndarray::{ArrayD, IxDyn};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A floating point value, with non-finite values represented as strings.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum JsonFloat {
+    Number(f64),
+    NonFinite(String),
+}
+impl From<f64> for JsonFloat {
+    fn from(value: f64) -> Self {
+        if value.is_finite() {
+            JsonFloat::Number(value)
+        } else {
+            JsonFloat::NonFinite(value.to_string())
+        }
+    }
+}
+impl TryFrom<JsonFloat> for f64 {
+    type Error = String;
+
+    fn try_from(value: JsonFloat) -> Result<Self, Self::Error> {
+        match value {
+            JsonFloat::Number(x) => Ok(x),
+            JsonFloat::NonFinite(x) => x.parse().map_err(|_| format!("Invalid float {x}")),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "UPPERCASE")]
+enum ScalarRepr {
+    Char(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Uchar(u8),
+    Ushort(u16),
+    Uint(u32),
+    Ulong(u64),
+    Float(JsonFloat),
+    Double(JsonFloat),
+    String(String),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "UPPERCASE")]
+enum VecRepr {
+    Char {
+        shape: Vec<usize>,
+        data: Vec<i8>,
+    },
+    Short {
+        shape: Vec<usize>,
+        data: Vec<i16>,
+    },
+    Int {
+        shape: Vec<usize>,
+        data: Vec<i32>,
+    },
+    Long {
+        shape: Vec<usize>,
+        data: Vec<i64>,
+    },
+    Uchar {
+        shape: Vec<usize>,
+        data: Vec<u8>,
+    },
+    Ushort {
+        shape: Vec<usize>,
+        data: Vec<u16>,
+    },
+    Uint {
+        shape: Vec<usize>,
+        data: Vec<u32>,
+    },
+    Ulong {
+        shape: Vec<usize>,
+        data: Vec<u64>,
+    },
+    Float {
+        shape: Vec<usize>,
+        data: Vec<JsonFloat>,
+    },
+    Double {
+        shape: Vec<usize>,
+        data: Vec<JsonFloat>,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum FieldRepr {
+    Vector(VecRepr),
+    Scalar(ScalarRepr),
+}
+
+impl From<&DmapScalar> for ScalarRepr {
+    fn from(value: &DmapScalar) -> Self {
+        match value {
+            DmapScalar::Char(x) => ScalarRepr::Char(*x),
+            DmapScalar::Short(x) => ScalarRepr::Short(*x),
+            DmapScalar::Int(x) => ScalarRepr::Int(*x),
+            DmapScalar::Long(x) => ScalarRepr::Long(*x),
+            DmapScalar::Uchar(x) => ScalarRepr::Uchar(*x),
+            DmapScalar::Ushort(x) => ScalarRepr::Ushort(*x),
+            DmapScalar::Uint(x) => ScalarRepr::Uint(*x),
+            DmapScalar::Ulong(x) => ScalarRepr::Ulong(*x),
+            DmapScalar::Float(x) => ScalarRepr::Float((*x as f64).into()),
+            DmapScalar::Double(x) => ScalarRepr::Double((*x).into()),
+            DmapScalar::String(x) => ScalarRepr::String(x.clone()),
+        }
+    }
+}
+impl TryFrom<ScalarRepr> for DmapScalar {
+    type Error = String;
+
+    fn try_from(value: ScalarRepr) -> Result<Self, Self::Error> {
+        Ok(match value {
+            ScalarRepr::Char(x) => DmapScalar::Char(x),
+            ScalarRepr::Short(x) => DmapScalar::Short(x),
+            ScalarRepr::Int(x) => DmapScalar::Int(x),
+            ScalarRepr::Long(x) => DmapScalar::Long(x),
+            ScalarRepr::Uchar(x) => DmapScalar::Uchar(x),
+            ScalarRepr::Ushort(x) => DmapScalar::Ushort(x),
+            ScalarRepr::Uint(x) => DmapScalar::Uint(x),
+            ScalarRepr::Ulong(x) => DmapScalar::Ulong(x),
+            ScalarRepr::Float(x) => DmapScalar::Float(f64::try_from(x)? as f32),
+            ScalarRepr::Double(x) => DmapScalar::Double(f64::try_from(x)?),
+            ScalarRepr::String(x) => DmapScalar::String(x),
+        })
+    }
+}
+
+/// Flattens `arr` into row-major order.
+fn flatten<T: Clone>(arr: &ArrayD<T>) -> (Vec<usize>, Vec<T>) {
+    (arr.shape().to_vec(), arr.iter().cloned().collect())
+}
+
+/// Reshapes row-major `data` into an array of dimensions `shape`.
+fn reshape<T>(shape: Vec<usize>, data: Vec<T>) -> Result<ArrayD<T>, String> {
+    ArrayD::from_shape_vec(IxDyn(&shape), data).map_err(|e| format!("Invalid shape {shape:?}: {e}"))
+}
+
+/// Converts each element of `data` from a `JsonFloat`.
+fn from_floats(data: Vec<JsonFloat>) -> Result<Vec<f64>, String> {
+    data.into_iter().map(f64::try_from).collect()
+}
+
+impl From<&DmapVec> for VecRepr {
+    fn from(value: &DmapVec) -> Self {
+        match value {
+            DmapVec::Char(x) => {
+                let (shape, data) = flatten(x);
+                VecRepr::Char { shape, data }
+            }
+            DmapVec::Short(x) => {
+                let (shape, data) = flatten(x);
+                VecRepr::Short { shape, data }
+            }
+            DmapVec::Int(x) => {
+                let (shape, data) = flatten(x);
+                VecRepr::Int { shape, data }
+            }
+            DmapVec::Long(x) => {
+                let (shape, data) = flatten(x);
+                VecRepr::Long { shape, data }
+            }
+            DmapVec::Uchar(x) => {
+                let (shape, data) = flatten(x);
+                VecRepr::Uchar { shape, data }
+            }
+            DmapVec::Ushort(x) => {
+                let (shape, data) = flatten(x);
+                VecRepr::Ushort { shape, data }
+            }
+            DmapVec::Uint(x) => {
+                let (shape, data) = flatten(x);
+                VecRepr::Uint { shape, data }
+            }
+            DmapVec::Ulong(x) => {
+                let (shape, data) = flatten(x);
+                VecRepr::Ulong { shape, data }
+            }
+            DmapVec::Float(x) => VecRepr::Float {
+                shape: x.shape().to_vec(),
+                data: x.iter().map(|&y| (y as f64).into()).collect(),
+            },
+            DmapVec::Double(x) => VecRepr::Double {
+                shape: x.shape().to_vec(),
+                data: x.iter().map(|&y| y.into()).collect(),
+            },
+        }
+    }
+}
+impl TryFrom<VecRepr> for DmapVec {
+    type Error = String;
+
+    fn try_from(value: VecRepr) -> Result<Self, Self::Error> {
+        Ok(match value {
+            VecRepr::Char { shape, data } => DmapVec::Char(reshape(shape, data)?),
+            VecRepr::Short { shape, data } => DmapVec::Short(reshape(shape, data)?),
+            VecRepr::Int { shape, data } => DmapVec::Int(reshape(shape, data)?),
+            VecRepr::Long { shape, data } => DmapVec::Long(reshape(shape, data)?),
+            VecRepr::Uchar { shape, data } => DmapVec::Uchar(reshape(shape, data)?),
+            VecRepr::Ushort { shape, data } => DmapVec::Ushort(reshape(shape, data)?),
+            VecRepr::Uint { shape, data } => DmapVec::Uint(reshape(shape, data)?),
+            VecRepr::Ulong { shape, data } => DmapVec::Ulong(reshape(shape, data)?),
+            VecRepr::Float { shape, data } => {
+                let data = from_floats(data)?.into_iter().map(|x| x as f32).collect();
+                DmapVec::Float(reshape(shape, data)?)
+            }
+            VecRepr::Double { shape, data } => DmapVec::Double(reshape(shape, from_floats(data)?)?),
+        })
+    }
+}
+
+impl Serialize for DmapScalar {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ScalarRepr::from(self).serialize(serializer)
+    }
+}
+impl<'de> Deserialize<'de> for DmapScalar {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        DmapScalar::try_from(ScalarRepr::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
+
+impl Serialize for DmapVec {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        VecRepr::from(self).serialize(serializer)
+    }
+}
+impl<'de> Deserialize<'de> for DmapVec {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        DmapVec::try_from(VecRepr::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
+
+impl Serialize for DmapField {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            DmapField::Scalar(x) => x.serialize(serializer),
+            DmapField::Vector(x) => x.serialize(serializer),
+        }
+    }
+}
+impl<'de> Deserialize<'de> for DmapField {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match FieldRepr::deserialize(deserializer)? {
+            FieldRepr::Scalar(x) => Ok(DmapField::Scalar(x.try_into().map_err(D::Error::custom)?)),
+            FieldRepr::Vector(x) => Ok(DmapField::Vector(x.try_into().map_err(D::Error::custom)?)),
+        }
+    }
+}
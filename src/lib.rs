@@ -4,115 +4,441 @@
 //!
 //! For more information about DMAP files, see [RST](https://radar-software-toolkit-rst.readthedocs.io/en/latest/)
 //! or [pyDARNio](https://pydarnio.readthedocs.io/en/latest/).
+//!
+//! Reads and writes log record counts, sizes and timings through the [`log`](https://docs.rs/log)
+//! facade: at the debug level for each file or buffer, and at the trace level for each record.
+//! Nothing is logged unless the application installs a logger.
+
+// The `#[pyfunction]` expansion in pyo3 0.22 converts `PyErr` into itself
+#![allow(clippy::useless_conversion)]
 
+#[cfg(feature = "arrow")]
+pub mod columnar;
+pub mod diff;
 pub mod error;
+pub mod filter;
 pub mod formats;
+pub mod index;
+#[cfg(feature = "serde")]
+pub mod json;
 pub mod types;
 
-use crate::error::DmapError;
-use crate::formats::dmap::{GenericRecord, Record};
-use crate::formats::fitacf::FitacfRecord;
+use crate::error::{BadRecordsError, DmapError, TruncatedFileError};
+use crate::formats::dmap::{
+    open_for_write, summarize, with_threads, Compression, GenericRecord, ReadOptions, Record,
+    RecordIter, Sink, ValidationMode, WriteMode, WriteOptions,
+};
+use crate::formats::fitacf::{strip_xcf, FitacfRecord};
 use crate::formats::grid::GridRecord;
 use crate::formats::iqdat::IqdatRecord;
 use crate::formats::map::MapRecord;
-use crate::formats::rawacf::RawacfRecord;
+use crate::formats::rawacf::{rawacf_fields, to_complex, RawacfRecord};
 use crate::formats::snd::SndRecord;
-use crate::types::DmapField;
-use bzip2::read::BzEncoder;
-use bzip2::Compression;
+use crate::formats::FileFormat;
+use crate::index::{manifest, DmapIndex};
+use crate::types::{
+    field_types, get_field_as, read_record_header, DmapField, DmapScalar, DmapType, DmapVec,
+    Endianness,
+};
+use bzip2::read::MultiBzDecoder;
+use flate2::read::MultiGzDecoder;
 use indexmap::IndexMap;
+use log::debug;
+use numpy::ndarray::{Array1, ArrayD};
+use numpy::{Complex32, IntoPyArray, PyArrayDyn};
 use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
 use rayon::iter::Either;
 use rayon::prelude::*;
-use std::ffi::OsStr;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fmt::Debug;
-use std::fs::{File, OpenOptions};
-use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::fs::File;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 
 /// Write bytes to file.
 ///
-/// Ordinarily, this function opens the file in `append` mode. If the extension of `outfile` is
-/// `.bz2`, the bytes will be compressed using bzip2 before being written, and the file is instead
-/// opened in `create_new` mode, meaning it will fail if a file already exists at the given path.
-fn write_to_file(bytes: Vec<u8>, outfile: &PathBuf) -> Result<(), std::io::Error> {
-    let mut out_bytes: Vec<u8> = vec![];
-    let mut file: File = OpenOptions::new().append(true).create(true).open(outfile)?;
-    match outfile.extension() {
-        Some(ext) if ext == OsStr::new("bz2") => {
-            let mut compressor = BzEncoder::new(bytes.as_slice(), Compression::best());
-            compressor.read_to_end(&mut out_bytes)?;
-        }
-        _ => {
-            out_bytes = bytes;
-        }
-    }
-    file.write_all(&out_bytes)
+/// The file is opened according to `opts.mode`. If the extension of `outfile` is `.bz2`, `.gz` or
+/// `.zst`, the bytes will be compressed using bzip2, gzip or zstd respectively before being
+/// written, with compression level `opts.level` if given.
+fn write_to_file(
+    bytes: Vec<u8>,
+    outfile: &PathBuf,
+    opts: &WriteOptions,
+) -> Result<(), std::io::Error> {
+    let start = Instant::now();
+    let mut sink = Sink::create(outfile, opts)?;
+    sink.write_all(&bytes)?;
+    sink.finish()?;
+    debug!(
+        "Wrote {} bytes to {} in {:?}",
+        bytes.len(),
+        outfile.display(),
+        start.elapsed()
+    );
+    Ok(())
 }
 
-/// Writes a collection of `impl Record`s to `outfile`
-fn write_generic<'a>(mut recs: Vec<impl Record<'a>>, outfile: &PathBuf) -> Result<(), DmapError> {
+/// Converts a collection of `impl Record`s into DMAP-formatted bytes, keeping the order of the
+/// fields of each record if `preserve_order`.
+fn serialize_records<'a>(
+    mut recs: Vec<impl Record<'a>>,
+    preserve_order: bool,
+) -> Result<Vec<u8>, DmapError> {
+    let start = Instant::now();
     let mut bytes: Vec<u8> = vec![];
     let (errors, rec_bytes): (Vec<_>, Vec<_>) =
-        recs.par_iter_mut()
-            .enumerate()
-            .partition_map(|(i, rec)| match rec.to_bytes() {
+        recs.par_iter_mut().enumerate().partition_map(|(i, rec)| {
+            let rec_bytes = if preserve_order {
+                rec.to_bytes_in_order()
+            } else {
+                rec.to_bytes()
+            };
+            match rec_bytes {
                 Err(e) => Either::Left((i, e)),
                 Ok(y) => Either::Right(y),
-            });
+            }
+        });
     if !errors.is_empty() {
         Err(DmapError::InvalidRecord(format!(
             "Corrupted records: {errors:?}"
         )))?
     }
     bytes.par_extend(rec_bytes.into_par_iter().flatten());
-    write_to_file(bytes, outfile)?;
+    debug!(
+        "Serialized {} records to {} bytes in {:?}",
+        recs.len(),
+        bytes.len(),
+        start.elapsed()
+    );
+    Ok(bytes)
+}
+
+/// Compresses `bytes` with `compression` at its default level.
+fn compress(bytes: Vec<u8>, compression: Compression) -> Result<Vec<u8>, DmapError> {
+    if compression == Compression::None {
+        return Ok(bytes);
+    }
+    let mut sink = Sink::new(vec![], compression, None)?;
+    sink.write_all(&bytes)?;
+    Ok(sink.finish()?)
+}
+
+/// Converts records of any type to the contents of a DMAP file, compressed with `compression`,
+/// without writing to the filesystem, e.g. for uploading to object storage.
+///
+/// The bytes are the same as those written to a new file by `write_records_opts`, with the
+/// compression chosen from the extension of the file. Returns `DmapError` if any record is invalid.
+pub fn records_to_bytes<'a>(
+    recs: Vec<impl Record<'a>>,
+    compression: Compression,
+) -> Result<Vec<u8>, DmapError> {
+    compress(serialize_records(recs, false)?, compression)
+}
+
+/// Writes a collection of `impl Record`s to `outfile` with the given options.
+fn write_generic<'a>(
+    recs: Vec<impl Record<'a>>,
+    outfile: &PathBuf,
+    opts: &WriteOptions,
+) -> Result<(), DmapError> {
+    let bytes = with_threads(opts.threads, || {
+        serialize_records(recs, opts.preserve_order)
+    })?;
+    write_to_file(bytes, outfile, opts)?;
+    Ok(())
+}
+
+/// Write records of any type to `writer`.
+///
+/// Unlike the file-writing functions, no compression is applied, so this can be used to stream
+/// records to a socket, an in-memory buffer or stdout. Nothing is written if any record is
+/// invalid.
+pub fn write_records_to<'a, W: Write>(
+    recs: Vec<impl Record<'a>>,
+    writer: &mut W,
+) -> Result<(), DmapError> {
+    let bytes = serialize_records(recs, false)?;
+    writer.write_all(&bytes)?;
+    Ok(())
+}
+
+/// Write records of any type to `outfile`, compressing with the given compression `level`.
+///
+/// The compression algorithm is chosen from the extension of `outfile` (`.bz2`, `.gz` or `.zst`).
+/// Valid levels are 1-9 for bzip2, 0-9 for gzip and 1-22 for zstd. `level` is ignored if
+/// `outfile` is not compressed.
+pub fn write_records_with<'a>(
+    recs: Vec<impl Record<'a>>,
+    outfile: &PathBuf,
+    level: i32,
+) -> Result<(), DmapError> {
+    let opts = WriteOptions {
+        level: Some(level),
+        ..Default::default()
+    };
+    write_generic(recs, outfile, &opts)
+}
+
+/// Write records of any type to `outfile` with the given options.
+///
+/// `opts.mode` controls whether an existing file is appended to, overwritten, or is an error.
+pub fn write_records_opts<'a>(
+    recs: Vec<impl Record<'a>>,
+    outfile: &PathBuf,
+    opts: &WriteOptions,
+) -> Result<(), DmapError> {
+    write_generic(recs, outfile, opts)
+}
+
+/// Write records of any type to `outfile`, compressing each group of `block_records` records as
+/// a separate stream, so that the blocks can be decompressed in parallel.
+///
+/// The compression is chosen from the extension of `outfile` (`.bz2`, `.gz` or `.zst`), and the
+/// streams are concatenated in one file, which is appended to if it exists. The readers in this
+/// crate decompress every stream of such a file. Returns `DmapError` if `outfile` has no
+/// compression extension, `block_records` is zero or any record is invalid.
+pub fn write_records_multistream<'a, T: Record<'a>>(
+    recs: Vec<T>,
+    outfile: &PathBuf,
+    block_records: usize,
+) -> Result<(), DmapError> {
+    let compression = Compression::from_path(outfile);
+    if compression == Compression::None || block_records == 0 {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!(
+                "Cannot write blocks of {block_records} records to {}: expected a .bz2, .gz or \
+                 .zst file and at least one record per block",
+                outfile.display()
+            ),
+        ))?
+    }
+    let mut blocks: Vec<Vec<T>> = vec![];
+    let mut recs = recs.into_iter();
+    loop {
+        let block: Vec<T> = recs.by_ref().take(block_records).collect();
+        if block.is_empty() {
+            break;
+        }
+        blocks.push(block);
+    }
+    let streams: Vec<Vec<u8>> = blocks
+        .into_par_iter()
+        .enumerate()
+        .map(|(i, block)| {
+            let mut bytes = vec![];
+            for (j, rec) in block.iter().enumerate() {
+                rec.to_bytes_into(&mut bytes).map_err(|e| {
+                    DmapError::InvalidRecord(format!("Record {}: {e}", i * block_records + j))
+                })?;
+            }
+            compress(bytes, compression)
+        })
+        .collect::<Result<_, _>>()?;
+    let mut file = open_for_write(outfile, WriteMode::Append)?;
+    for stream in streams.iter() {
+        file.write_all(stream)?;
+    }
+    debug!(
+        "Wrote {} streams of up to {block_records} records to {}",
+        streams.len(),
+        outfile.display()
+    );
     Ok(())
 }
 
 /// Write generic DMAP to `outfile`
 pub fn write_dmap(recs: Vec<GenericRecord>, outfile: &PathBuf) -> Result<(), DmapError> {
-    write_generic(recs, outfile)
+    write_generic(recs, outfile, &WriteOptions::default())
 }
 
 /// Write IQDAT records to `outfile`.
 pub fn write_iqdat(recs: Vec<IqdatRecord>, outfile: &PathBuf) -> Result<(), DmapError> {
-    write_generic(recs, outfile)
+    write_generic(recs, outfile, &WriteOptions::default())
 }
 
 /// Write RAWACF records to `outfile`.
 pub fn write_rawacf(recs: Vec<RawacfRecord>, outfile: &PathBuf) -> Result<(), DmapError> {
-    write_generic(recs, outfile)
+    write_generic(recs, outfile, &WriteOptions::default())
 }
 
 /// Write FITACF records to `outfile`.
 pub fn write_fitacf(recs: Vec<FitacfRecord>, outfile: &PathBuf) -> Result<(), DmapError> {
-    write_generic(recs, outfile)
+    write_generic(recs, outfile, &WriteOptions::default())
 }
 
 /// Write GRID records to `outfile`.
 pub fn write_grid(recs: Vec<GridRecord>, outfile: &PathBuf) -> Result<(), DmapError> {
-    write_generic(recs, outfile)
+    write_generic(recs, outfile, &WriteOptions::default())
 }
 
 /// Write MAP records to `outfile`.
 pub fn write_map(recs: Vec<MapRecord>, outfile: &PathBuf) -> Result<(), DmapError> {
-    write_generic(recs, outfile)
+    write_generic(recs, outfile, &WriteOptions::default())
 }
 
 /// Write SND records to `outfile`.
 pub fn write_snd(recs: Vec<SndRecord>, outfile: &PathBuf) -> Result<(), DmapError> {
-    write_generic(recs, outfile)
+    write_generic(recs, outfile, &WriteOptions::default())
 }
 
-/// Attempts to convert `recs` to `T` then append to `outfile`.
-fn try_write_generic<T: for<'a> Record<'a>>(
-    mut recs: Vec<IndexMap<String, DmapField>>,
+/// Reads each of `inputs` as a file of type `T`, then appends all records to `output` in input
+/// order.
+///
+/// Fails before writing anything if any input cannot be read as type `T`, returning
+/// `DmapError::InvalidRecord` naming the offending file.
+pub fn concat_files<T: for<'a> Record<'a>>(
+    inputs: &[PathBuf],
+    output: &PathBuf,
+) -> Result<(), DmapError> {
+    let mut recs: Vec<T> = vec![];
+    for infile in inputs {
+        let file_recs = T::read_file(infile).map_err(|e| {
+            DmapError::InvalidRecord(format!("Cannot read {}: {e}", infile.display()))
+        })?;
+        recs.extend(file_recs);
+    }
+    write_generic(recs, output, &WriteOptions::default())
+}
+
+/// Reads `infile` as a file of type `T` and writes its records to `outfile`, replacing any
+/// existing file, e.g. to recompress a file or write its fields in canonical order.
+///
+/// Both files are compressed according to their extensions, so e.g. rewriting `a.fitacf.bz2` to
+/// `a.fitacf.gz` changes bzip2 to gzip. All records are read before `outfile` is opened, so a file
+/// can be rewritten in place. Returns `DmapError` if `infile` cannot be read as type `T`.
+pub fn rewrite<T: for<'a> Record<'a>>(
+    infile: &PathBuf,
     outfile: &PathBuf,
-) -> Result<(), DmapError>
+) -> Result<(), DmapError> {
+    let opts = WriteOptions {
+        mode: WriteMode::Overwrite,
+        ..Default::default()
+    };
+    write_generic(T::read_file(infile)?, outfile, &opts)
+}
+
+/// Splits the records of `infile` into files in `outdir`, one per UT interval of `minutes`
+/// minutes, returning the paths of the files written.
+///
+/// Intervals are aligned to the start of the day when `minutes` divides evenly into a day, e.g.
+/// `60` gives hourly files. Each output is named from the time of its first record followed by the
+/// file name of `infile`, e.g. `20230404.0000.00.test.fitacf.bz2`, and is compressed according to
+/// its extension. Existing outputs are overwritten, so splitting a file again into the same
+/// `outdir` gives the same files. Returns `DmapError::InvalidRecord` if any record lacks usable
+/// time fields.
+pub fn split_by_interval<T: for<'a> Record<'a>>(
+    infile: &PathBuf,
+    outdir: &Path,
+    minutes: u32,
+) -> Result<Vec<PathBuf>, DmapError> {
+    if minutes == 0 {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "Interval must be at least one minute",
+        ))?
+    }
+    let file_name = infile
+        .file_name()
+        .ok_or_else(|| DmapError::InvalidRecord(format!("{} is not a file", infile.display())))?
+        .to_string_lossy();
+    let interval = minutes as i64 * 60;
+
+    let mut buckets: IndexMap<i64, Vec<T>> = IndexMap::new();
+    for (i, rec) in T::read_file(infile)?.into_iter().enumerate() {
+        let time = filter::record_time(rec.data())
+            .map_err(|e| DmapError::InvalidRecord(format!("Record {i} has no usable time: {e}")))?;
+        let bucket = time.unix_timestamp().div_euclid(interval);
+        buckets.entry(bucket).or_default().push(rec);
+    }
+
+    let opts = WriteOptions {
+        mode: WriteMode::Overwrite,
+        ..Default::default()
+    };
+    let mut outfiles: Vec<PathBuf> = vec![];
+    for recs in buckets.into_values() {
+        let time = filter::record_time(recs[0].data())?;
+        let outfile = outdir.join(format!(
+            "{:04}{:02}{:02}.{:02}{:02}.{:02}.{file_name}",
+            time.year(),
+            time.month() as u8,
+            time.day(),
+            time.hour(),
+            time.minute(),
+            time.second()
+        ));
+        write_generic(recs, &outfile, &opts)?;
+        outfiles.push(outfile);
+    }
+    Ok(outfiles)
+}
+
+/// Checks that each of `recs` has the same fields as the first, ignoring the optional fields of
+/// `T`, so that a batch which mixes records of different formats is caught before conversion.
+/// Legacy fields of each record are renamed first.
+///
+/// If `T` has no fixed fields, as for `GenericRecord`, every field is compared. Returns
+/// `DmapError::BadRecords` with the index of each record whose fields differ from the first.
+fn check_same_fields<T: for<'a> Record<'a>>(
+    recs: &mut [IndexMap<String, DmapField>],
+) -> Result<(), DmapError> {
+    recs.iter_mut().for_each(T::rename_legacy_fields);
+    let first = match recs.first() {
+        Some(x) => x,
+        None => return Ok(()),
+    };
+    let optional: HashSet<&str> = match T::fields() {
+        Some(fields) => fields
+            .scalars_optional
+            .iter()
+            .chain(fields.vectors_optional.iter())
+            .map(|(name, _)| *name)
+            .collect(),
+        None => HashSet::new(),
+    };
+    let names = |rec: &IndexMap<String, DmapField>| -> BTreeSet<String> {
+        rec.keys()
+            .filter(|name| !optional.contains(name.as_str()))
+            .cloned()
+            .collect()
+    };
+    let first_names = names(first);
+    let mut errors: Vec<(usize, DmapError)> = vec![];
+    for (i, rec) in recs.iter().enumerate().skip(1) {
+        let rec_names = names(rec);
+        if rec_names != first_names {
+            let missing: Vec<&String> = first_names.difference(&rec_names).collect();
+            let unexpected: Vec<&String> = rec_names.difference(&first_names).collect();
+            errors.push((
+                i,
+                DmapError::InvalidRecord(format!(
+                    "Record {i} has different fields to record 0: missing {missing:?}, \
+                    unexpected {unexpected:?}"
+                )),
+            ));
+        }
+    }
+    if !errors.is_empty() {
+        let (indices, mut errors): (Vec<_>, Vec<_>) = errors.into_iter().unzip();
+        Err(DmapError::BadRecords(
+            indices,
+            Box::new(errors.swap_remove(0)),
+        ))?
+    }
+    Ok(())
+}
+
+/// Attempts to convert `recs` to `T`, then converts them into DMAP-formatted bytes.
+fn try_serialize_records<T: for<'a> Record<'a>>(
+    mut recs: Vec<IndexMap<String, DmapField>>,
+) -> Result<Vec<u8>, DmapError>
 where
     for<'a> <T as TryFrom<&'a mut IndexMap<String, DmapField>>>::Error: Send + Debug,
 {
+    check_same_fields::<T>(&mut recs)?;
     let mut bytes: Vec<u8> = vec![];
     let (errors, rec_bytes): (Vec<_>, Vec<_>) =
         recs.par_iter_mut()
@@ -125,21 +451,37 @@ where
                 },
             });
     if !errors.is_empty() {
+        let (indices, mut errors): (Vec<_>, Vec<_>) = errors.into_iter().unzip();
         Err(DmapError::BadRecords(
-            errors.iter().map(|(i, _)| *i).collect(), errors[0].1.to_string()
+            indices,
+            Box::new(errors.swap_remove(0)),
         ))?
     }
     bytes.par_extend(rec_bytes.into_par_iter().flatten());
-    write_to_file(bytes, outfile)?;
+    Ok(bytes)
+}
+
+/// Attempts to convert `recs` to `T` then write to `outfile` with the given options.
+fn try_write_generic<T: for<'a> Record<'a>>(
+    recs: Vec<IndexMap<String, DmapField>>,
+    outfile: &PathBuf,
+    opts: &WriteOptions,
+) -> Result<(), DmapError>
+where
+    for<'a> <T as TryFrom<&'a mut IndexMap<String, DmapField>>>::Error: Send + Debug,
+{
+    let bytes = with_threads(opts.threads, || try_serialize_records::<T>(recs))?;
+    write_to_file(bytes, outfile, opts)?;
     Ok(())
 }
 
-/// Attempts to convert `recs` to `GenericRecord` then append to `outfile`.
+/// Attempts to convert `recs` to `GenericRecord` then append to `outfile`. Every record must have
+/// the same fields as the first.
 pub fn try_write_dmap(
     recs: Vec<IndexMap<String, DmapField>>,
     outfile: &PathBuf,
 ) -> Result<(), DmapError> {
-    try_write_generic::<GenericRecord>(recs, outfile)
+    try_write_generic::<GenericRecord>(recs, outfile, &WriteOptions::default())
 }
 
 /// Attempts to convert `recs` to `IqdatRecord` then append to `outfile`.
@@ -147,7 +489,7 @@ pub fn try_write_iqdat(
     recs: Vec<IndexMap<String, DmapField>>,
     outfile: &PathBuf,
 ) -> Result<(), DmapError> {
-    try_write_generic::<IqdatRecord>(recs, outfile)
+    try_write_generic::<IqdatRecord>(recs, outfile, &WriteOptions::default())
 }
 
 /// Attempts to convert `recs` to `RawacfRecord` then append to `outfile`.
@@ -155,7 +497,7 @@ pub fn try_write_rawacf(
     recs: Vec<IndexMap<String, DmapField>>,
     outfile: &PathBuf,
 ) -> Result<(), DmapError> {
-    try_write_generic::<RawacfRecord>(recs, outfile)
+    try_write_generic::<RawacfRecord>(recs, outfile, &WriteOptions::default())
 }
 
 /// Attempts to convert `recs` to `FitacfRecord` then append to `outfile`.
@@ -163,7 +505,7 @@ pub fn try_write_fitacf(
     recs: Vec<IndexMap<String, DmapField>>,
     outfile: &PathBuf,
 ) -> Result<(), DmapError> {
-    try_write_generic::<FitacfRecord>(recs, outfile)
+    try_write_generic::<FitacfRecord>(recs, outfile, &WriteOptions::default())
 }
 
 /// Attempts to convert `recs` to `GridRecord` then append to `outfile`.
@@ -171,7 +513,7 @@ pub fn try_write_grid(
     recs: Vec<IndexMap<String, DmapField>>,
     outfile: &PathBuf,
 ) -> Result<(), DmapError> {
-    try_write_generic::<GridRecord>(recs, outfile)
+    try_write_generic::<GridRecord>(recs, outfile, &WriteOptions::default())
 }
 
 /// Attempts to convert `recs` to `MapRecord` then append to `outfile`.
@@ -179,7 +521,7 @@ pub fn try_write_map(
     recs: Vec<IndexMap<String, DmapField>>,
     outfile: &PathBuf,
 ) -> Result<(), DmapError> {
-    try_write_generic::<MapRecord>(recs, outfile)
+    try_write_generic::<MapRecord>(recs, outfile, &WriteOptions::default())
 }
 
 /// Attempts to convert `recs` to `SndRecord` then append to `outfile`.
@@ -187,7 +529,7 @@ pub fn try_write_snd(
     recs: Vec<IndexMap<String, DmapField>>,
     outfile: &PathBuf,
 ) -> Result<(), DmapError> {
-    try_write_generic::<SndRecord>(recs, outfile)
+    try_write_generic::<SndRecord>(recs, outfile, &WriteOptions::default())
 }
 
 /// Read in a DMAP file
@@ -205,11 +547,147 @@ pub fn read_rawacf(infile: PathBuf) -> Result<Vec<RawacfRecord>, DmapError> {
     RawacfRecord::read_file(&infile)
 }
 
+/// Reads many files of type `T` in parallel, returning the result for each file in the order of
+/// `infiles`, so that one bad file doesn't prevent reading the others.
+pub fn read_files<T: for<'a> Record<'a>>(infiles: Vec<PathBuf>) -> Vec<Result<Vec<T>, DmapError>> {
+    infiles
+        .par_iter()
+        .map(|infile| T::read_file(infile))
+        .collect()
+}
+
+/// Reads a file of type `T`, accepting fields stored with the types in `opts.type_overrides` and
+/// casting them to the types expected by `T`.
+///
+/// Overrides only relax type checks, not field presence. Fields without an override must have
+/// the expected type, as with `Record::read_file`. With `ValidationMode::Lenient`, fields which
+/// are not part of `T` are kept as they are stored in the file.
+///
+/// If `opts.threads` is given, the records are parsed on a new thread pool of that size.
+pub fn read_file_with<T: for<'a> Record<'a>>(
+    infile: &PathBuf,
+    opts: &ReadOptions,
+) -> Result<Vec<T>, DmapError> {
+    with_threads(opts.threads, || read_file_with_global(infile, opts))
+}
+
+/// Reads a file as for `read_file_with`, on the current rayon thread pool.
+fn read_file_with_global<T: for<'a> Record<'a>>(
+    infile: &PathBuf,
+    opts: &ReadOptions,
+) -> Result<Vec<T>, DmapError> {
+    GenericRecord::read_file(infile)?
+        .into_par_iter()
+        .map(|rec| {
+            let original = rec.inner();
+            let mut fields = original.clone();
+            let mut unknown: IndexMap<String, DmapField> = IndexMap::new();
+            let mut result = T::try_from(&mut fields);
+            if let (Err(_), ValidationMode::Lenient, Some(known)) =
+                (&result, opts.validation, T::fields())
+            {
+                fields = original.clone();
+                unknown = original
+                    .iter()
+                    .filter(|(name, _)| !known.all_fields.contains(&name.as_str()))
+                    .map(|(name, field)| (name.clone(), field.clone()))
+                    .collect();
+                fields.retain(|name, _| !unknown.contains_key(name));
+                result = T::try_from(&mut fields);
+            }
+            let rec = result?;
+            for (name, field) in original
+                .iter()
+                .filter(|(name, _)| fields.contains_key(*name))
+            {
+                let (found, expected) = (field.get_type(), fields[name].get_type());
+                if found != expected && opts.type_overrides.get(name) != Some(&found) {
+                    Err(DmapError::InvalidRecord(format!(
+                        "Field {name} has incorrect type {found}, expected {expected}"
+                    )))?
+                }
+            }
+            if unknown.is_empty() {
+                Ok(rec)
+            } else {
+                let mut data = rec.inner();
+                data.extend(unknown);
+                Ok(T::new_unchecked(data))
+            }
+        })
+        .collect()
+}
+
+/// Reads a file of type `T` without checking that the fields of each record are valid for `T`.
+///
+/// Records are only checked to be well-formed DMAP, which is faster for files of many small
+/// records. The file is trusted to follow the format of `T`: missing, unknown or mistyped fields
+/// are not detected, so accessors may return errors and writing the records may fail or drop
+/// fields. Use `Record::read_file` for files which may not follow the format.
+pub fn read_file_unchecked<T: for<'a> Record<'a>>(infile: &PathBuf) -> Result<Vec<T>, DmapError> {
+    Ok(GenericRecord::read_file(infile)?
+        .into_iter()
+        .map(|rec| T::new_unchecked(rec.inner()))
+        .collect())
+}
+
 /// Read in a FITACF file
 pub fn read_fitacf(infile: PathBuf) -> Result<Vec<FitacfRecord>, DmapError> {
     FitacfRecord::read_file(&infile)
 }
 
+/// Read in the records of a FITACF file which have a `bmnum` in `beams`
+pub fn read_fitacf_beams(infile: PathBuf, beams: &[i16]) -> Result<Vec<FitacfRecord>, DmapError> {
+    FitacfRecord::read_file_filtered(
+        &infile,
+        |rec| matches!(get_field_as::<i16>(rec, "bmnum"), Ok(x) if beams.contains(&x)),
+    )
+}
+
+/// Read in the records of a FITACF file which have a `channel` of `channel`
+pub fn read_fitacf_channel(infile: PathBuf, channel: i16) -> Result<Vec<FitacfRecord>, DmapError> {
+    FitacfRecord::read_file_filtered(
+        &infile,
+        |rec| matches!(get_field_as::<i16>(rec, "channel"), Ok(x) if x == channel),
+    )
+}
+
+/// Checks that every record of a FITACF file is valid, returning the number of records.
+#[pyfunction]
+#[pyo3(name = "validate_fitacf")]
+#[pyo3(text_signature = "(infile: str, /)")]
+fn validate_fitacf_py(py: Python<'_>, infile: PathBuf) -> PyResult<usize> {
+    py.allow_threads(|| FitacfRecord::validate_file(&infile))
+        .map_err(PyErr::from)
+}
+
+/// Removes the cross-correlation (XCF) and elevation fields from FITACF records, returning the
+/// stripped records.
+#[pyfunction]
+#[pyo3(name = "strip_xcf")]
+#[pyo3(text_signature = "(recs: list[dict], /)")]
+fn strip_xcf_py(mut recs: Vec<IndexMap<String, DmapField>>) -> Vec<IndexMap<String, DmapField>> {
+    recs.iter_mut().for_each(strip_xcf);
+    recs
+}
+
+/// Converts a FITACF file into a CSV file, with a row for each range gate of each record.
+///
+/// See `FitacfRecord::csv_header` and `FitacfRecord::csv_rows` for the columns. `outfile` is
+/// overwritten if it exists.
+pub fn fitacf_to_csv(infile: &PathBuf, outfile: &PathBuf) -> Result<(), DmapError> {
+    let recs = FitacfRecord::read_file(infile)?;
+    let mut writer = std::io::BufWriter::new(File::create(outfile)?);
+    writeln!(writer, "{}", FitacfRecord::csv_header())?;
+    for rec in recs.iter() {
+        for row in rec.csv_rows() {
+            writeln!(writer, "{row}")?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
 /// Read in a GRID file
 pub fn read_grid(infile: PathBuf) -> Result<Vec<GridRecord>, DmapError> {
     GridRecord::read_file(&infile)
@@ -225,138 +703,1278 @@ pub fn read_snd(infile: PathBuf) -> Result<Vec<SndRecord>, DmapError> {
     SndRecord::read_file(&infile)
 }
 
-/// Reads the data from infile into a collection of `IndexMap`s
+/// The first bytes of a bzip2-compressed stream.
+const BZIP2_MAGIC: &[u8] = b"BZh";
+
+/// The first bytes of a gzip-compressed stream.
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+/// The first bytes of a zstd-compressed frame.
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Reads the first bytes of `file`, enough to detect its compression, then rewinds it.
+fn read_magic(file: &mut File) -> Result<Vec<u8>, DmapError> {
+    let mut magic: Vec<u8> = vec![];
+    Read::by_ref(file)
+        .take(ZSTD_MAGIC.len() as u64)
+        .read_to_end(&mut magic)?;
+    file.seek(SeekFrom::Start(0))?;
+    Ok(magic)
+}
+
+/// Whether `file` is compressed with bzip2, gzip or zstd, detected from its contents.
+fn is_compressed(file: &mut File) -> Result<bool, DmapError> {
+    let magic = read_magic(file)?;
+    Ok([BZIP2_MAGIC, GZIP_MAGIC, ZSTD_MAGIC]
+        .iter()
+        .any(|x| magic.starts_with(x)))
+}
+
+/// Opens `infile` for reading, detecting bzip2, gzip or zstd compression from the file contents
+/// rather than the extension.
+fn open_any(infile: &PathBuf) -> Result<Box<dyn Read>, DmapError> {
+    let mut file = File::open(infile)?;
+    let magic = read_magic(&mut file)?;
+    if magic.starts_with(BZIP2_MAGIC) {
+        Ok(Box::new(MultiBzDecoder::new(file)))
+    } else if magic.starts_with(GZIP_MAGIC) {
+        Ok(Box::new(MultiGzDecoder::new(file)))
+    } else if magic.starts_with(ZSTD_MAGIC) {
+        Ok(Box::new(zstd::stream::read::Decoder::new(file)?))
+    } else {
+        Ok(Box::new(file))
+    }
+}
+
+/// Read in a DMAP file of any format, detecting bzip2, gzip or zstd compression from the file
+/// contents rather than the extension.
+///
+/// This is the most convenient but least strict reader: the records are returned as
+/// `GenericRecord`s, so the fields are not checked against any particular file format.
+pub fn read_any(infile: PathBuf) -> Result<Vec<GenericRecord>, DmapError> {
+    GenericRecord::read_records(open_any(&infile)?)
+}
+
+/// Reads only record `index` of `infile`, a DMAP file of any format, as a `GenericRecord`.
+///
+/// Uncompressed files are indexed with `DmapIndex`, so only the headers of the preceding records
+/// are read. Compressed files are decompressed up to the end of the record. Returns `DmapError`
+/// if `index` is out of bounds or the record is invalid.
+pub fn read_record_any(infile: &PathBuf, index: usize) -> Result<GenericRecord, DmapError> {
+    let mut file = File::open(infile)?;
+    if is_compressed(&mut file)? {
+        RecordIter::new(open_any(infile)?)
+            .nth(index)
+            .unwrap_or_else(|| {
+                Err(DmapError::InvalidRecord(format!(
+                    "Record {index} out of bounds for {}",
+                    infile.display()
+                )))
+            })
+    } else {
+        DmapIndex::build(&mut file)?.read_record(&mut file, index)
+    }
+}
+
+/// Gets the UT times of the first and last records of `infile`, a DMAP file of any format, for
+/// finding the time span of a file without reading all of it.
+///
+/// The start is the time of the first record as given by `filter::record_time`, and the end is
+/// the end time of the last record as given by `filter::record_end_time`, i.e. from the `start.*`
+/// and `end.*` fields for grid and map files. Records are assumed to be in time order.
+///
+/// Only the first and last records of uncompressed files are parsed, using `DmapIndex`.
+/// Compressed files must be decompressed in full. Returns `DmapError` if the file has no records
+/// or either record lacks time fields.
+pub fn time_bounds(
+    infile: &PathBuf,
+) -> Result<(time::OffsetDateTime, time::OffsetDateTime), DmapError> {
+    let mut file = File::open(infile)?;
+    let (first, last) = if is_compressed(&mut file)? {
+        let mut recs = RecordIter::<GenericRecord>::new(open_any(infile)?);
+        let first = recs.next().transpose()?;
+        let mut last = first.clone();
+        for rec in recs {
+            last = Some(rec?);
+        }
+        (first, last)
+    } else {
+        let index = DmapIndex::build(&mut file)?;
+        match index.len() {
+            0 => (None, None),
+            n => (
+                Some(index.read_record::<GenericRecord>(&mut file, 0)?),
+                Some(index.read_record(&mut file, n - 1)?),
+            ),
+        }
+    };
+    let (first, last) = first
+        .zip(last)
+        .ok_or_else(|| DmapError::InvalidRecord(format!("{} has no records", infile.display())))?;
+    Ok((
+        filter::record_time(first.data())?,
+        filter::record_end_time(last.data())?,
+    ))
+}
+
+/// Read in a DMAP file of any format as with `read_any`, also returning the format of the file.
+///
+/// The format is detected from the first record as with `sniff_format`, and is
+/// `FileFormat::Dmap` if the file has no records.
+pub fn read_all(infile: PathBuf) -> Result<(FileFormat, Vec<GenericRecord>), DmapError> {
+    let recs = read_any(infile)?;
+    let format = recs.first().map_or(FileFormat::Dmap, FileFormat::classify);
+    Ok((format, recs))
+}
+
+/// Parses one record starting from the position of `cursor`, for reading records embedded in
+/// other data such as a custom container format.
+///
+/// On success, `cursor` is left positioned at the byte after the end of the record, i.e. at the
+/// start of the next record if records are concatenated. On failure the position of `cursor` is
+/// unspecified. Returns `DmapError` if the record is invalid.
+pub fn parse_one(cursor: &mut Cursor<&[u8]>) -> Result<GenericRecord, DmapError> {
+    GenericRecord::parse_record(cursor)
+}
+
+/// Detects the format of `infile` from the fields of its first record.
+///
+/// Only the first record is read and parsed. Compression is detected from the file contents.
+/// Returns `DmapError` if the file is empty or the first record is invalid.
+pub fn sniff_format(infile: &PathBuf) -> Result<FileFormat, DmapError> {
+    Ok(FileFormat::classify(&first_record(infile)?.1))
+}
+
+/// Reads and parses only the first record of `infile`, also returning its byte order.
+fn first_record(infile: &PathBuf) -> Result<(Endianness, GenericRecord), DmapError> {
+    let mut stream = open_any(infile)?;
+    let mut header = vec![0; 2 * i32::size()];
+    stream
+        .read_exact(&mut header)
+        .map_err(|_| DmapError::InvalidRecord(format!("No records in {}", infile.display())))?;
+    let (endianness, size) = read_record_header(&header)?;
+    if size <= header.len() as i32 {
+        Err(DmapError::InvalidRecord(format!(
+            "Record size {size} too small"
+        )))?
+    }
+    header.resize(size as usize, 0);
+    stream.read_exact(&mut header[2 * i32::size()..])?;
+    let rec = GenericRecord::parse_record(&mut Cursor::new(header.as_slice()))?;
+    Ok((endianness, rec))
+}
+
+/// Counts the records in `infile` by reading only the code and size header of each record.
+///
+/// No scalars or vectors are parsed, so the records are not checked for validity. Compression is
+/// detected from the file contents. Returns `DmapError` if a record header is truncated or
+/// declares a size which is too small, or `DmapError::TruncatedFile` if the final record extends
+/// past the end of the file.
+pub fn count_records(infile: &PathBuf) -> Result<usize, DmapError> {
+    let mut stream = open_any(infile)?;
+    let header_size = 2 * i32::size();
+    let mut header = vec![0; header_size];
+    let mut count = 0;
+    let mut offset: u64 = 0;
+    loop {
+        let mut bytes_read = 0;
+        while bytes_read < header_size {
+            match stream.read(&mut header[bytes_read..])? {
+                0 if bytes_read == 0 => return Ok(count),
+                0 => Err(DmapError::CorruptStream("Record header is truncated"))?,
+                n => bytes_read += n,
+            }
+        }
+        let (_, size) = read_record_header(&header)?;
+        if size <= header_size as i32 {
+            Err(DmapError::InvalidRecord(format!(
+                "Record {count} size {size} too small"
+            )))?
+        }
+        let body_size = (size as usize - header_size) as u64;
+        let mut body = Read::by_ref(&mut stream).take(body_size);
+        let body_read = std::io::copy(&mut body, &mut std::io::sink())?;
+        if body_read < body_size {
+            Err(DmapError::TruncatedFile {
+                expected: size as usize,
+                available: header_size + body_read as usize,
+                byte: offset,
+            })?
+        }
+        offset += size as u64;
+        count += 1;
+    }
+}
+
+/// Gets the number of bytes in `infile` after decompression, detecting bzip2, gzip or zstd
+/// compression from the file contents.
+///
+/// Compressed files are decompressed without keeping the decompressed bytes, so this takes about
+/// as long as reading the file but little memory. For uncompressed files the file length is
+/// returned without reading the file.
+pub fn uncompressed_size(infile: &PathBuf) -> Result<u64, DmapError> {
+    let mut file = File::open(infile)?;
+    if is_compressed(&mut file)? {
+        Ok(std::io::copy(&mut open_any(infile)?, &mut std::io::sink())?)
+    } else {
+        Ok(file.metadata()?.len())
+    }
+}
+
+/// The metadata of a DMAP file which can be found without keeping its records, from
+/// `file_info`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileInfo {
+    /// The format of the file, detected from the first record as with `sniff_format`
+    pub format: FileFormat,
+    /// The number of records
+    pub num_records: usize,
+    /// Whether the file is compressed with bzip2, gzip or zstd
+    pub compressed: bool,
+    /// The size of the file in bytes after decompression
+    pub uncompressed_bytes: u64,
+    /// The byte order of the first record
+    pub endianness: Endianness,
+    /// The times of the first and last records, as given by `time_bounds`, or `None` if they
+    /// have no time fields
+    pub time_bounds: Option<(time::OffsetDateTime, time::OffsetDateTime)>,
+    /// The `(major, minor)` revision of the format in the first record, e.g. from
+    /// `rawacf.revision.major` and `rawacf.revision.minor`, or `None` if the format has no
+    /// revision fields
+    pub revision: Option<(i32, i32)>,
+}
+
+/// Gets the `(major, minor)` revision of `format` from the fields of `rec`, e.g.
+/// `fitacf.revision.major` and `fitacf.revision.minor` for FITACF.
+fn format_revision(rec: &GenericRecord, format: FileFormat) -> Option<(i32, i32)> {
+    let prefix = match format {
+        FileFormat::Iqdat => "iqdata",
+        FileFormat::Rawacf => "rawacf",
+        FileFormat::Fitacf => "fitacf",
+        FileFormat::Snd => "snd",
+        FileFormat::Dmap | FileFormat::Grid | FileFormat::Map => return None,
+    };
+    let part = |name: &str| match rec.data.get(&format!("{prefix}.revision.{name}"))? {
+        DmapField::Scalar(x) => i32::try_from(x.clone()).ok(),
+        DmapField::Vector(_) => None,
+    };
+    Some((part("major")?, part("minor")?))
+}
+
+/// Gets the metadata of `infile`, a DMAP file of any format, in one call, for inspecting files
+/// without reading them into memory.
+///
+/// This combines `sniff_format`, `count_records`, `uncompressed_size` and `time_bounds`, so
+/// compressed files are decompressed several times. Returns `DmapError` if the file has no
+/// records or a record header is invalid.
+pub fn file_info(infile: &PathBuf) -> Result<FileInfo, DmapError> {
+    let (endianness, first) = first_record(infile)?;
+    let format = FileFormat::classify(&first);
+    Ok(FileInfo {
+        format,
+        num_records: count_records(infile)?,
+        compressed: is_compressed(&mut File::open(infile)?)?,
+        uncompressed_bytes: uncompressed_size(infile)?,
+        endianness,
+        time_bounds: time_bounds(infile).ok(),
+        revision: format_revision(&first, format),
+    })
+}
+
+/// Counts the records of `infile` which contain each optional field of the format `T`.
+///
+/// Every optional scalar and vector field of `T` is in the result, with a count of zero if no
+/// record contains it. The result is empty for `GenericRecord`, which has no optional fields.
+/// Returns `DmapError` if the file cannot be read or a record is invalid.
+pub fn present_optional_fields<T: for<'a> Record<'a>>(
+    infile: &PathBuf,
+) -> Result<HashMap<String, usize>, DmapError> {
+    let mut counts: HashMap<String, usize> = match T::fields() {
+        Some(fields) => fields
+            .scalars_optional
+            .iter()
+            .chain(fields.vectors_optional.iter())
+            .map(|(name, _)| (name.to_string(), 0))
+            .collect(),
+        None => HashMap::new(),
+    };
+    for rec in T::iter_records(infile)? {
+        let rec = rec?;
+        for (name, count) in counts.iter_mut() {
+            if rec.data().contains_key(name) {
+                *count += 1;
+            }
+        }
+    }
+    Ok(counts)
+}
+
+/// A field whose kind or `Type` in a file differs from the schema of its format, from
+/// `schema_report`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeMismatch {
+    /// The name of the field
+    pub name: String,
+    /// The kind and `Type` of the field in the schema, e.g. `"vector FLOAT"`
+    pub expected: String,
+    /// The kind and `Type` of the field in the file, e.g. `"vector DOUBLE"`
+    pub found: String,
+    /// The number of records in which the field has the `found` kind and `Type`
+    pub num_records: usize,
+}
+
+/// How the fields of a file compare to the schema of a format, from `schema_report`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SchemaReport {
+    /// The number of records in the file
+    pub num_records: usize,
+    /// Each required field of the format, mapped to the number of records which contain it
+    pub required: IndexMap<String, usize>,
+    /// Each optional field of the format, mapped to the number of records which contain it
+    pub optional: IndexMap<String, usize>,
+    /// Each field which is not part of the format, mapped to the number of records which contain
+    /// it
+    pub unknown: IndexMap<String, usize>,
+    /// The fields whose kind or `Type` differs from the format, once for each kind and `Type`
+    /// found
+    pub mismatches: Vec<TypeMismatch>,
+}
+
+/// Surveys the fields of `infile` against the schema of the format `T`, without failing on
+/// records which do not match it.
+///
+/// This is the non-fatal counterpart to reading the file as type `T`: missing required fields,
+/// unknown fields and fields of the wrong kind or `Type` are counted rather than returned as
+/// errors. The records are read as `GenericRecord`s, so every field is unknown for
+/// `GenericRecord`. Returns `DmapError` if the file cannot be read as DMAP.
+pub fn schema_report<T: for<'a> Record<'a>>(infile: &PathBuf) -> Result<SchemaReport, DmapError> {
+    let mut report = SchemaReport::default();
+    let mut expected: HashMap<&str, String> = HashMap::new();
+    if let Some(fields) = T::fields() {
+        let kinds = [
+            (&fields.scalars_required, "scalar", true),
+            (&fields.scalars_optional, "scalar", false),
+            (&fields.vectors_required, "vector", true),
+            (&fields.vectors_optional, "vector", false),
+        ];
+        for (fields, kind, required) in kinds {
+            for (name, field_type) in fields {
+                let counts = if required {
+                    &mut report.required
+                } else {
+                    &mut report.optional
+                };
+                counts.insert(name.to_string(), 0);
+                expected.insert(name, format!("{kind} {field_type}"));
+            }
+        }
+    }
+    let mut mismatches: IndexMap<(String, String), usize> = IndexMap::new();
+    for rec in GenericRecord::iter_records(infile)? {
+        report.num_records += 1;
+        for (name, field) in rec?.data {
+            let count = match report.required.get_mut(&name) {
+                Some(x) => x,
+                None => match report.optional.get_mut(&name) {
+                    Some(x) => x,
+                    None => report.unknown.entry(name.clone()).or_default(),
+                },
+            };
+            *count += 1;
+            let found = diff::describe(&field);
+            if expected.get(name.as_str()).map_or(false, |x| *x != found) {
+                *mismatches.entry((name, found)).or_default() += 1;
+            }
+        }
+    }
+    report.mismatches = mismatches
+        .into_iter()
+        .map(|((name, found), num_records)| TypeMismatch {
+            expected: expected[name.as_str()].clone(),
+            name,
+            found,
+            num_records,
+        })
+        .collect();
+    Ok(report)
+}
+
+/// Hashes `bytes` with 64-bit FNV-1a, which unlike `DefaultHasher` is the same on every platform
+/// and Rust version.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Computes a hash of each record of `infile`, for tracking which records change between
+/// processing runs.
+///
+/// Each hash is the 64-bit FNV-1a hash of the record as serialized by `Record::to_bytes`, so it
+/// depends only on the fields of the record and is stable across platforms and versions of this
+/// crate. Returns `DmapError` if the file cannot be read or a record is invalid.
+pub fn record_hashes<T: for<'a> Record<'a>>(infile: &PathBuf) -> Result<Vec<u64>, DmapError> {
+    let mut buffer = vec![];
+    T::iter_records(infile)?
+        .map(|rec| {
+            buffer.clear();
+            rec?.to_bytes_into(&mut buffer)?;
+            Ok(fnv1a(&buffer))
+        })
+        .collect()
+}
+
+/// A file to read from Python: a path as a `str` or `os.PathLike`, or a binary file object such
+/// as the result of `open(path, "rb")` or `bz2.open(path)`.
+#[derive(FromPyObject)]
+enum PyInfile<'py> {
+    Path(PathBuf),
+    File(Bound<'py, PyAny>),
+}
+
+/// Reads the data from infile into a collection of `IndexMap`s, keeping at most `max_records`
+/// records if given
+///
+/// File objects are read to the end with their `read` method and the bytes are parsed as they
+/// are, so compressed data must be decompressed by the file object.
+///
+/// The GIL is released while the records are parsed, after the contents of a file object have
+/// been read.
 fn read_generic<T: for<'a> Record<'a> + Send>(
-    infile: PathBuf,
-) -> Result<Vec<IndexMap<String, DmapField>>, DmapError> {
-    match T::read_file(&infile) {
-        Ok(recs) => {
-            let new_recs = recs.into_iter().map(|rec| rec.inner()).collect();
-            Ok(new_recs)
+    py: Python<'_>,
+    infile: PyInfile<'_>,
+    max_records: Option<usize>,
+) -> PyResult<Vec<IndexMap<String, DmapField>>> {
+    let recs = match infile {
+        PyInfile::Path(path) => py.allow_threads(|| match max_records {
+            Some(n) => T::read_file_limited(&path, n),
+            None => T::read_file(&path),
+        }),
+        PyInfile::File(file) => {
+            let data = file.call_method0("read")?;
+            let bytes = data.downcast::<PyBytes>()?.as_bytes().to_vec();
+            py.allow_threads(|| match max_records {
+                Some(n) => RecordIter::new(Cursor::new(bytes)).take(n).collect(),
+                None => T::read_records_from_bytes(bytes),
+            })
         }
-        Err(e) => Err(e),
+    }?;
+    Ok(recs.into_iter().map(|rec| rec.inner()).collect())
+}
+
+/// Parses the records in `bytes` into a collection of `IndexMap`s
+fn read_generic_bytes<T: for<'a> Record<'a> + Send>(
+    bytes: Vec<u8>,
+) -> Result<Vec<IndexMap<String, DmapField>>, DmapError> {
+    let recs = T::read_records_from_bytes(bytes)?;
+    Ok(recs.into_iter().map(|rec| rec.inner()).collect())
+}
+
+/// Converts records into a list of Python dictionaries. If `typed`, scalars are converted into
+/// 0-dimensional numpy arrays of the exact dtype rather than Python `int`s and `float`s, so that
+/// writing the records back keeps their `Type`.
+fn records_into_py(
+    py: Python<'_>,
+    recs: Vec<IndexMap<String, DmapField>>,
+    typed: bool,
+) -> PyObject {
+    if typed {
+        recs.into_iter()
+            .map(|rec| {
+                rec.into_iter()
+                    .map(|(name, field)| (name, field.into_typed_py(py)))
+                    .collect::<IndexMap<String, PyObject>>()
+            })
+            .collect::<Vec<_>>()
+            .into_py(py)
+    } else {
+        recs.into_py(py)
     }
 }
 
-/// Reads a generic DMAP file, returning a list of dictionaries containing the fields.
+/// Generates the Python functions which read records of type `$record` from a file or from
+/// `bytes`, returning a list of dictionaries containing the fields.
+macro_rules! read_py {
+    (
+        $record:ty,
+        $desc:tt,
+        $fn_name:ident: $name:tt,
+        $bytes_fn_name:ident: $bytes_name:tt,
+        $indexed_fn_name:ident: $indexed_name:tt,
+        $lax_fn_name:ident: $lax_name:tt
+    ) => {
+        #[doc = concat!("Reads ", $desc, " file, returning a list of dictionaries containing the fields.")]
+        #[doc = ""]
+        #[doc = "`infile` is a path as a `str` or `os.PathLike`, or a binary file object such as `bz2.open(path)` which is read to the end."]
+        #[doc = "If `max_records` is given, only that many records are read from the start of the file."]
+        #[doc = "If `typed` is `True`, scalars are returned as 0-dimensional numpy arrays of their exact dtype."]
+        #[pyfunction]
+        #[pyo3(name = $name)]
+        #[pyo3(signature = (infile, /, *, max_records = None, typed = false))]
+        #[pyo3(text_signature = "(infile: str | os.PathLike | BinaryIO, /, *, max_records: int | None = None, typed: bool = False)")]
+        fn $fn_name(py: Python<'_>, infile: PyInfile<'_>, max_records: Option<usize>, typed: bool) -> PyResult<PyObject> {
+            let recs = read_generic::<$record>(py, infile, max_records)?;
+            Ok(records_into_py(py, recs, typed))
+        }
+
+        #[doc = concat!("Reads the contents of ", $desc, " file from `bytes`, returning a list of dictionaries containing the fields.")]
+        #[doc = ""]
+        #[doc = "If `typed` is `True`, scalars are returned as 0-dimensional numpy arrays of their exact dtype."]
+        #[pyfunction]
+        #[pyo3(name = $bytes_name)]
+        #[pyo3(signature = (data, /, *, typed = false))]
+        #[pyo3(text_signature = "(data: bytes, /, *, typed: bool = False)")]
+        fn $bytes_fn_name(py: Python<'_>, data: &[u8], typed: bool) -> PyResult<PyObject> {
+            let data = data.to_vec();
+            let recs = py.allow_threads(|| read_generic_bytes::<$record>(data)).map_err(PyErr::from)?;
+            Ok(records_into_py(py, recs, typed))
+        }
+
+        #[doc = concat!("Reads ", $desc, " file, returning a list of `(offset, dict)` tuples where `offset` is the byte at which the record starts.")]
+        #[pyfunction]
+        #[pyo3(name = $indexed_name)]
+        #[pyo3(text_signature = "(infile: str, /)")]
+        fn $indexed_fn_name(py: Python<'_>, infile: PathBuf) -> PyResult<Vec<(usize, IndexMap<String, DmapField>)>> {
+            let recs = py.allow_threads(|| <$record>::read_file_indexed(&infile)).map_err(PyErr::from)?;
+            Ok(recs.into_iter().map(|(i, rec)| (i, rec.inner())).collect())
+        }
+
+        #[doc = concat!("Reads ", $desc, " file up to the first corrupted record, returning a list of dictionaries containing the fields and the byte at which the corrupted record starts, or `None` if there is none.")]
+        #[pyfunction]
+        #[pyo3(name = $lax_name)]
+        #[pyo3(text_signature = "(infile: str, /)")]
+        fn $lax_fn_name(py: Python<'_>, infile: PathBuf) -> PyResult<(Vec<IndexMap<String, DmapField>>, Option<usize>)> {
+            let (recs, bad_byte) = py.allow_threads(|| <$record>::read_file_lax(&infile)).map_err(PyErr::from)?;
+            Ok((recs.into_iter().map(|rec| rec.inner()).collect(), bad_byte))
+        }
+    };
+}
+
+read_py!(
+    GenericRecord,
+    "a generic DMAP",
+    read_dmap_py: "read_dmap",
+    read_dmap_bytes_py: "read_dmap_bytes",
+    read_dmap_indexed_py: "read_dmap_indexed",
+    read_dmap_lax_py: "read_dmap_lax"
+);
+read_py!(
+    IqdatRecord,
+    "an IQDAT",
+    read_iqdat_py: "read_iqdat",
+    read_iqdat_bytes_py: "read_iqdat_bytes",
+    read_iqdat_indexed_py: "read_iqdat_indexed",
+    read_iqdat_lax_py: "read_iqdat_lax"
+);
+read_py!(
+    RawacfRecord,
+    "a RAWACF",
+    read_rawacf_py: "read_rawacf",
+    read_rawacf_bytes_py: "read_rawacf_bytes",
+    read_rawacf_indexed_py: "read_rawacf_indexed",
+    read_rawacf_lax_py: "read_rawacf_lax"
+);
+read_py!(
+    FitacfRecord,
+    "a FITACF",
+    read_fitacf_py: "read_fitacf",
+    read_fitacf_bytes_py: "read_fitacf_bytes",
+    read_fitacf_indexed_py: "read_fitacf_indexed",
+    read_fitacf_lax_py: "read_fitacf_lax"
+);
+read_py!(
+    GridRecord,
+    "a GRID",
+    read_grid_py: "read_grid",
+    read_grid_bytes_py: "read_grid_bytes",
+    read_grid_indexed_py: "read_grid_indexed",
+    read_grid_lax_py: "read_grid_lax"
+);
+read_py!(
+    MapRecord,
+    "a MAP",
+    read_map_py: "read_map",
+    read_map_bytes_py: "read_map_bytes",
+    read_map_indexed_py: "read_map_indexed",
+    read_map_lax_py: "read_map_lax"
+);
+read_py!(
+    SndRecord,
+    "an SND",
+    read_snd_py: "read_snd",
+    read_snd_bytes_py: "read_snd_bytes",
+    read_snd_indexed_py: "read_snd_indexed",
+    read_snd_lax_py: "read_snd_lax"
+);
+
+/// Reads a DMAP file of any format, returning a list of dictionaries containing the fields.
+///
+/// Compression is detected from the file contents, and no fields are checked.
 #[pyfunction]
-#[pyo3(name = "read_dmap")]
+#[pyo3(name = "read_any")]
 #[pyo3(text_signature = "(infile: str, /)")]
-fn read_dmap_py(infile: PathBuf) -> PyResult<Vec<IndexMap<String, DmapField>>> {
-    read_generic::<GenericRecord>(infile).map_err(PyErr::from)
+fn read_any_py(py: Python<'_>, infile: PathBuf) -> PyResult<Vec<IndexMap<String, DmapField>>> {
+    py.allow_threads(|| read_any(infile))
+        .map(|recs| recs.into_iter().map(|rec| rec.inner()).collect())
+        .map_err(PyErr::from)
 }
 
-/// Reads an IQDAT file, returning a list of dictionaries containing the fields.
+/// Reads a DMAP file of any format, returning the lowercase name of the format, e.g. `"rawacf"`,
+/// and a list of dictionaries containing the fields.
+///
+/// The format is detected from the first record, and is `"dmap"` if the record does not match
+/// any specific format. Compression is detected from the file contents, and no fields are checked.
 #[pyfunction]
-#[pyo3(name = "read_iqdat")]
+#[pyo3(name = "read_all")]
 #[pyo3(text_signature = "(infile: str, /)")]
-fn read_iqdat_py(infile: PathBuf) -> PyResult<Vec<IndexMap<String, DmapField>>> {
-    read_generic::<IqdatRecord>(infile).map_err(PyErr::from)
+fn read_all_py(
+    py: Python<'_>,
+    infile: PathBuf,
+) -> PyResult<(String, Vec<IndexMap<String, DmapField>>)> {
+    let (format, recs) = py.allow_threads(|| read_all(infile))?;
+    Ok((
+        format.to_string(),
+        recs.into_iter().map(|rec| rec.inner()).collect(),
+    ))
+}
+
+/// Reads many RAWACF files in parallel.
+///
+/// Returns a list with an entry for each file: either a list of dictionaries containing the fields,
+/// or the exception raised when reading the file.
+#[pyfunction]
+#[pyo3(name = "read_rawacf_files")]
+#[pyo3(text_signature = "(infiles: list[str], /)")]
+fn read_rawacf_files_py(py: Python, infiles: Vec<PathBuf>) -> Vec<PyObject> {
+    py.allow_threads(|| read_files::<RawacfRecord>(infiles))
+        .into_iter()
+        .map(|result| match result {
+            Ok(recs) => recs
+                .into_iter()
+                .map(|rec| rec.inner())
+                .collect::<Vec<_>>()
+                .into_py(py),
+            Err(e) => PyErr::from(e).into_value(py).into_py(py),
+        })
+        .collect()
 }
 
-/// Reads a RAWACF file, returning a list of dictionaries containing the fields.
+/// Reads a FITACF file, returning a list of dictionaries containing the fields of the records
+/// with a `bmnum` in `beams`.
 #[pyfunction]
-#[pyo3(name = "read_rawacf")]
+#[pyo3(name = "read_fitacf_beams")]
+#[pyo3(text_signature = "(infile: str, beams: list[int], /)")]
+fn read_fitacf_beams_py(
+    py: Python<'_>,
+    infile: PathBuf,
+    beams: Vec<i16>,
+) -> PyResult<Vec<IndexMap<String, DmapField>>> {
+    let recs = py
+        .allow_threads(|| read_fitacf_beams(infile, &beams))
+        .map_err(PyErr::from)?;
+    Ok(recs.into_iter().map(|rec| rec.inner()).collect())
+}
+
+/// Converts a FITACF file into a CSV file, with a row for each range gate of each record.
+///
+/// Each row has the time, station, channel and beam of the record, followed by the value of each
+/// range-indexed vector field at that range gate. Absent vector fields are empty cells.
+#[pyfunction]
+#[pyo3(name = "fitacf_to_csv")]
+#[pyo3(text_signature = "(infile: str, outfile: str, /)")]
+fn fitacf_to_csv_py(py: Python<'_>, infile: PathBuf, outfile: PathBuf) -> PyResult<()> {
+    py.allow_threads(|| fitacf_to_csv(&infile, &outfile))
+        .map_err(PyErr::from)
+}
+
+/// Detects the format of a DMAP file from the fields of its first record.
+///
+/// Returns the lowercase name of the format, e.g. `"rawacf"`, or `"dmap"` if the record does not
+/// match any specific format.
+#[pyfunction]
+#[pyo3(name = "sniff_format")]
 #[pyo3(text_signature = "(infile: str, /)")]
-fn read_rawacf_py(infile: PathBuf) -> PyResult<Vec<IndexMap<String, DmapField>>> {
-    read_generic::<RawacfRecord>(infile).map_err(PyErr::from)
+fn sniff_format_py(py: Python<'_>, infile: PathBuf) -> PyResult<String> {
+    py.allow_threads(|| sniff_format(&infile))
+        .map(|fmt| fmt.to_string())
+        .map_err(PyErr::from)
 }
 
-/// Reads a FITACF file, returning a list of dictionaries containing the fields.
+/// Gets the number of bytes in a DMAP file after decompression.
+///
+/// Compression is detected from the file contents. For uncompressed files this is the file size.
 #[pyfunction]
-#[pyo3(name = "read_fitacf")]
+#[pyo3(name = "uncompressed_size")]
 #[pyo3(text_signature = "(infile: str, /)")]
-fn read_fitacf_py(infile: PathBuf) -> PyResult<Vec<IndexMap<String, DmapField>>> {
-    read_generic::<FitacfRecord>(infile).map_err(PyErr::from)
+fn uncompressed_size_py(py: Python<'_>, infile: PathBuf) -> PyResult<u64> {
+    py.allow_threads(|| uncompressed_size(&infile))
+        .map_err(PyErr::from)
 }
 
-/// Reads a GRID file, returning a list of dictionaries containing the fields.
+/// Reads the key scalars of each record of a DMAP file of any format, returning a list of
+/// dictionaries with keys `index`, `byte_offset`, `stid`, `bmnum`, `channel`, `cp` and `time`,
+/// an ISO-8601 string in UT. Fields which a record does not have are `None`.
 #[pyfunction]
-#[pyo3(name = "read_grid")]
+#[pyo3(name = "manifest")]
 #[pyo3(text_signature = "(infile: str, /)")]
-fn read_grid_py(infile: PathBuf) -> PyResult<Vec<IndexMap<String, DmapField>>> {
-    read_generic::<GridRecord>(infile).map_err(PyErr::from)
+fn manifest_py(py: Python, infile: PathBuf) -> PyResult<Vec<Bound<PyDict>>> {
+    py.allow_threads(|| manifest::<GenericRecord>(&infile))?
+        .into_iter()
+        .map(|entry| {
+            let dict = PyDict::new_bound(py);
+            dict.set_item("index", entry.index)?;
+            dict.set_item("byte_offset", entry.byte_offset)?;
+            dict.set_item("stid", entry.stid)?;
+            dict.set_item("bmnum", entry.bmnum)?;
+            dict.set_item("channel", entry.channel)?;
+            dict.set_item("cp", entry.cp)?;
+            dict.set_item("time", entry.time.map(iso8601).transpose()?)?;
+            Ok(dict)
+        })
+        .collect()
 }
 
-/// Reads a MAP file, returning a list of dictionaries containing the fields.
+/// Gets the `acfd` field of a RAWACF record as a complex64 numpy array, combining the real and
+/// imaginary parts in the last axis.
 #[pyfunction]
-#[pyo3(name = "read_map")]
+#[pyo3(name = "acfd_complex")]
+#[pyo3(text_signature = "(record: dict, /)")]
+fn acfd_complex_py(
+    py: Python<'_>,
+    record: IndexMap<String, DmapField>,
+) -> PyResult<Bound<'_, PyArrayDyn<Complex32>>> {
+    let acfd = get_field_as::<&ArrayD<f32>>(&record, "acfd")?;
+    Ok(to_complex(acfd)?.into_pyarray_bound(py))
+}
+
+/// Gets the `xcfd` field of a RAWACF record as a complex64 numpy array, combining the real and
+/// imaginary parts in the last axis.
+#[pyfunction]
+#[pyo3(name = "xcfd_complex")]
+#[pyo3(text_signature = "(record: dict, /)")]
+fn xcfd_complex_py(
+    py: Python<'_>,
+    record: IndexMap<String, DmapField>,
+) -> PyResult<Bound<'_, PyArrayDyn<Complex32>>> {
+    let xcfd = get_field_as::<&ArrayD<f32>>(&record, "xcfd")?;
+    Ok(to_complex(xcfd)?.into_pyarray_bound(py))
+}
+
+/// Lists the required fields of the RAWACF format which are missing from `record`, which is
+/// empty if none are missing.
+#[pyfunction]
+#[pyo3(name = "check_rawacf")]
+#[pyo3(text_signature = "(record: dict, /)")]
+fn check_rawacf_py(record: IndexMap<String, DmapField>) -> Vec<String> {
+    RawacfRecord::missing_required_fields(&record, rawacf_fields())
+}
+
+/// Describes each field of `record` on its own line, giving its name, type and, for vector
+/// fields, its shape, without printing vector data.
+#[pyfunction]
+#[pyo3(name = "summary")]
+#[pyo3(text_signature = "(record: dict, /)")]
+fn summary_py(record: IndexMap<String, DmapField>) -> String {
+    summarize(&record)
+}
+
+/// Maps the name of each field of `record` to the name of its DMAP type, e.g. `"SHORT"`.
+///
+/// Python values are converted to DMAP types in the same way as when writing, so this shows the
+/// type each field would be written with, e.g. to check that an `int` field is not written wider
+/// or narrower than its format expects.
+#[pyfunction]
+#[pyo3(name = "field_types")]
+#[pyo3(text_signature = "(record: dict, /)")]
+fn field_types_py(record: IndexMap<String, DmapField>) -> HashMap<String, String> {
+    field_types(&record)
+}
+
+/// Formats `time` as an ISO-8601 (RFC 3339) string, e.g. `2021-06-07T18:01:00Z`.
+fn iso8601(time: time::OffsetDateTime) -> PyResult<String> {
+    time.format(&time::format_description::well_known::Rfc3339)
+        .map_err(|e| DmapError::InvalidScalar(format!("Cannot format time: {e}")).into())
+}
+
+/// Parses the `origin.time` field of `record`, returning an ISO-8601 string in UT.
+#[pyfunction]
+#[pyo3(name = "origin_time")]
+#[pyo3(text_signature = "(record: dict, /)")]
+fn origin_time_py(record: IndexMap<String, DmapField>) -> PyResult<String> {
+    iso8601(filter::parse_origin_time(&record)?)
+}
+
+/// Gets the time of `record` from its `time.yr` to `time.us` fields, or its `start.*` fields for
+/// grid and map records, returning an ISO-8601 string in UT.
+#[pyfunction]
+#[pyo3(name = "record_time")]
+#[pyo3(text_signature = "(record: dict, /)")]
+fn record_time_py(record: IndexMap<String, DmapField>) -> PyResult<String> {
+    iso8601(filter::record_time(&record)?)
+}
+
+/// Gets the time of `record` as an ISO-8601 string in UT. The same as `record_time`.
+#[pyfunction]
+#[pyo3(name = "record_timestamp")]
+#[pyo3(text_signature = "(record: dict, /)")]
+fn record_timestamp_py(record: IndexMap<String, DmapField>) -> PyResult<String> {
+    iso8601(filter::record_timestamp(&record)?)
+}
+
+/// Gets the UT times of the first and last records of a DMAP file of any format, returning a
+/// tuple of ISO-8601 strings. For grid and map files the end is the end time of the last record.
+///
+/// Only the first and last records of uncompressed files are parsed.
+#[pyfunction]
+#[pyo3(name = "time_bounds")]
 #[pyo3(text_signature = "(infile: str, /)")]
-fn read_map_py(infile: PathBuf) -> PyResult<Vec<IndexMap<String, DmapField>>> {
-    read_generic::<MapRecord>(infile).map_err(PyErr::from)
+fn time_bounds_py(py: Python, infile: PathBuf) -> PyResult<(String, String)> {
+    let (start, end) = py.allow_threads(|| time_bounds(&infile))?;
+    Ok((iso8601(start)?, iso8601(end)?))
 }
 
-/// Reads an SND file, returning a list of dictionaries containing the fields.
+/// Gets the metadata of a DMAP file of any format, returning a dictionary with keys `format`,
+/// `num_records`, `compressed`, `uncompressed_bytes`, `endianness` (`"little"` or `"big"`),
+/// `time_bounds`, a tuple of ISO-8601 strings, and `revision`, a `(major, minor)` tuple.
+/// `time_bounds` and `revision` are `None` if the records do not have the fields.
 #[pyfunction]
-#[pyo3(name = "read_snd")]
+#[pyo3(name = "file_info")]
 #[pyo3(text_signature = "(infile: str, /)")]
-fn read_snd_py(infile: PathBuf) -> PyResult<Vec<IndexMap<String, DmapField>>> {
-    read_generic::<SndRecord>(infile).map_err(PyErr::from)
+fn file_info_py(py: Python, infile: PathBuf) -> PyResult<Bound<PyDict>> {
+    let info = py.allow_threads(|| file_info(&infile))?;
+    let dict = PyDict::new_bound(py);
+    dict.set_item("format", info.format.as_str())?;
+    dict.set_item("num_records", info.num_records)?;
+    dict.set_item("compressed", info.compressed)?;
+    dict.set_item("uncompressed_bytes", info.uncompressed_bytes)?;
+    let endianness = match info.endianness {
+        Endianness::Little => "little",
+        Endianness::Big => "big",
+    };
+    dict.set_item("endianness", endianness)?;
+    let time_bounds = match info.time_bounds {
+        Some((start, end)) => Some((iso8601(start)?, iso8601(end)?)),
+        None => None,
+    };
+    dict.set_item("time_bounds", time_bounds)?;
+    dict.set_item("revision", info.revision)?;
+    Ok(dict)
 }
 
-/// Checks that a list of dictionaries contains DMAP records, then appends to outfile.
+/// Gets the scalar field `field` of every record in `records`, returning a numpy array.
+///
+/// The array is of integers if every value is an integer, and floats otherwise.
+#[pyfunction]
+#[pyo3(name = "extract_scalar")]
+#[pyo3(text_signature = "(records: list[dict], field: str, /)")]
+fn extract_scalar_py(
+    py: Python<'_>,
+    records: Vec<IndexMap<String, DmapField>>,
+    field: &str,
+) -> PyResult<PyObject> {
+    let scalars = records
+        .into_iter()
+        .enumerate()
+        .map(|(i, mut rec)| match rec.swap_remove(field) {
+            Some(DmapField::Scalar(DmapScalar::String(_))) => Err(DmapError::InvalidScalar(
+                format!("Record {i}: Field {field} is a string, expected a number"),
+            )),
+            Some(DmapField::Scalar(x)) => Ok(x),
+            Some(DmapField::Vector(_)) => Err(DmapError::InvalidScalar(format!(
+                "Record {i}: Field {field} is a vector, expected a scalar"
+            ))),
+            None => Err(DmapError::InvalidRecord(format!(
+                "Record {i}: Field {field} not in record"
+            ))),
+        })
+        .collect::<Result<Vec<DmapScalar>, DmapError>>()?;
+    let is_float = |x: &DmapScalar| matches!(x, DmapScalar::Float(_) | DmapScalar::Double(_));
+    let column = if scalars.iter().any(is_float) {
+        let values = scalars
+            .into_iter()
+            .map(f64::try_from)
+            .collect::<Result<_, _>>()?;
+        DmapVec::Double(Array1::from_vec(values).into_dyn())
+    } else {
+        let values = scalars
+            .into_iter()
+            .map(i64::try_from)
+            .collect::<Result<_, _>>()?;
+        DmapVec::Long(Array1::from_vec(values).into_dyn())
+    };
+    Ok(column.into_py(py))
+}
+
+/// Concatenates the records of `inputs`, which must all be files of format `fmt`
+/// (e.g. `"rawacf"`), and appends them to `output`.
+#[pyfunction]
+#[pyo3(name = "concat_files")]
+#[pyo3(text_signature = "(inputs: list[str], output: str, fmt: str, /)")]
+fn concat_files_py(
+    py: Python<'_>,
+    inputs: Vec<PathBuf>,
+    output: PathBuf,
+    fmt: &str,
+) -> PyResult<()> {
+    let fmt = fmt.parse::<FileFormat>()?;
+    py.allow_threads(|| match fmt {
+        FileFormat::Dmap => concat_files::<GenericRecord>(&inputs, &output),
+        FileFormat::Iqdat => concat_files::<IqdatRecord>(&inputs, &output),
+        FileFormat::Rawacf => concat_files::<RawacfRecord>(&inputs, &output),
+        FileFormat::Fitacf => concat_files::<FitacfRecord>(&inputs, &output),
+        FileFormat::Grid => concat_files::<GridRecord>(&inputs, &output),
+        FileFormat::Map => concat_files::<MapRecord>(&inputs, &output),
+        FileFormat::Snd => concat_files::<SndRecord>(&inputs, &output),
+    })
+    .map_err(PyErr::from)
+}
+
+/// Checks that a list of dictionaries contains valid records of format `fmt` (e.g. `"rawacf"`),
+/// then writes to outfile. With `"dmap"` no type checking is done, as with `write_dmap`.
+///
+/// Raises a `ValueError` listing the indices of the invalid records if any record is invalid,
+/// in which case nothing is written. Records are appended to outfile unless `append` is `False`,
+/// in which case it is overwritten. If `exist_ok` is `False`, an existing outfile raises an
+/// `IOError`.
+#[pyfunction]
+#[pyo3(name = "write")]
+#[pyo3(signature = (recs, outfile, fmt, /, *, append = true, exist_ok = true))]
+#[pyo3(
+    text_signature = "(recs: list[dict], outfile: str, fmt: str, /, *, append: bool = True, exist_ok: bool = True)"
+)]
+fn write_py(
+    py: Python<'_>,
+    recs: Vec<IndexMap<String, DmapField>>,
+    outfile: PathBuf,
+    fmt: &str,
+    append: bool,
+    exist_ok: bool,
+) -> PyResult<()> {
+    let opts = py_write_options(append, exist_ok);
+    let fmt = fmt.parse::<FileFormat>()?;
+    py.allow_threads(|| match fmt {
+        FileFormat::Dmap => try_write_generic::<GenericRecord>(recs, &outfile, &opts),
+        FileFormat::Iqdat => try_write_generic::<IqdatRecord>(recs, &outfile, &opts),
+        FileFormat::Rawacf => try_write_generic::<RawacfRecord>(recs, &outfile, &opts),
+        FileFormat::Fitacf => try_write_generic::<FitacfRecord>(recs, &outfile, &opts),
+        FileFormat::Grid => try_write_generic::<GridRecord>(recs, &outfile, &opts),
+        FileFormat::Map => try_write_generic::<MapRecord>(recs, &outfile, &opts),
+        FileFormat::Snd => try_write_generic::<SndRecord>(recs, &outfile, &opts),
+    })
+    .map_err(PyErr::from)
+}
+
+/// Counts the records of `infile`, a file of format `fmt` (e.g. `"fitacf"`), which contain each
+/// optional field of the format, returning a dictionary mapping field names to counts.
+#[pyfunction]
+#[pyo3(name = "present_optional_fields")]
+#[pyo3(text_signature = "(infile: str, fmt: str, /)")]
+fn present_optional_fields_py(
+    py: Python<'_>,
+    infile: PathBuf,
+    fmt: &str,
+) -> PyResult<HashMap<String, usize>> {
+    let fmt = fmt.parse::<FileFormat>()?;
+    py.allow_threads(|| match fmt {
+        FileFormat::Dmap => present_optional_fields::<GenericRecord>(&infile),
+        FileFormat::Iqdat => present_optional_fields::<IqdatRecord>(&infile),
+        FileFormat::Rawacf => present_optional_fields::<RawacfRecord>(&infile),
+        FileFormat::Fitacf => present_optional_fields::<FitacfRecord>(&infile),
+        FileFormat::Grid => present_optional_fields::<GridRecord>(&infile),
+        FileFormat::Map => present_optional_fields::<MapRecord>(&infile),
+        FileFormat::Snd => present_optional_fields::<SndRecord>(&infile),
+    })
+    .map_err(PyErr::from)
+}
+
+/// Surveys the fields of `infile` against the schema of the format `fmt` (e.g. `"fitacf"`)
+/// without failing on records which do not match it.
+///
+/// Returns a dictionary with keys `num_records`; `required`, `optional` and `unknown`, each
+/// mapping field names to the number of records containing them; and `mismatches`, a list of
+/// dictionaries with keys `name`, `expected`, `found` and `num_records` for each field whose kind
+/// or type differs from the schema, e.g. with `expected` of `"vector FLOAT"`.
+#[pyfunction]
+#[pyo3(name = "schema_report")]
+#[pyo3(text_signature = "(infile: str, fmt: str, /)")]
+fn schema_report_py<'py>(
+    py: Python<'py>,
+    infile: PathBuf,
+    fmt: &str,
+) -> PyResult<Bound<'py, PyDict>> {
+    let fmt = fmt.parse::<FileFormat>()?;
+    let report = py.allow_threads(|| match fmt {
+        FileFormat::Dmap => schema_report::<GenericRecord>(&infile),
+        FileFormat::Iqdat => schema_report::<IqdatRecord>(&infile),
+        FileFormat::Rawacf => schema_report::<RawacfRecord>(&infile),
+        FileFormat::Fitacf => schema_report::<FitacfRecord>(&infile),
+        FileFormat::Grid => schema_report::<GridRecord>(&infile),
+        FileFormat::Map => schema_report::<MapRecord>(&infile),
+        FileFormat::Snd => schema_report::<SndRecord>(&infile),
+    })?;
+    let dict = PyDict::new_bound(py);
+    dict.set_item("num_records", report.num_records)?;
+    dict.set_item("required", report.required)?;
+    dict.set_item("optional", report.optional)?;
+    dict.set_item("unknown", report.unknown)?;
+    let mismatches = report
+        .mismatches
+        .into_iter()
+        .map(|x| {
+            let mismatch = PyDict::new_bound(py);
+            mismatch.set_item("name", x.name)?;
+            mismatch.set_item("expected", x.expected)?;
+            mismatch.set_item("found", x.found)?;
+            mismatch.set_item("num_records", x.num_records)?;
+            Ok(mismatch)
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+    dict.set_item("mismatches", mismatches)?;
+    Ok(dict)
+}
+
+/// Computes a hash of each record of `infile`, a file of format `fmt` (e.g. `"fitacf"`),
+/// returning a list of ints. The hashes are stable, so they can be compared between processing
+/// runs to find which records changed.
+#[pyfunction]
+#[pyo3(name = "record_hashes")]
+#[pyo3(text_signature = "(infile: str, fmt: str, /)")]
+fn record_hashes_py(py: Python<'_>, infile: PathBuf, fmt: &str) -> PyResult<Vec<u64>> {
+    let fmt = fmt.parse::<FileFormat>()?;
+    py.allow_threads(|| match fmt {
+        FileFormat::Dmap => record_hashes::<GenericRecord>(&infile),
+        FileFormat::Iqdat => record_hashes::<IqdatRecord>(&infile),
+        FileFormat::Rawacf => record_hashes::<RawacfRecord>(&infile),
+        FileFormat::Fitacf => record_hashes::<FitacfRecord>(&infile),
+        FileFormat::Grid => record_hashes::<GridRecord>(&infile),
+        FileFormat::Map => record_hashes::<MapRecord>(&infile),
+        FileFormat::Snd => record_hashes::<SndRecord>(&infile),
+    })
+    .map_err(PyErr::from)
+}
+
+/// Builds the `WriteOptions` for the `append` and `exist_ok` keywords of the Python write
+/// functions.
+fn py_write_options(append: bool, exist_ok: bool) -> WriteOptions {
+    let mode = match (append, exist_ok) {
+        (_, false) => WriteMode::CreateNew,
+        (true, true) => WriteMode::Append,
+        (false, true) => WriteMode::Overwrite,
+    };
+    WriteOptions {
+        mode,
+        ..Default::default()
+    }
+}
+
+/// Checks that a list of dictionaries contains DMAP records with the same fields, then writes to
+/// outfile. Raises a `ValueError` listing the indices of the records whose fields differ from
+/// the first, in which case nothing is written.
+///
+/// Records are appended to outfile unless `append` is `False`, in which case it is overwritten.
+/// If `exist_ok` is `False`, an existing outfile raises an `IOError`.
 ///
 /// **NOTE:** No type checking is done, so the fields may not be written as the expected
 /// DMAP type, e.g. `stid` might be written one byte instead of two as this function
-/// does not know that typically `stid` is two bytes.
+/// does not know that typically `stid` is two bytes. To keep the width of a scalar, store it as
+/// a numpy scalar such as `numpy.int16(65)` or as a 0-dimensional numpy array, as returned by
+/// the readers with `typed=True`: it is written with the type of its dtype. Use `field_types` to
+/// check the type each field will be written with.
 #[pyfunction]
 #[pyo3(name = "write_dmap")]
-#[pyo3(text_signature = "(recs: list[dict], outfile: str, /)")]
-fn write_dmap_py(recs: Vec<IndexMap<String, DmapField>>, outfile: PathBuf) -> PyResult<()> {
-    try_write_dmap(recs, &outfile).map_err(PyErr::from)
+#[pyo3(signature = (recs, outfile, /, *, append = true, exist_ok = true))]
+#[pyo3(
+    text_signature = "(recs: list[dict], outfile: str, /, *, append: bool = True, exist_ok: bool = True)"
+)]
+fn write_dmap_py(
+    py: Python<'_>,
+    recs: Vec<IndexMap<String, DmapField>>,
+    outfile: PathBuf,
+    append: bool,
+    exist_ok: bool,
+) -> PyResult<()> {
+    let opts = py_write_options(append, exist_ok);
+    py.allow_threads(|| try_write_generic::<GenericRecord>(recs, &outfile, &opts))
+        .map_err(PyErr::from)
 }
 
-/// Checks that a list of dictionaries contains valid IQDAT records, then appends to outfile.
+/// Checks that a list of dictionaries contains valid IQDAT records, then writes to outfile.
+///
+/// Records are appended to outfile unless `append` is `False`, in which case it is overwritten.
+/// If `exist_ok` is `False`, an existing outfile raises an `IOError`.
 #[pyfunction]
 #[pyo3(name = "write_iqdat")]
-#[pyo3(text_signature = "(recs: list[dict], outfile: str, /)")]
-fn write_iqdat_py(recs: Vec<IndexMap<String, DmapField>>, outfile: PathBuf) -> PyResult<()> {
-    try_write_iqdat(recs, &outfile).map_err(PyErr::from)
+#[pyo3(signature = (recs, outfile, /, *, append = true, exist_ok = true))]
+#[pyo3(
+    text_signature = "(recs: list[dict], outfile: str, /, *, append: bool = True, exist_ok: bool = True)"
+)]
+fn write_iqdat_py(
+    py: Python<'_>,
+    recs: Vec<IndexMap<String, DmapField>>,
+    outfile: PathBuf,
+    append: bool,
+    exist_ok: bool,
+) -> PyResult<()> {
+    let opts = py_write_options(append, exist_ok);
+    py.allow_threads(|| try_write_generic::<IqdatRecord>(recs, &outfile, &opts))
+        .map_err(PyErr::from)
 }
 
-/// Checks that a list of dictionaries contains valid RAWACF records, then appends to outfile.
+/// Checks that a list of dictionaries contains valid RAWACF records, then writes to outfile.
+///
+/// Records are appended to outfile unless `append` is `False`, in which case it is overwritten.
+/// If `exist_ok` is `False`, an existing outfile raises an `IOError`.
 #[pyfunction]
 #[pyo3(name = "write_rawacf")]
-#[pyo3(text_signature = "(recs: list[dict], outfile: str, /)")]
-fn write_rawacf_py(recs: Vec<IndexMap<String, DmapField>>, outfile: PathBuf) -> PyResult<()> {
-    try_write_rawacf(recs, &outfile).map_err(PyErr::from)
+#[pyo3(signature = (recs, outfile, /, *, append = true, exist_ok = true))]
+#[pyo3(
+    text_signature = "(recs: list[dict], outfile: str, /, *, append: bool = True, exist_ok: bool = True)"
+)]
+fn write_rawacf_py(
+    py: Python<'_>,
+    recs: Vec<IndexMap<String, DmapField>>,
+    outfile: PathBuf,
+    append: bool,
+    exist_ok: bool,
+) -> PyResult<()> {
+    let opts = py_write_options(append, exist_ok);
+    py.allow_threads(|| try_write_generic::<RawacfRecord>(recs, &outfile, &opts))
+        .map_err(PyErr::from)
+}
+
+/// Checks that a list of dictionaries contains valid RAWACF records, then returns the contents of
+/// a RAWACF file as `bytes` without writing to disk.
+///
+/// `compression` is `"bz2"`, `"gz"` or `"zst"` to compress the bytes, or `None`.
+#[pyfunction]
+#[pyo3(name = "rawacf_to_bytes")]
+#[pyo3(signature = (recs, /, *, compression = None))]
+#[pyo3(text_signature = "(recs: list[dict], /, *, compression: str | None = None)")]
+fn rawacf_to_bytes_py<'py>(
+    py: Python<'py>,
+    recs: Vec<IndexMap<String, DmapField>>,
+    compression: Option<&str>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let compression = compression.map_or(Ok(Compression::None), str::parse)?;
+    let bytes =
+        py.allow_threads(|| compress(try_serialize_records::<RawacfRecord>(recs)?, compression))?;
+    Ok(PyBytes::new_bound(py, &bytes))
 }
 
-/// Checks that a list of dictionaries contains valid FITACF records, then appends to outfile.
+/// Checks that a list of dictionaries contains valid FITACF records, then writes to outfile.
+///
+/// Records are appended to outfile unless `append` is `False`, in which case it is overwritten.
+/// If `exist_ok` is `False`, an existing outfile raises an `IOError`.
 #[pyfunction]
 #[pyo3(name = "write_fitacf")]
-#[pyo3(text_signature = "(recs: list[dict], outfile: str, /)")]
-fn write_fitacf_py(recs: Vec<IndexMap<String, DmapField>>, outfile: PathBuf) -> PyResult<()> {
-    try_write_fitacf(recs, &outfile).map_err(PyErr::from)
+#[pyo3(signature = (recs, outfile, /, *, append = true, exist_ok = true))]
+#[pyo3(
+    text_signature = "(recs: list[dict], outfile: str, /, *, append: bool = True, exist_ok: bool = True)"
+)]
+fn write_fitacf_py(
+    py: Python<'_>,
+    recs: Vec<IndexMap<String, DmapField>>,
+    outfile: PathBuf,
+    append: bool,
+    exist_ok: bool,
+) -> PyResult<()> {
+    let opts = py_write_options(append, exist_ok);
+    py.allow_threads(|| try_write_generic::<FitacfRecord>(recs, &outfile, &opts))
+        .map_err(PyErr::from)
 }
 
-/// Checks that a list of dictionaries contains valid GRID records, then appends to outfile.
+/// Reads a FITACF file and writes its records to `outfile`, replacing any existing file.
+///
+/// Both files are compressed according to their extensions, so this can decompress or recompress
+/// a file, and the fields are written in canonical order.
+#[pyfunction]
+#[pyo3(name = "rewrite_fitacf")]
+#[pyo3(text_signature = "(infile: str, outfile: str, /)")]
+fn rewrite_fitacf_py(py: Python, infile: PathBuf, outfile: PathBuf) -> PyResult<()> {
+    py.allow_threads(|| rewrite::<FitacfRecord>(&infile, &outfile))
+        .map_err(PyErr::from)
+}
+
+/// Checks that a list of dictionaries contains valid GRID records, then writes to outfile.
+///
+/// Records are appended to outfile unless `append` is `False`, in which case it is overwritten.
+/// If `exist_ok` is `False`, an existing outfile raises an `IOError`.
 #[pyfunction]
 #[pyo3(name = "write_grid")]
-#[pyo3(text_signature = "(recs: list[dict], outfile: str, /)")]
-fn write_grid_py(recs: Vec<IndexMap<String, DmapField>>, outfile: PathBuf) -> PyResult<()> {
-    try_write_grid(recs, &outfile).map_err(PyErr::from)
+#[pyo3(signature = (recs, outfile, /, *, append = true, exist_ok = true))]
+#[pyo3(
+    text_signature = "(recs: list[dict], outfile: str, /, *, append: bool = True, exist_ok: bool = True)"
+)]
+fn write_grid_py(
+    py: Python<'_>,
+    recs: Vec<IndexMap<String, DmapField>>,
+    outfile: PathBuf,
+    append: bool,
+    exist_ok: bool,
+) -> PyResult<()> {
+    let opts = py_write_options(append, exist_ok);
+    py.allow_threads(|| try_write_generic::<GridRecord>(recs, &outfile, &opts))
+        .map_err(PyErr::from)
 }
 
-/// Checks that a list of dictionaries contains valid MAP records, then appends to outfile.
+/// Checks that a list of dictionaries contains valid MAP records, then writes to outfile.
+///
+/// Records are appended to outfile unless `append` is `False`, in which case it is overwritten.
+/// If `exist_ok` is `False`, an existing outfile raises an `IOError`.
 #[pyfunction]
 #[pyo3(name = "write_map")]
-#[pyo3(text_signature = "(recs: list[dict], outfile: str, /)")]
-fn write_map_py(recs: Vec<IndexMap<String, DmapField>>, outfile: PathBuf) -> PyResult<()> {
-    try_write_map(recs, &outfile).map_err(PyErr::from)
+#[pyo3(signature = (recs, outfile, /, *, append = true, exist_ok = true))]
+#[pyo3(
+    text_signature = "(recs: list[dict], outfile: str, /, *, append: bool = True, exist_ok: bool = True)"
+)]
+fn write_map_py(
+    py: Python<'_>,
+    recs: Vec<IndexMap<String, DmapField>>,
+    outfile: PathBuf,
+    append: bool,
+    exist_ok: bool,
+) -> PyResult<()> {
+    let opts = py_write_options(append, exist_ok);
+    py.allow_threads(|| try_write_generic::<MapRecord>(recs, &outfile, &opts))
+        .map_err(PyErr::from)
 }
 
-/// Checks that a list of dictionaries contains valid SND records, then appends to outfile.
+/// Checks that a list of dictionaries contains valid SND records, then writes to outfile.
+///
+/// Records are appended to outfile unless `append` is `False`, in which case it is overwritten.
+/// If `exist_ok` is `False`, an existing outfile raises an `IOError`.
 #[pyfunction]
 #[pyo3(name = "write_snd")]
-#[pyo3(text_signature = "(recs: list[dict], outfile: str, /)")]
-fn write_snd_py(recs: Vec<IndexMap<String, DmapField>>, outfile: PathBuf) -> PyResult<()> {
-    try_write_snd(recs, &outfile).map_err(PyErr::from)
+#[pyo3(signature = (recs, outfile, /, *, append = true, exist_ok = true))]
+#[pyo3(
+    text_signature = "(recs: list[dict], outfile: str, /, *, append: bool = True, exist_ok: bool = True)"
+)]
+fn write_snd_py(
+    py: Python<'_>,
+    recs: Vec<IndexMap<String, DmapField>>,
+    outfile: PathBuf,
+    append: bool,
+    exist_ok: bool,
+) -> PyResult<()> {
+    let opts = py_write_options(append, exist_ok);
+    py.allow_threads(|| try_write_generic::<SndRecord>(recs, &outfile, &opts))
+        .map_err(PyErr::from)
 }
 
 /// Functions for SuperDARN DMAP file format I/O.
+///
+/// The readers and writers release the GIL while records are parsed or written, so they can run
+/// concurrently on Python threads.
 #[pymodule]
 fn dmap(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add(
+        "BadRecordsError",
+        m.py().get_type_bound::<BadRecordsError>(),
+    )?;
+    m.add(
+        "TruncatedFileError",
+        m.py().get_type_bound::<TruncatedFileError>(),
+    )?;
     m.add_function(wrap_pyfunction!(read_dmap_py, m)?)?;
     m.add_function(wrap_pyfunction!(read_iqdat_py, m)?)?;
     m.add_function(wrap_pyfunction!(read_rawacf_py, m)?)?;
@@ -364,13 +1982,62 @@ fn dmap(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(read_snd_py, m)?)?;
     m.add_function(wrap_pyfunction!(read_grid_py, m)?)?;
     m.add_function(wrap_pyfunction!(read_map_py, m)?)?;
+    m.add_function(wrap_pyfunction!(read_any_py, m)?)?;
+    m.add_function(wrap_pyfunction!(read_all_py, m)?)?;
+    m.add_function(wrap_pyfunction!(read_rawacf_files_py, m)?)?;
+    m.add_function(wrap_pyfunction!(read_fitacf_beams_py, m)?)?;
+    m.add_function(wrap_pyfunction!(fitacf_to_csv_py, m)?)?;
+    m.add_function(wrap_pyfunction!(strip_xcf_py, m)?)?;
+    m.add_function(wrap_pyfunction!(extract_scalar_py, m)?)?;
+    m.add_function(wrap_pyfunction!(summary_py, m)?)?;
+    m.add_function(wrap_pyfunction!(field_types_py, m)?)?;
+    m.add_function(wrap_pyfunction!(check_rawacf_py, m)?)?;
+    m.add_function(wrap_pyfunction!(acfd_complex_py, m)?)?;
+    m.add_function(wrap_pyfunction!(xcfd_complex_py, m)?)?;
+    m.add_function(wrap_pyfunction!(manifest_py, m)?)?;
+    m.add_function(wrap_pyfunction!(origin_time_py, m)?)?;
+    m.add_function(wrap_pyfunction!(record_time_py, m)?)?;
+    m.add_function(wrap_pyfunction!(record_timestamp_py, m)?)?;
+    m.add_function(wrap_pyfunction!(time_bounds_py, m)?)?;
+    m.add_function(wrap_pyfunction!(file_info_py, m)?)?;
+    m.add_function(wrap_pyfunction!(validate_fitacf_py, m)?)?;
+    m.add_function(wrap_pyfunction!(sniff_format_py, m)?)?;
+    m.add_function(wrap_pyfunction!(uncompressed_size_py, m)?)?;
+    m.add_function(wrap_pyfunction!(read_dmap_bytes_py, m)?)?;
+    m.add_function(wrap_pyfunction!(read_iqdat_bytes_py, m)?)?;
+    m.add_function(wrap_pyfunction!(read_rawacf_bytes_py, m)?)?;
+    m.add_function(wrap_pyfunction!(read_fitacf_bytes_py, m)?)?;
+    m.add_function(wrap_pyfunction!(read_snd_bytes_py, m)?)?;
+    m.add_function(wrap_pyfunction!(read_grid_bytes_py, m)?)?;
+    m.add_function(wrap_pyfunction!(read_map_bytes_py, m)?)?;
+    m.add_function(wrap_pyfunction!(read_dmap_indexed_py, m)?)?;
+    m.add_function(wrap_pyfunction!(read_dmap_lax_py, m)?)?;
+    m.add_function(wrap_pyfunction!(read_iqdat_indexed_py, m)?)?;
+    m.add_function(wrap_pyfunction!(read_iqdat_lax_py, m)?)?;
+    m.add_function(wrap_pyfunction!(read_rawacf_indexed_py, m)?)?;
+    m.add_function(wrap_pyfunction!(read_rawacf_lax_py, m)?)?;
+    m.add_function(wrap_pyfunction!(read_fitacf_indexed_py, m)?)?;
+    m.add_function(wrap_pyfunction!(read_fitacf_lax_py, m)?)?;
+    m.add_function(wrap_pyfunction!(read_snd_indexed_py, m)?)?;
+    m.add_function(wrap_pyfunction!(read_snd_lax_py, m)?)?;
+    m.add_function(wrap_pyfunction!(read_grid_indexed_py, m)?)?;
+    m.add_function(wrap_pyfunction!(read_grid_lax_py, m)?)?;
+    m.add_function(wrap_pyfunction!(read_map_indexed_py, m)?)?;
+    m.add_function(wrap_pyfunction!(read_map_lax_py, m)?)?;
     m.add_function(wrap_pyfunction!(write_dmap_py, m)?)?;
     m.add_function(wrap_pyfunction!(write_iqdat_py, m)?)?;
     m.add_function(wrap_pyfunction!(write_rawacf_py, m)?)?;
+    m.add_function(wrap_pyfunction!(rawacf_to_bytes_py, m)?)?;
     m.add_function(wrap_pyfunction!(write_fitacf_py, m)?)?;
+    m.add_function(wrap_pyfunction!(rewrite_fitacf_py, m)?)?;
     m.add_function(wrap_pyfunction!(write_grid_py, m)?)?;
     m.add_function(wrap_pyfunction!(write_map_py, m)?)?;
     m.add_function(wrap_pyfunction!(write_snd_py, m)?)?;
+    m.add_function(wrap_pyfunction!(write_py, m)?)?;
+    m.add_function(wrap_pyfunction!(concat_files_py, m)?)?;
+    m.add_function(wrap_pyfunction!(present_optional_fields_py, m)?)?;
+    m.add_function(wrap_pyfunction!(schema_report_py, m)?)?;
+    m.add_function(wrap_pyfunction!(record_hashes_py, m)?)?;
 
     Ok(())
 }
@@ -2,18 +2,24 @@
 use crate::error::DmapError;
 use indexmap::IndexMap;
 use numpy::array::PyArray;
-use numpy::ndarray::ArrayD;
+use numpy::ndarray::{arr0, ArrayD};
 use numpy::PyArrayMethods;
 use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
 use pyo3::{Bound, FromPyObject, IntoPy, PyAny, PyObject, PyResult, Python};
 use std::cmp::PartialEq;
+use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 use std::io::Cursor;
 use zerocopy::{AsBytes, ByteOrder, FromBytes, LittleEndian};
 
 type Result<T> = std::result::Result<T, DmapError>;
 
+/// The value of the `code` field which begins every DMAP record, defined as `DATACODE` in
+/// [RST](https://github.com/SuperDARN/rst/blob/main/codebase/general/src.lib/dmap.1.25/include/dmap.h).
+/// Its bytes also identify the byte order of the record.
+pub const DMAP_CODE: i32 = 65537;
+
 /// Defines the fields of a record and their `Type`.
 pub struct Fields<'a> {
     /// The names of all fields of the record type
@@ -33,6 +39,11 @@ pub struct Fields<'a> {
 /// The possible data types that a scalar or vector field may have.
 ///
 /// `String` type is not supported for vector fields.
+///
+/// No field of the RST formats is `Long` or `Ulong`: 8-byte fields such as the times in MAP and
+/// GRID files are `Double`. 64-bit integers are read and written in generic records, and files
+/// which store a field of a known format as a 64-bit integer can be read with
+/// `ReadOptions::type_overrides`, which casts the values to the expected type.
 #[derive(Debug, PartialEq, Clone)]
 pub enum Type {
     Char,
@@ -136,7 +147,7 @@ pub enum DmapScalar {
 }
 impl DmapScalar {
     /// Gets the corresponding `Type`
-    pub(crate) fn get_type(&self) -> Type {
+    pub fn get_type(&self) -> Type {
         match self {
             Self::Char(_) => Type::Char,
             Self::Short(_) => Type::Short,
@@ -151,8 +162,29 @@ impl DmapScalar {
             Self::String(_) => Type::String,
         }
     }
+    /// Widens a numeric scalar into a `Number` without loss, or `None` for a `String`.
+    fn to_number(&self) -> Option<Number> {
+        match self {
+            Self::Char(x) => Some(x.to_number()),
+            Self::Short(x) => Some(x.to_number()),
+            Self::Int(x) => Some(x.to_number()),
+            Self::Long(x) => Some(x.to_number()),
+            Self::Uchar(x) => Some(x.to_number()),
+            Self::Ushort(x) => Some(x.to_number()),
+            Self::Uint(x) => Some(x.to_number()),
+            Self::Ulong(x) => Some(x.to_number()),
+            Self::Float(x) => Some(x.to_number()),
+            Self::Double(x) => Some(x.to_number()),
+            Self::String(_) => None,
+        }
+    }
     /// Converts `self` into a new `Type`, if possible.
-    pub(crate) fn cast_as(&self, new_type: &Type) -> Result<Self> {
+    ///
+    /// Integer values are converted exactly or not at all, so e.g. an `Int` of `70000` cannot be
+    /// cast to `Short`, and a `Float` can only be cast to an integer type if it is a whole number
+    /// within range. Casting a `Double` to `Float` may lose precision, but fails if the value
+    /// overflows. Strings cannot be cast to or from numeric types.
+    pub fn cast_as(&self, new_type: &Type) -> Result<Self> {
         match new_type {
             Type::Char => Ok(Self::Char(i8::try_from(self.clone())?)),
             Type::Short => Ok(Self::Short(i16::try_from(self.clone())?)),
@@ -164,9 +196,7 @@ impl DmapScalar {
             Type::Ulong => Ok(Self::Ulong(u64::try_from(self.clone())?)),
             Type::Float => Ok(Self::Float(f32::try_from(self.clone())?)),
             Type::Double => Ok(Self::Double(f64::try_from(self.clone())?)),
-            Type::String => Err(DmapError::InvalidScalar(
-                "Unable to cast value to String".to_string(),
-            )),
+            Type::String => Ok(Self::String(String::try_from(self.clone())?)),
         }
     }
     /// Copies the data and metadata (`Type` key) to raw bytes
@@ -188,6 +218,22 @@ impl DmapScalar {
         bytes.append(&mut data_bytes);
         bytes
     }
+    /// Formats the value without its `Type`, e.g. `5` rather than `SHORT 5`.
+    pub(crate) fn value_string(&self) -> String {
+        match self {
+            Self::Char(x) => x.to_string(),
+            Self::Short(x) => x.to_string(),
+            Self::Int(x) => x.to_string(),
+            Self::Long(x) => x.to_string(),
+            Self::Uchar(x) => x.to_string(),
+            Self::Ushort(x) => x.to_string(),
+            Self::Uint(x) => x.to_string(),
+            Self::Ulong(x) => x.to_string(),
+            Self::Float(x) => x.to_string(),
+            Self::Double(x) => x.to_string(),
+            Self::String(x) => x.clone(),
+        }
+    }
 }
 impl Display for DmapScalar {
     fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
@@ -206,6 +252,24 @@ impl Display for DmapScalar {
         }
     }
 }
+/// Formats the elements of the vector as an `ndarray` array, without its `Type`. Large arrays are
+/// abbreviated unless formatted with the alternate flag, e.g. `{:#}`.
+impl Display for DmapVec {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Self::Char(x) => Display::fmt(x, f),
+            Self::Short(x) => Display::fmt(x, f),
+            Self::Int(x) => Display::fmt(x, f),
+            Self::Long(x) => Display::fmt(x, f),
+            Self::Uchar(x) => Display::fmt(x, f),
+            Self::Ushort(x) => Display::fmt(x, f),
+            Self::Uint(x) => Display::fmt(x, f),
+            Self::Ulong(x) => Display::fmt(x, f),
+            Self::Float(x) => Display::fmt(x, f),
+            Self::Double(x) => Display::fmt(x, f),
+        }
+    }
+}
 impl IntoPy<PyObject> for DmapScalar {
     fn into_py(self, py: Python<'_>) -> PyObject {
         match self {
@@ -223,8 +287,32 @@ impl IntoPy<PyObject> for DmapScalar {
         }
     }
 }
+impl DmapScalar {
+    /// Converts the scalar into a 0-dimensional numpy array of the exact dtype, so that its
+    /// `Type` is kept in Python. Strings are converted to `str` as usual.
+    fn into_typed_py(self, py: Python<'_>) -> PyObject {
+        match self {
+            Self::Char(x) => DmapVec::Char(arr0(x).into_dyn()).into_py(py),
+            Self::Short(x) => DmapVec::Short(arr0(x).into_dyn()).into_py(py),
+            Self::Int(x) => DmapVec::Int(arr0(x).into_dyn()).into_py(py),
+            Self::Long(x) => DmapVec::Long(arr0(x).into_dyn()).into_py(py),
+            Self::Uchar(x) => DmapVec::Uchar(arr0(x).into_dyn()).into_py(py),
+            Self::Ushort(x) => DmapVec::Ushort(arr0(x).into_dyn()).into_py(py),
+            Self::Uint(x) => DmapVec::Uint(arr0(x).into_dyn()).into_py(py),
+            Self::Ulong(x) => DmapVec::Ulong(arr0(x).into_dyn()).into_py(py),
+            Self::Float(x) => DmapVec::Float(arr0(x).into_dyn()).into_py(py),
+            Self::Double(x) => DmapVec::Double(arr0(x).into_dyn()).into_py(py),
+            Self::String(x) => x.into_py(py),
+        }
+    }
+}
 
 /// A vector field in a DMAP record.
+///
+/// Vectors are row-major arrays, indexed in the same order as the C arrays of RST: the last axis
+/// varies fastest. DMAP stores the dimensions of a vector fastest-varying first, so the shape is
+/// the reverse of the dimensions in the file. For example, the `acfd` field of a RAWACF record is
+/// stored with dimensions `[2, mplgs, n]` and has shape `[n, mplgs, 2]`.
 #[derive(Clone, Debug, PartialEq)]
 pub enum DmapVec {
     Char(ArrayD<i8>),
@@ -240,7 +328,7 @@ pub enum DmapVec {
 }
 impl DmapVec {
     /// Gets the corresponding `Type` of the vector
-    pub(crate) fn get_type(&self) -> Type {
+    pub fn get_type(&self) -> Type {
         match self {
             DmapVec::Char(_) => Type::Char,
             DmapVec::Short(_) => Type::Short,
@@ -366,7 +454,196 @@ impl DmapVec {
             DmapVec::Double(x) => x.shape(),
         }
     }
+    /// Whether `self` and `other` have the same type and shape, and elements which are equal to
+    /// within tolerance.
+    ///
+    /// `FLOAT` and `DOUBLE` elements `x` and `y` are equal if `|x - y|` is at most
+    /// `max(rel_tol * max(|x|, |y|), abs_tol)`, or if both are NaN. Elements of other types must
+    /// be exactly equal.
+    pub fn approx_eq(&self, other: &DmapVec, rel_tol: f32, abs_tol: f32) -> bool {
+        fn close(x: f64, y: f64, rel_tol: f64, abs_tol: f64) -> bool {
+            (x.is_nan() && y.is_nan())
+                || x == y
+                || (x - y).abs() <= (rel_tol * x.abs().max(y.abs())).max(abs_tol)
+        }
+        let (rel_tol, abs_tol) = (rel_tol as f64, abs_tol as f64);
+        match (self, other) {
+            _ if self.shape() != other.shape() => false,
+            (DmapVec::Float(x), DmapVec::Float(y)) => x
+                .iter()
+                .zip(y.iter())
+                .all(|(&a, &b)| close(a as f64, b as f64, rel_tol, abs_tol)),
+            (DmapVec::Double(x), DmapVec::Double(y)) => x
+                .iter()
+                .zip(y.iter())
+                .all(|(&a, &b)| close(a, b, rel_tol, abs_tol)),
+            _ => self == other,
+        }
+    }
+    /// Converts a 0-dimensional vector into the scalar it holds, or returns `None` if the vector
+    /// has any dimensions.
+    fn into_scalar(self) -> Option<DmapScalar> {
+        if !self.shape().is_empty() {
+            return None;
+        }
+        match self {
+            DmapVec::Char(x) => x.first().copied().map(DmapScalar::Char),
+            DmapVec::Short(x) => x.first().copied().map(DmapScalar::Short),
+            DmapVec::Int(x) => x.first().copied().map(DmapScalar::Int),
+            DmapVec::Long(x) => x.first().copied().map(DmapScalar::Long),
+            DmapVec::Uchar(x) => x.first().copied().map(DmapScalar::Uchar),
+            DmapVec::Ushort(x) => x.first().copied().map(DmapScalar::Ushort),
+            DmapVec::Uint(x) => x.first().copied().map(DmapScalar::Uint),
+            DmapVec::Ulong(x) => x.first().copied().map(DmapScalar::Ulong),
+            DmapVec::Float(x) => x.first().copied().map(DmapScalar::Float),
+            DmapVec::Double(x) => x.first().copied().map(DmapScalar::Double),
+        }
+    }
+    /// Formats element `index` of the flattened vector, or `None` if `index` is out of bounds.
+    pub(crate) fn element_string(&self, index: usize) -> Option<String> {
+        match self {
+            DmapVec::Char(x) => x.iter().nth(index).map(|y| y.to_string()),
+            DmapVec::Short(x) => x.iter().nth(index).map(|y| y.to_string()),
+            DmapVec::Int(x) => x.iter().nth(index).map(|y| y.to_string()),
+            DmapVec::Long(x) => x.iter().nth(index).map(|y| y.to_string()),
+            DmapVec::Uchar(x) => x.iter().nth(index).map(|y| y.to_string()),
+            DmapVec::Ushort(x) => x.iter().nth(index).map(|y| y.to_string()),
+            DmapVec::Uint(x) => x.iter().nth(index).map(|y| y.to_string()),
+            DmapVec::Ulong(x) => x.iter().nth(index).map(|y| y.to_string()),
+            DmapVec::Float(x) => x.iter().nth(index).map(|y| y.to_string()),
+            DmapVec::Double(x) => x.iter().nth(index).map(|y| y.to_string()),
+        }
+    }
+    /// Converts the elements of `self` into a new `Type`, preserving the shape.
+    ///
+    /// Elements are converted in the same way as `DmapScalar::cast_as`. Returns `DmapError` if
+    /// any integer element cannot be represented exactly as the new type, e.g. an `Int` of
+    /// `70000` cast to `Short`, or a `Float` with a fractional part cast to `Int`. Casting a
+    /// `Double` vector to `Float` may lose precision, but fails if any element overflows.
+    pub fn cast_as(&self, new_type: &Type) -> Result<DmapVec> {
+        match self {
+            DmapVec::Char(x) => cast_array(x, new_type),
+            DmapVec::Short(x) => cast_array(x, new_type),
+            DmapVec::Int(x) => cast_array(x, new_type),
+            DmapVec::Long(x) => cast_array(x, new_type),
+            DmapVec::Uchar(x) => cast_array(x, new_type),
+            DmapVec::Ushort(x) => cast_array(x, new_type),
+            DmapVec::Uint(x) => cast_array(x, new_type),
+            DmapVec::Ulong(x) => cast_array(x, new_type),
+            DmapVec::Float(x) => cast_array(x, new_type),
+            DmapVec::Double(x) => cast_array(x, new_type),
+        }
+    }
+    /// Converts the elements of `self` into the `Type` `target`, preserving the shape. The same as
+    /// `DmapVec::cast_as`.
+    pub fn cast_to(&self, target: Type) -> Result<DmapVec> {
+        self.cast_as(&target)
+    }
+}
+
+/// Intermediate representation of a numeric value, wide enough to hold any DMAP numeric type.
+#[derive(Debug, Clone, Copy)]
+enum Number {
+    Integer(i128),
+    Real(f64),
 }
+
+/// Numeric types which can be stored as elements of a `DmapVec` or as a `DmapScalar`.
+trait VecElement: DmapType + Copy {
+    /// Widens `self` into a `Number` without loss.
+    fn to_number(self) -> Number;
+    /// Narrows a `Number` into `Self`, returning `None` if the value is out of range. Integer
+    /// values must be represented exactly, while real values narrowed to `f32` are rounded.
+    fn from_number(value: Number) -> Option<Self>
+    where
+        Self: Sized;
+}
+
+/// Implements `VecElement` for the integer types
+macro_rules! vec_element_int {
+    ($($t:ty),*) => {$(
+        impl VecElement for $t {
+            fn to_number(self) -> Number {
+                Number::Integer(self as i128)
+            }
+            fn from_number(value: Number) -> Option<Self> {
+                match value {
+                    Number::Integer(x) => <$t>::try_from(x).ok(),
+                    Number::Real(x) if x.is_finite() && x.fract() == 0.0 => {
+                        if x < <$t>::MIN as f64 || x > <$t>::MAX as f64 {
+                            None
+                        } else {
+                            <$t>::try_from(x as i128).ok()
+                        }
+                    }
+                    Number::Real(_) => None,
+                }
+            }
+        }
+    )*};
+}
+vec_element_int!(i8, i16, i32, i64, u8, u16, u32, u64);
+
+impl VecElement for f32 {
+    fn to_number(self) -> Number {
+        Number::Real(self as f64)
+    }
+    fn from_number(value: Number) -> Option<Self> {
+        match value {
+            Number::Integer(x) if (x as f32) as i128 == x => Some(x as f32),
+            Number::Integer(_) => None,
+            Number::Real(x) if x.is_finite() && (x as f32).is_infinite() => None,
+            Number::Real(x) => Some(x as f32),
+        }
+    }
+}
+impl VecElement for f64 {
+    fn to_number(self) -> Number {
+        Number::Real(self)
+    }
+    fn from_number(value: Number) -> Option<Self> {
+        match value {
+            Number::Integer(x) if (x as f64) as i128 == x => Some(x as f64),
+            Number::Integer(_) => None,
+            Number::Real(x) => Some(x),
+        }
+    }
+}
+
+/// Converts each element of `arr` into type `U`, keeping the shape of `arr`.
+fn convert_elements<T: VecElement, U: VecElement>(
+    arr: &ArrayD<T>,
+    target: &Type,
+) -> Result<ArrayD<U>> {
+    let mut data: Vec<U> = Vec::with_capacity(arr.len());
+    for &x in arr.iter() {
+        data.push(U::from_number(x.to_number()).ok_or_else(|| {
+            DmapError::InvalidVector(format!("Unable to convert {x:?} to {target}"))
+        })?);
+    }
+    ArrayD::from_shape_vec(arr.shape(), data)
+        .map_err(|e| DmapError::InvalidVector(format!("Unable to cast vector to {target}: {e}")))
+}
+
+/// Converts `arr` into the `DmapVec` variant corresponding to `target`.
+fn cast_array<T: VecElement>(arr: &ArrayD<T>, target: &Type) -> Result<DmapVec> {
+    match target {
+        Type::Char => Ok(DmapVec::Char(convert_elements(arr, target)?)),
+        Type::Short => Ok(DmapVec::Short(convert_elements(arr, target)?)),
+        Type::Int => Ok(DmapVec::Int(convert_elements(arr, target)?)),
+        Type::Long => Ok(DmapVec::Long(convert_elements(arr, target)?)),
+        Type::Uchar => Ok(DmapVec::Uchar(convert_elements(arr, target)?)),
+        Type::Ushort => Ok(DmapVec::Ushort(convert_elements(arr, target)?)),
+        Type::Uint => Ok(DmapVec::Uint(convert_elements(arr, target)?)),
+        Type::Ulong => Ok(DmapVec::Ulong(convert_elements(arr, target)?)),
+        Type::Float => Ok(DmapVec::Float(convert_elements(arr, target)?)),
+        Type::Double => Ok(DmapVec::Double(convert_elements(arr, target)?)),
+        Type::String => Err(DmapError::InvalidVector(
+            "Unable to cast vector to String".to_string(),
+        )),
+    }
+}
+
 impl IntoPy<PyObject> for DmapVec {
     fn into_py(self, py: Python<'_>) -> PyObject {
         match self {
@@ -595,7 +872,7 @@ impl TryFrom<DmapVec> for ArrayD<f64> {
 ///
 /// This is the type that is stored in a DMAP record, representing either a scalar or
 /// vector field.
-#[derive(Debug, Clone, PartialEq, FromPyObject)]
+#[derive(Debug, Clone, PartialEq)]
 #[repr(C)]
 pub enum DmapField {
     Vector(DmapVec),
@@ -609,6 +886,13 @@ impl DmapField {
             Self::Vector(x) => x.as_bytes(),
         }
     }
+    /// Gets the `Type` of the field's data.
+    pub fn get_type(&self) -> Type {
+        match self {
+            Self::Scalar(x) => x.get_type(),
+            Self::Vector(x) => x.get_type(),
+        }
+    }
 }
 impl IntoPy<PyObject> for DmapField {
     fn into_py(self, py: Python<'_>) -> PyObject {
@@ -618,6 +902,43 @@ impl IntoPy<PyObject> for DmapField {
         }
     }
 }
+impl<'py> FromPyObject<'py> for DmapField {
+    /// Extracts numpy arrays as vectors, except that 0-dimensional arrays are scalars of the
+    /// array's dtype, as returned by `DmapScalar::into_typed_py`. Numpy scalars such as
+    /// `numpy.int16(5)` are also scalars of their dtype, while Python `int`s and `float`s are
+    /// the first `Type` which can hold the value.
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        let from_array = |x: DmapVec| {
+            if x.shape().is_empty() {
+                DmapField::Scalar(
+                    x.into_scalar()
+                        .expect("0-dimensional array has one element"),
+                )
+            } else {
+                DmapField::Vector(x)
+            }
+        };
+        if let Ok(x) = ob.extract::<DmapVec>() {
+            return Ok(from_array(x));
+        }
+        if ob.hasattr("__array__")? {
+            if let Ok(x) = ob.call_method0("__array__")?.extract::<DmapVec>() {
+                return Ok(from_array(x));
+            }
+        }
+        Ok(DmapField::Scalar(ob.extract::<DmapScalar>()?))
+    }
+}
+impl DmapField {
+    /// Converts the field into a Python object, with scalars as 0-dimensional numpy arrays of
+    /// the exact dtype. See `DmapScalar::into_typed_py`.
+    pub(crate) fn into_typed_py(self, py: Python<'_>) -> PyObject {
+        match self {
+            DmapField::Scalar(x) => x.into_typed_py(py),
+            DmapField::Vector(x) => x.into_py(py),
+        }
+    }
+}
 impl From<i8> for DmapField {
     fn from(value: i8) -> Self {
         DmapField::Scalar(DmapScalar::Char(value))
@@ -673,6 +994,11 @@ impl From<String> for DmapField {
         DmapField::Scalar(DmapScalar::String(value))
     }
 }
+impl From<&str> for DmapField {
+    fn from(value: &str) -> Self {
+        DmapField::Scalar(DmapScalar::String(value.to_string()))
+    }
+}
 impl From<ArrayD<i8>> for DmapField {
     fn from(value: ArrayD<i8>) -> Self {
         DmapField::Vector(DmapVec::Char(value))
@@ -976,6 +1302,205 @@ impl TryFrom<DmapField> for ArrayD<f64> {
     }
 }
 
+/// Trait for borrowing a typed value from a `DmapField`, used by the typed accessor methods of
+/// each record type.
+pub trait FieldValue<'a>: Sized {
+    /// Borrows the value of `field`, returning `None` if `field` is not of type `Self`.
+    fn from_field(field: &'a DmapField) -> Option<Self>;
+    /// Describes the kind of field that `Self` is borrowed from, for error messages.
+    fn description() -> String;
+}
+macro_rules! field_value_impls {
+    ($($variant:ident: $type:ty),*) => {
+        $(
+            impl FieldValue<'_> for $type {
+                fn from_field(field: &DmapField) -> Option<Self> {
+                    match field {
+                        DmapField::Scalar(DmapScalar::$variant(x)) => Some(*x),
+                        _ => None,
+                    }
+                }
+                fn description() -> String {
+                    format!("scalar of type {}", Type::$variant)
+                }
+            }
+            impl<'a> FieldValue<'a> for &'a ArrayD<$type> {
+                fn from_field(field: &'a DmapField) -> Option<Self> {
+                    match field {
+                        DmapField::Vector(DmapVec::$variant(x)) => Some(x),
+                        _ => None,
+                    }
+                }
+                fn description() -> String {
+                    format!("vector of type {}", Type::$variant)
+                }
+            }
+        )*
+    }
+}
+field_value_impls!(
+    Char: i8,
+    Short: i16,
+    Int: i32,
+    Long: i64,
+    Uchar: u8,
+    Ushort: u16,
+    Uint: u32,
+    Ulong: u64,
+    Float: f32,
+    Double: f64
+);
+impl<'a> FieldValue<'a> for &'a str {
+    fn from_field(field: &'a DmapField) -> Option<Self> {
+        match field {
+            DmapField::Scalar(DmapScalar::String(x)) => Some(x.as_str()),
+            _ => None,
+        }
+    }
+    fn description() -> String {
+        format!("scalar of type {}", Type::String)
+    }
+}
+
+/// Gets the field `key` of `data` as type `T`.
+///
+/// Returns `DmapError` if `key` is not in `data`, or the field is not of type `T`.
+pub fn get_field_as<'a, T: FieldValue<'a>>(
+    data: &'a IndexMap<String, DmapField>,
+    key: &str,
+) -> Result<T> {
+    let field = data
+        .get(key)
+        .ok_or_else(|| DmapError::InvalidRecord(format!("Field {key} not in record")))?;
+    T::from_field(field).ok_or_else(|| {
+        let found = match field {
+            DmapField::Scalar(x) => format!("scalar of type {}", x.get_type()),
+            DmapField::Vector(x) => format!("vector of type {}", x.get_type()),
+        };
+        let msg = format!("Field {key} is a {found}, expected a {}", T::description());
+        match field {
+            DmapField::Scalar(_) => DmapError::InvalidScalar(msg),
+            DmapField::Vector(_) => DmapError::InvalidVector(msg),
+        }
+    })
+}
+
+/// Removes each of `fields` from `record`, keeping the order of the remaining fields. Fields which
+/// are not in `record` are ignored.
+pub fn drop_fields(record: &mut IndexMap<String, DmapField>, fields: &[&str]) {
+    for name in fields {
+        record.shift_remove(*name);
+    }
+}
+
+/// Maps the name of each field of `fields` to the name of its `Type`, e.g. `"SHORT"`, for both
+/// scalar and vector fields.
+pub fn field_types(fields: &IndexMap<String, DmapField>) -> HashMap<String, String> {
+    fields
+        .iter()
+        .map(|(name, field)| (name.clone(), field.get_type().to_string()))
+        .collect()
+}
+
+/// The letters of the numeric `channel` values, in order from channel 1.
+///
+/// Stereo radars record `channel` as 1 for channel A and 2 for channel B, and name their files
+/// with the lowercase letter. Radars with a single channel record `channel` as 0, which has no
+/// letter.
+const CHANNEL_LETTERS: [char; 2] = ['a', 'b'];
+
+/// Converts a numeric `channel` to the letter used in file names: 1 is `'a'` and 2 is `'b'`.
+///
+/// Returns `DmapError` for any other value, including 0 for single-channel radars.
+pub fn channel_to_letter(channel: i16) -> Result<char> {
+    usize::try_from(channel)
+        .ok()
+        .and_then(|x| x.checked_sub(1))
+        .and_then(|i| CHANNEL_LETTERS.get(i))
+        .copied()
+        .ok_or_else(|| DmapError::InvalidScalar(format!("Channel {channel} has no letter")))
+}
+
+/// Converts a channel letter to the numeric `channel`: `'a'` is 1 and `'b'` is 2, ignoring case.
+///
+/// Returns `DmapError` for any other character.
+pub fn letter_to_channel(letter: char) -> Result<i16> {
+    CHANNEL_LETTERS
+        .iter()
+        .position(|&x| x == letter.to_ascii_lowercase())
+        .map(|i| i as i16 + 1)
+        .ok_or_else(|| DmapError::InvalidScalar(format!("Unknown channel letter '{letter}'")))
+}
+
+/// Rewrites the `channel` scalar of `record` as a `SHORT` in the numeric convention, for merging
+/// files written by tools which disagree on the convention.
+///
+/// A `channel` stored as a one-letter string, or as a `CHAR` holding a letter, is converted with
+/// `letter_to_channel`. Other integer types are converted to `SHORT` unchanged. Records without a
+/// `channel` are left as they are. Returns `DmapError` if `channel` is a vector, an unknown
+/// letter or a value which does not fit in a `SHORT`.
+pub fn normalize_channel(record: &mut IndexMap<String, DmapField>) -> Result<()> {
+    let channel = match record.get("channel") {
+        None => return Ok(()),
+        Some(DmapField::Scalar(DmapScalar::String(x))) => {
+            let mut chars = x.chars();
+            match (chars.next(), chars.next()) {
+                (Some(letter), None) => letter_to_channel(letter)?,
+                _ => Err(DmapError::InvalidScalar(format!("Unknown channel '{x}'")))?,
+            }
+        }
+        Some(DmapField::Scalar(DmapScalar::Char(x))) if (*x as u8).is_ascii_alphabetic() => {
+            letter_to_channel(*x as u8 as char)?
+        }
+        Some(DmapField::Scalar(x)) => i16::try_from(x.clone())?,
+        Some(DmapField::Vector(_)) => Err(DmapError::InvalidVector(
+            "Field channel is a vector, expected scalar".to_string(),
+        ))?,
+    };
+    record.insert("channel".to_string(), DmapField::from(channel));
+    Ok(())
+}
+
+/// Gets the scalar field `key` of every record in `records`.
+///
+/// Returns `DmapError` naming the index of the first record where `key` is missing or not of
+/// type `T`.
+pub fn extract_scalar<T: DmapType + for<'a> FieldValue<'a>>(
+    records: &[IndexMap<String, DmapField>],
+    key: &str,
+) -> Result<Vec<T>> {
+    records
+        .iter()
+        .enumerate()
+        .map(|(i, rec)| {
+            get_field_as::<T>(rec, key)
+                .map_err(|e| DmapError::InvalidRecord(format!("Record {i}: {e}")))
+        })
+        .collect()
+}
+
+/// Gets a copy of the vector field `key` of every record in `records`.
+///
+/// Returns `DmapError` naming the index of the first record where `key` is missing or not a
+/// vector of type `T`.
+pub fn extract_vector<T: DmapType + Clone>(
+    records: &[IndexMap<String, DmapField>],
+    key: &str,
+) -> Result<Vec<ArrayD<T>>>
+where
+    for<'a> &'a ArrayD<T>: FieldValue<'a>,
+{
+    records
+        .iter()
+        .enumerate()
+        .map(|(i, rec)| {
+            get_field_as::<&ArrayD<T>>(rec, key)
+                .cloned()
+                .map_err(|e| DmapError::InvalidRecord(format!("Record {i}: {e}")))
+        })
+        .collect()
+}
+
 /// Trait for raw types that can be stored in DMAP files.
 pub trait DmapType: std::fmt::Debug {
     /// Size in bytes of the type.
@@ -1186,215 +1711,53 @@ impl DmapType for String {
         bytes.push(0); // null-terminate
         bytes
     }
+    /// Reads the string up to the first null terminator.
+    ///
+    /// Returns `DmapError` if `bytes` is not null-terminated or the string is not valid UTF-8.
     fn from_bytes(bytes: &[u8]) -> Result<Self> {
-        let data = String::from_utf8(bytes.to_owned())
-            .map_err(|_| DmapError::InvalidScalar("Cannot convert bytes to String".to_string()))?;
-        Ok(data.trim_end_matches(char::from(0)).to_string())
+        let end = bytes
+            .iter()
+            .position(|&x| x == 0)
+            .ok_or_else(|| DmapError::InvalidScalar("String is not null-terminated".to_string()))?;
+        String::from_utf8(bytes[..end].to_owned())
+            .map_err(|_| DmapError::InvalidScalar("Cannot convert bytes to String".to_string()))
     }
     fn dmap_type(&self) -> Type {
         Type::String
     }
 }
-impl TryFrom<DmapScalar> for u8 {
-    type Error = DmapError;
-    fn try_from(value: DmapScalar) -> std::result::Result<Self, Self::Error> {
-        match value {
-            DmapScalar::Char(x) => Ok(x as u8),
-            DmapScalar::Short(x) => Ok(x as u8),
-            DmapScalar::Int(x) => Ok(x as u8),
-            DmapScalar::Long(x) => Ok(x as u8),
-            DmapScalar::Uchar(x) => Ok(x),
-            DmapScalar::Ushort(x) => Ok(x as u8),
-            DmapScalar::Uint(x) => Ok(x as u8),
-            DmapScalar::Ulong(x) => Ok(x as u8),
-            DmapScalar::Float(x) => Ok(x as u8),
-            DmapScalar::Double(x) => Ok(x as u8),
-            DmapScalar::String(x) => Err(DmapError::InvalidScalar(format!(
-                "Unable to convert {x} to u8"
-            ))),
-        }
-    }
-}
-impl TryFrom<DmapScalar> for u16 {
-    type Error = DmapError;
-    fn try_from(value: DmapScalar) -> std::result::Result<Self, Self::Error> {
-        match value {
-            DmapScalar::Char(x) => Ok(x as u16),
-            DmapScalar::Short(x) => Ok(x as u16),
-            DmapScalar::Int(x) => Ok(x as u16),
-            DmapScalar::Long(x) => Ok(x as u16),
-            DmapScalar::Uchar(x) => Ok(x as u16),
-            DmapScalar::Ushort(x) => Ok(x),
-            DmapScalar::Uint(x) => Ok(x as u16),
-            DmapScalar::Ulong(x) => Ok(x as u16),
-            DmapScalar::Float(x) => Ok(x as u16),
-            DmapScalar::Double(x) => Ok(x as u16),
-            DmapScalar::String(x) => Err(DmapError::InvalidScalar(format!(
-                "Unable to convert {x} to u16"
-            ))),
-        }
-    }
-}
-impl TryFrom<DmapScalar> for u32 {
-    type Error = DmapError;
-    fn try_from(value: DmapScalar) -> std::result::Result<Self, Self::Error> {
-        match value {
-            DmapScalar::Char(x) => Ok(x as u32),
-            DmapScalar::Short(x) => Ok(x as u32),
-            DmapScalar::Int(x) => Ok(x as u32),
-            DmapScalar::Long(x) => Ok(x as u32),
-            DmapScalar::Uchar(x) => Ok(x as u32),
-            DmapScalar::Ushort(x) => Ok(x as u32),
-            DmapScalar::Uint(x) => Ok(x),
-            DmapScalar::Ulong(x) => Ok(x as u32),
-            DmapScalar::Float(x) => Ok(x as u32),
-            DmapScalar::Double(x) => Ok(x as u32),
-            DmapScalar::String(x) => Err(DmapError::InvalidScalar(format!(
-                "Unable to convert {x} to u32"
-            ))),
-        }
-    }
-}
-impl TryFrom<DmapScalar> for u64 {
-    type Error = DmapError;
-    fn try_from(value: DmapScalar) -> std::result::Result<Self, Self::Error> {
-        match value {
-            DmapScalar::Char(x) => Ok(x as u64),
-            DmapScalar::Short(x) => Ok(x as u64),
-            DmapScalar::Int(x) => Ok(x as u64),
-            DmapScalar::Long(x) => Ok(x as u64),
-            DmapScalar::Uchar(x) => Ok(x as u64),
-            DmapScalar::Ushort(x) => Ok(x as u64),
-            DmapScalar::Uint(x) => Ok(x as u64),
-            DmapScalar::Ulong(x) => Ok(x),
-            DmapScalar::Float(x) => Ok(x as u64),
-            DmapScalar::Double(x) => Ok(x as u64),
-            DmapScalar::String(x) => Err(DmapError::InvalidScalar(format!(
-                "Unable to convert {x} to u64"
-            ))),
-        }
-    }
-}
-impl TryFrom<DmapScalar> for i8 {
-    type Error = DmapError;
-    fn try_from(value: DmapScalar) -> std::result::Result<Self, Self::Error> {
-        match value {
-            DmapScalar::Char(x) => Ok(x),
-            DmapScalar::Short(x) => Ok(x as i8),
-            DmapScalar::Int(x) => Ok(x as i8),
-            DmapScalar::Long(x) => Ok(x as i8),
-            DmapScalar::Uchar(x) => Ok(x as i8),
-            DmapScalar::Ushort(x) => Ok(x as i8),
-            DmapScalar::Uint(x) => Ok(x as i8),
-            DmapScalar::Ulong(x) => Ok(x as i8),
-            DmapScalar::Float(x) => Ok(x as i8),
-            DmapScalar::Double(x) => Ok(x as i8),
-            DmapScalar::String(x) => Err(DmapError::InvalidScalar(format!(
-                "Unable to convert {x} to i8"
-            ))),
-        }
-    }
-}
-impl TryFrom<DmapScalar> for i16 {
-    type Error = DmapError;
-    fn try_from(value: DmapScalar) -> std::result::Result<Self, Self::Error> {
-        match value {
-            DmapScalar::Char(x) => Ok(x as i16),
-            DmapScalar::Short(x) => Ok(x),
-            DmapScalar::Int(x) => Ok(x as i16),
-            DmapScalar::Long(x) => Ok(x as i16),
-            DmapScalar::Uchar(x) => Ok(x as i16),
-            DmapScalar::Ushort(x) => Ok(x as i16),
-            DmapScalar::Uint(x) => Ok(x as i16),
-            DmapScalar::Ulong(x) => Ok(x as i16),
-            DmapScalar::Float(x) => Ok(x as i16),
-            DmapScalar::Double(x) => Ok(x as i16),
-            DmapScalar::String(x) => Err(DmapError::InvalidScalar(format!(
-                "Unable to convert {x} to i16"
-            ))),
-        }
-    }
-}
-impl TryFrom<DmapScalar> for i32 {
-    type Error = DmapError;
-    fn try_from(value: DmapScalar) -> std::result::Result<Self, Self::Error> {
-        match value {
-            DmapScalar::Char(x) => Ok(x as i32),
-            DmapScalar::Short(x) => Ok(x as i32),
-            DmapScalar::Int(x) => Ok(x),
-            DmapScalar::Long(x) => Ok(x as i32),
-            DmapScalar::Uchar(x) => Ok(x as i32),
-            DmapScalar::Ushort(x) => Ok(x as i32),
-            DmapScalar::Uint(x) => Ok(x as i32),
-            DmapScalar::Ulong(x) => Ok(x as i32),
-            DmapScalar::Float(x) => Ok(x as i32),
-            DmapScalar::Double(x) => Ok(x as i32),
-            DmapScalar::String(x) => Err(DmapError::InvalidScalar(format!(
-                "Unable to convert {x} to i32"
-            ))),
-        }
-    }
-}
-impl TryFrom<DmapScalar> for i64 {
-    type Error = DmapError;
-    fn try_from(value: DmapScalar) -> std::result::Result<Self, Self::Error> {
-        match value {
-            DmapScalar::Char(x) => Ok(x as i64),
-            DmapScalar::Short(x) => Ok(x as i64),
-            DmapScalar::Int(x) => Ok(x as i64),
-            DmapScalar::Long(x) => Ok(x),
-            DmapScalar::Uchar(x) => Ok(x as i64),
-            DmapScalar::Ushort(x) => Ok(x as i64),
-            DmapScalar::Uint(x) => Ok(x as i64),
-            DmapScalar::Ulong(x) => Ok(x as i64),
-            DmapScalar::Float(x) => Ok(x as i64),
-            DmapScalar::Double(x) => Ok(x as i64),
-            DmapScalar::String(x) => Err(DmapError::InvalidScalar(format!(
-                "Unable to convert {x} to i64"
-            ))),
-        }
-    }
-}
-impl TryFrom<DmapScalar> for f32 {
-    type Error = DmapError;
-    fn try_from(value: DmapScalar) -> std::result::Result<Self, Self::Error> {
-        match value {
-            DmapScalar::Char(x) => Ok(x as f32),
-            DmapScalar::Short(x) => Ok(x as f32),
-            DmapScalar::Int(x) => Ok(x as f32),
-            DmapScalar::Long(x) => Ok(x as f32),
-            DmapScalar::Uchar(x) => Ok(x as f32),
-            DmapScalar::Ushort(x) => Ok(x as f32),
-            DmapScalar::Uint(x) => Ok(x as f32),
-            DmapScalar::Ulong(x) => Ok(x as f32),
-            DmapScalar::Float(x) => Ok(x),
-            DmapScalar::Double(x) => Ok(x as f32),
-            DmapScalar::String(x) => Err(DmapError::InvalidScalar(format!(
-                "Unable to convert {x} to f32"
-            ))),
-        }
-    }
-}
-impl TryFrom<DmapScalar> for f64 {
-    type Error = DmapError;
-    fn try_from(value: DmapScalar) -> std::result::Result<Self, Self::Error> {
-        match value {
-            DmapScalar::Char(x) => Ok(x as f64),
-            DmapScalar::Short(x) => Ok(x as f64),
-            DmapScalar::Int(x) => Ok(x as f64),
-            DmapScalar::Long(x) => Ok(x as f64),
-            DmapScalar::Uchar(x) => Ok(x as f64),
-            DmapScalar::Ushort(x) => Ok(x as f64),
-            DmapScalar::Uint(x) => Ok(x as f64),
-            DmapScalar::Ulong(x) => Ok(x as f64),
-            DmapScalar::Float(x) => Ok(x as f64),
-            DmapScalar::Double(x) => Ok(x),
-            DmapScalar::String(x) => Err(DmapError::InvalidScalar(format!(
-                "Unable to convert {x} to f64"
-            ))),
+/// Implements conversion of numeric `DmapScalar`s into each numeric type, failing instead of
+/// truncating if the value does not fit.
+macro_rules! scalar_try_from {
+    ($($type:ty: $variant:ident),*) => {$(
+        impl TryFrom<DmapScalar> for $type {
+            type Error = DmapError;
+            fn try_from(value: DmapScalar) -> std::result::Result<Self, Self::Error> {
+                value
+                    .to_number()
+                    .and_then(<$type>::from_number)
+                    .ok_or_else(|| {
+                        DmapError::InvalidScalar(format!(
+                            "Unable to convert {value} to {}",
+                            Type::$variant
+                        ))
+                    })
+            }
         }
-    }
-}
+    )*};
+}
+scalar_try_from!(
+    i8: Char,
+    i16: Short,
+    i32: Int,
+    i64: Long,
+    u8: Uchar,
+    u16: Ushort,
+    u32: Uint,
+    u64: Ulong,
+    f32: Float,
+    f64: Double
+);
 impl TryFrom<DmapScalar> for String {
     type Error = DmapError;
     fn try_from(value: DmapScalar) -> std::result::Result<Self, Self::Error> {
@@ -1494,25 +1857,36 @@ pub fn check_vector_opt(
 ///
 /// The number of bytes read depends on the `Type` of the data, which is represented by a key
 /// stored as an `i32` beginning at the `cursor` position.
-pub(crate) fn parse_scalar(cursor: &mut Cursor<Vec<u8>>) -> Result<(String, DmapField)> {
+pub(crate) fn parse_scalar(cursor: &mut Cursor<&[u8]>) -> Result<(String, DmapField)> {
     let _mode = 6;
+    let field_start = cursor.position();
     let name = read_data::<String>(cursor).map_err(|e| {
         DmapError::InvalidScalar(format!(
             "Invalid scalar name, byte {}: {e}",
             cursor.position()
         ))
     })?;
+    let data = parse_scalar_data(cursor).map_err(|e| field_error(&name, field_start, e))?;
+
+    Ok((name, DmapField::Scalar(data)))
+}
+
+/// Wraps an error parsing the field `name`, which starts at byte `byte` of the cursor, into a
+/// `DmapError::InvalidField`. The record index is filled in by the caller which knows it.
+fn field_error(name: &str, byte: u64, e: DmapError) -> DmapError {
+    DmapError::InvalidField {
+        record: 0,
+        field: name.to_string(),
+        byte,
+        reason: e.to_string(),
+    }
+}
+
+/// Parses the type key and data of a scalar, starting from the `cursor` position.
+fn parse_scalar_data(cursor: &mut Cursor<&[u8]>) -> Result<DmapScalar> {
     let data_type_key = match read_data::<i8>(cursor) {
-        Err(e) => Err(DmapError::InvalidScalar(format!(
-            "Invalid data type for field '{name}', byte {}: {e}",
-            cursor.position() - i8::size() as u64
-        )))?,
-        Ok(x) => Type::from_key(x).map_err(|e| {
-            DmapError::InvalidScalar(format!(
-                "Field {name}: {e}, byte {}",
-                cursor.position() - i8::size() as u64
-            ))
-        })?,
+        Err(e) => Err(DmapError::InvalidScalar(format!("Invalid data type: {e}")))?,
+        Ok(x) => Type::from_key(x)?,
     };
 
     let data: DmapScalar = match data_type_key {
@@ -1529,7 +1903,7 @@ pub(crate) fn parse_scalar(cursor: &mut Cursor<Vec<u8>>) -> Result<(String, Dmap
         Type::String => DmapScalar::String(read_data::<String>(cursor)?),
     };
 
-    Ok((name, DmapField::Scalar(data)))
+    Ok(data)
 }
 
 /// Parses a vector starting from the `cursor` position.
@@ -1538,42 +1912,40 @@ pub(crate) fn parse_scalar(cursor: &mut Cursor<Vec<u8>>) -> Result<(String, Dmap
 /// stored as an `i32` beginning at the `cursor` position, as well as on the dimensions of the
 /// data which follows the key.
 pub(crate) fn parse_vector(
-    cursor: &mut Cursor<Vec<u8>>,
+    cursor: &mut Cursor<&[u8]>,
     record_size: i32,
 ) -> Result<(String, DmapField)> {
     let _mode = 7;
+    let field_start = cursor.position();
     let name = read_data::<String>(cursor).map_err(|e| {
         DmapError::InvalidVector(format!(
             "Invalid vector name, byte {}: {e}",
             cursor.position()
         ))
     })?;
-    let data_type_key = read_data::<i8>(cursor).map_err(|e| {
-        DmapError::InvalidVector(format!(
-            "Invalid data type for field '{name}', byte {}: {e}",
-            cursor.position() - i8::size() as u64
-        ))
-    })?;
+    let vector = parse_vector_data(cursor, &name, record_size)
+        .map_err(|e| field_error(&name, field_start, e))?;
+
+    Ok((name, DmapField::Vector(vector)))
+}
+
+/// Parses the type key, dimensions and data of the vector `name`, starting from the `cursor`
+/// position.
+fn parse_vector_data(cursor: &mut Cursor<&[u8]>, name: &str, record_size: i32) -> Result<DmapVec> {
+    let data_type_key = read_data::<i8>(cursor)
+        .map_err(|e| DmapError::InvalidVector(format!("Invalid data type: {e}")))?;
 
     let data_type = Type::from_key(data_type_key)?;
 
     let vector_dimension = read_data::<i32>(cursor)?;
     if vector_dimension > record_size {
         return Err(DmapError::InvalidVector(format!(
-            "Parsed number of vector dimensions {} for field '{}' at byte {} are larger \
-            than record size {}",
-            vector_dimension,
-            name,
-            cursor.position() - i32::size() as u64,
-            record_size
+            "Parsed number of vector dimensions {vector_dimension} is larger than record size \
+            {record_size}"
         )));
     } else if vector_dimension <= 0 {
         return Err(DmapError::InvalidVector(format!(
-            "Parsed number of vector dimensions {} for field '{}' at byte {} are zero or \
-            negative",
-            vector_dimension,
-            name,
-            cursor.position() - i32::size() as u64,
+            "Parsed number of vector dimensions {vector_dimension} is zero or negative"
         )));
     }
 
@@ -1581,33 +1953,27 @@ pub(crate) fn parse_vector(
     let mut total_elements = 1;
     for _ in 0..vector_dimension {
         let dim = read_data::<i32>(cursor)?;
-        if dim <= 0 && name != "slist" {
+        // `slist` lists the range gates with scatter, so it is legitimately empty for a beam
+        // with no scatter. Every other vector must have at least one element.
+        let min_dim = if name == "slist" { 0 } else { 1 };
+        if dim < min_dim {
             return Err(DmapError::InvalidVector(format!(
-                "Vector dimension {} at byte {} is zero or negative for field '{}'",
-                dim,
-                cursor.position() - i32::size() as u64,
-                name
+                "Vector dimension {dim} is {}",
+                if dim < 0 { "negative" } else { "zero" }
             )));
         } else if dim > record_size {
             return Err(DmapError::InvalidVector(format!(
-                "Vector dimension {} at byte {} for field '{}' exceeds record size {} ",
-                dim,
-                cursor.position() - i32::size() as u64,
-                name,
-                record_size,
+                "Vector dimension {dim} exceeds record size {record_size}"
             )));
         }
-        dimensions.push(dim as u32 as usize);
+        dimensions.push(dim as usize);
         total_elements *= dim;
     }
-    dimensions = dimensions.into_iter().rev().collect(); // reverse the dimensions, stored in column-major order
+    dimensions = dimensions.into_iter().rev().collect(); // stored fastest-varying first, so reverse for row-major
     if total_elements * data_type.size() as i32 > record_size {
         return Err(DmapError::InvalidVector(format!(
-            "Vector size {} starting at byte {} for field '{}' exceeds record size {}",
-            total_elements * data_type.size() as i32,
-            cursor.position() - vector_dimension as u64 * i32::size() as u64,
-            name,
-            record_size
+            "Vector size {} exceeds record size {record_size}",
+            total_elements * data_type.size() as i32
         )));
     }
 
@@ -1680,11 +2046,188 @@ pub(crate) fn parse_vector(
         }
     };
 
-    Ok((name, DmapField::Vector(vector)))
+    Ok(vector)
+}
+
+/// The byte order of the numeric data in a DMAP record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endianness {
+    Little,
+    Big,
+}
+
+impl Endianness {
+    /// Detects the byte order of a record from the bytes of its `code` field, which always holds
+    /// `DMAP_CODE`. Returns `None` if `code` does not match in either byte order.
+    pub fn detect(code: [u8; 4]) -> Option<Endianness> {
+        if i32::from_le_bytes(code) == DMAP_CODE {
+            Some(Endianness::Little)
+        } else if i32::from_be_bytes(code) == DMAP_CODE {
+            Some(Endianness::Big)
+        } else {
+            None
+        }
+    }
+
+    /// Interprets `bytes` as an `i32` in this byte order.
+    fn read_i32(&self, bytes: [u8; 4]) -> i32 {
+        match self {
+            Endianness::Little => i32::from_le_bytes(bytes),
+            Endianness::Big => i32::from_be_bytes(bytes),
+        }
+    }
+}
+
+/// Reads the `code` and `size` fields at the start of a record, returning the byte order of the
+/// record and its size in bytes. Records with an unrecognized `code` are assumed to be
+/// little-endian.
+pub(crate) fn read_record_header(header: &[u8]) -> Result<(Endianness, i32)> {
+    if header.len() < 2 * i32::size() {
+        return Err(DmapError::CorruptStream("Record header is truncated"));
+    }
+    let code: [u8; 4] = header[..4].try_into().unwrap();
+    let endianness = Endianness::detect(code).unwrap_or(Endianness::Little);
+    Ok((
+        endianness,
+        endianness.read_i32(header[4..8].try_into().unwrap()),
+    ))
+}
+
+/// Converts a big-endian record to little-endian in place, by reversing the bytes of every
+/// numeric value. String data and field names are left untouched.
+pub(crate) fn swap_record_endianness(record: &mut [u8]) -> Result<()> {
+    let mut position = 0;
+    next_i32(record, &mut position)?; // code
+    next_i32(record, &mut position)?; // size
+    let num_scalars = next_i32(record, &mut position)?;
+    let num_vectors = next_i32(record, &mut position)?;
+    for _ in 0..num_scalars {
+        skip_string(record, &mut position)?;
+        let data_type = next_type(record, &mut position)?;
+        match data_type {
+            Type::String => skip_string(record, &mut position)?,
+            x => swap_bytes(record, &mut position, x.size())?,
+        }
+    }
+    for _ in 0..num_vectors {
+        skip_string(record, &mut position)?;
+        let data_type = next_type(record, &mut position)?;
+        let num_dims = next_i32(record, &mut position)?;
+        let mut num_elements: usize = 1;
+        for _ in 0..num_dims {
+            let dim = next_i32(record, &mut position)?;
+            if dim < 0 {
+                return Err(DmapError::CorruptStream("Negative vector dimension"));
+            }
+            num_elements = num_elements.saturating_mul(dim as usize);
+        }
+        if num_elements.saturating_mul(data_type.size()) > record.len() - position {
+            return Err(DmapError::CorruptStream("Vector extends out of record"));
+        }
+        for _ in 0..num_elements {
+            swap_bytes(record, &mut position, data_type.size())?;
+        }
+    }
+    Ok(())
+}
+
+/// Reverses the `size` bytes of `record` at `position`, then advances `position` past them.
+fn swap_bytes(record: &mut [u8], position: &mut usize, size: usize) -> Result<()> {
+    if record.len() - *position < size {
+        return Err(DmapError::CorruptStream("Cursor extends out of buffer"));
+    }
+    record[*position..*position + size].reverse();
+    *position += size;
+    Ok(())
+}
+
+/// Swaps the `i32` at `position` in `record` to little-endian and returns it, then advances
+/// `position` past it.
+fn next_i32(record: &mut [u8], position: &mut usize) -> Result<i32> {
+    swap_bytes(record, position, i32::size())?;
+    Ok(i32::from_le_bytes(
+        record[*position - i32::size()..*position]
+            .try_into()
+            .unwrap(),
+    ))
+}
+
+/// Reads the little-endian `i32` at `position` in `record`, then advances `position` past it.
+fn read_i32_at(record: &[u8], position: &mut usize) -> Result<i32> {
+    let bytes = record
+        .get(*position..*position + i32::size())
+        .ok_or(DmapError::CorruptStream("Cursor extends out of buffer"))?;
+    *position += i32::size();
+    Ok(i32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// Advances `position` past the little-endian scalar field starting at `position` in `record`,
+/// without reading its value.
+pub(crate) fn skip_scalar(record: &[u8], position: &mut usize) -> Result<()> {
+    if *position > record.len() {
+        return Err(DmapError::CorruptStream("Cursor extends out of buffer"));
+    }
+    skip_string(record, position)?;
+    match next_type(record, position)? {
+        Type::String => skip_string(record, position),
+        data_type if data_type.size() > record.len() - *position => {
+            Err(DmapError::CorruptStream("Scalar extends out of record"))
+        }
+        data_type => {
+            *position += data_type.size();
+            Ok(())
+        }
+    }
+}
+
+/// Advances `position` past the little-endian vector field starting at `position` in `record`,
+/// without reading its data. This only checks that the vector fits within `record`; the vector
+/// must still be parsed with `parse_vector` to validate it.
+pub(crate) fn skip_vector(record: &[u8], position: &mut usize) -> Result<()> {
+    if *position > record.len() {
+        return Err(DmapError::CorruptStream("Cursor extends out of buffer"));
+    }
+    skip_string(record, position)?;
+    let data_type = next_type(record, position)?;
+    let num_dims = read_i32_at(record, position)?;
+    let mut num_elements: usize = 1;
+    for _ in 0..num_dims {
+        let dim = read_i32_at(record, position)?;
+        if dim < 0 {
+            return Err(DmapError::CorruptStream("Negative vector dimension"));
+        }
+        num_elements = num_elements.saturating_mul(dim as usize);
+    }
+    let num_bytes = num_elements.saturating_mul(data_type.size());
+    if num_bytes > record.len() - *position {
+        return Err(DmapError::CorruptStream("Vector extends out of record"));
+    }
+    *position += num_bytes;
+    Ok(())
+}
+
+/// Advances `position` past the null-terminated string at `position` in `record`.
+fn skip_string(record: &[u8], position: &mut usize) -> Result<()> {
+    match record[*position..].iter().position(|&x| x == 0) {
+        Some(len) => {
+            *position += len + 1;
+            Ok(())
+        }
+        None => Err(DmapError::CorruptStream("String is improperly terminated")),
+    }
+}
+
+/// Reads the `Type` key at `position` in `record`, then advances `position` past it.
+fn next_type(record: &[u8], position: &mut usize) -> Result<Type> {
+    let key = *record
+        .get(*position)
+        .ok_or(DmapError::CorruptStream("Cursor extends out of buffer"))?;
+    *position += 1;
+    Type::from_key(key as i8)
 }
 
 /// Read the raw data (excluding metadata) for a DMAP vector of type `T` from `cursor`.
-fn read_vector<T: DmapType>(cursor: &mut Cursor<Vec<u8>>, num_elements: i32) -> Result<Vec<T>> {
+fn read_vector<T: DmapType>(cursor: &mut Cursor<&[u8]>, num_elements: i32) -> Result<Vec<T>> {
     let mut data: Vec<T> = vec![];
     for _ in 0..num_elements {
         data.push(read_data::<T>(cursor)?);
@@ -1693,9 +2236,9 @@ fn read_vector<T: DmapType>(cursor: &mut Cursor<Vec<u8>>, num_elements: i32) ->
 }
 
 /// Reads a singular value of type `T` starting from the `cursor` position.
-pub(crate) fn read_data<T: DmapType>(cursor: &mut Cursor<Vec<u8>>) -> Result<T> {
+pub(crate) fn read_data<T: DmapType>(cursor: &mut Cursor<&[u8]>) -> Result<T> {
     let position = cursor.position() as usize;
-    let stream = cursor.get_mut();
+    let stream: &[u8] = cursor.get_ref();
 
     if position > stream.len() {
         return Err(DmapError::CorruptStream("Cursor extends out of buffer"));
@@ -1709,14 +2252,10 @@ pub(crate) fn read_data<T: DmapType>(cursor: &mut Cursor<Vec<u8>>) -> Result<T>
     let data_size = match T::size() {
         0 => {
             // String type
-            let mut byte_counter = 0;
-            while stream[position + byte_counter] != 0 {
-                byte_counter += 1;
-                if position + byte_counter >= stream.len() {
-                    return Err(DmapError::CorruptStream("String is improperly terminated"));
-                }
+            match stream[position..].iter().position(|&x| x == 0) {
+                Some(byte_counter) => byte_counter + 1,
+                None => return Err(DmapError::CorruptStream("String is improperly terminated")),
             }
-            byte_counter + 1
         }
         x => x,
     };
@@ -0,0 +1,173 @@
+"""Tests of the Python bindings.
+
+Build the module into the current environment with `maturin develop`, then run
+`pytest tests/test_python.py` from the root of the repository.
+"""
+
+import bz2
+import os
+import time
+from concurrent.futures import ThreadPoolExecutor
+from pathlib import Path
+
+import dmap
+import numpy as np
+import pytest
+
+TEST_FILES = Path(__file__).parent / "test_files"
+
+
+def assert_records_equal(left, right):
+    assert len(left) == len(right)
+    for a, b in zip(left, right):
+        assert list(a) == list(b)
+        for name in a:
+            assert np.array_equal(a[name], b[name]), name
+
+
+@pytest.mark.skipif((os.cpu_count() or 1) < 2, reason="needs two CPUs to run reads in parallel")
+def test_concurrent_reads_overlap():
+    # Reads release the GIL while decompressing and parsing, so reads on two threads overlap
+    recs = dmap.read_rawacf(TEST_FILES / "test.rawacf")
+    path = TEST_FILES / "concurrent_reads.rawacf.bz2"
+    dmap.write_rawacf(recs * 100, path, append=False)
+    try:
+        start = time.perf_counter()
+        for _ in range(2):
+            dmap.read_rawacf(path)
+        sequential = time.perf_counter() - start
+
+        with ThreadPoolExecutor(max_workers=2) as pool:
+            start = time.perf_counter()
+            results = list(pool.map(dmap.read_rawacf, [path] * 2))
+            concurrent = time.perf_counter() - start
+    finally:
+        path.unlink()
+    assert all(len(result) == 100 * len(recs) for result in results)
+    # Holding the GIL would make the concurrent reads take as long as the sequential ones
+    assert concurrent < 0.8 * sequential, (concurrent, sequential)
+
+
+def test_read_typed_scalars():
+    path = TEST_FILES / "test.rawacf"
+    rec = dmap.read_rawacf(path, typed=True)[0]
+    expected = {
+        "radar.revision.major": np.int8,
+        "stid": np.int16,
+        "time.us": np.int32,
+        "noise.search": np.float32,
+    }
+    for name, dtype in expected.items():
+        assert isinstance(rec[name], np.ndarray) and rec[name].ndim == 0, name
+        assert rec[name].dtype == dtype, name
+    assert isinstance(rec["origin.time"], str)
+
+    # Without typed, scalars are plain Python numbers with the same values
+    untyped = dmap.read_rawacf(path)[0]
+    assert type(untyped["stid"]) is int
+    assert untyped["stid"] == rec["stid"].item()
+
+
+def test_dtype_round_trip():
+    dtypes = {
+        "CHAR": np.int8,
+        "SHORT": np.int16,
+        "INT": np.int32,
+        "LONG": np.int64,
+        "UCHAR": np.uint8,
+        "USHORT": np.uint16,
+        "UINT": np.uint32,
+        "ULONG": np.uint64,
+        "FLOAT": np.float32,
+        "DOUBLE": np.float64,
+    }
+    rec = {"name": "round trip"}
+    for type_name, dtype in dtypes.items():
+        # The extreme values of each integer type check that no bits are lost
+        value = np.iinfo(dtype).max if np.issubdtype(dtype, np.integer) else 1.5
+        rec[f"scalar.{type_name}"] = dtype(value)
+        rec[f"vector.{type_name}"] = np.arange(6, dtype=dtype).reshape(2, 3)
+    types = dmap.field_types(rec)
+    assert types["name"] == "STRING"
+    for type_name in dtypes:
+        assert types[f"scalar.{type_name}"] == type_name
+        assert types[f"vector.{type_name}"] == type_name
+
+    tempfile = TEST_FILES / "dtype_round_trip.dmap"
+    dmap.write_dmap([rec], tempfile, append=False)
+    try:
+        (read,) = dmap.read_dmap(tempfile, typed=True)
+    finally:
+        tempfile.unlink()
+    assert list(read) == list(rec)
+    assert read["name"] == rec["name"]
+    for type_name, dtype in dtypes.items():
+        for name in (f"scalar.{type_name}", f"vector.{type_name}"):
+            assert read[name].dtype == dtype, name
+            assert read[name].shape == np.shape(rec[name]), name
+            assert np.array_equal(read[name], rec[name]), name
+
+
+def test_read_path_like_and_file_objects():
+    path = TEST_FILES / "test.fitacf"
+    expected = dmap.read_fitacf(str(path))
+    assert len(expected) > 1
+    assert_records_equal(dmap.read_fitacf(path), expected)
+    with open(path, "rb") as f:
+        assert_records_equal(dmap.read_fitacf(f), expected)
+    # Compressed data must be decompressed by the file object
+    with bz2.open(TEST_FILES / "test.fitacf.bz2") as f:
+        assert_records_equal(dmap.read_fitacf(f), expected)
+
+    assert_records_equal(dmap.read_fitacf(path, max_records=1), expected[:1])
+    with open(path, "rb") as f:
+        assert_records_equal(dmap.read_fitacf(f, max_records=1), expected[:1])
+    with bz2.open(TEST_FILES / "test.fitacf.bz2") as f:
+        assert_records_equal(dmap.read_fitacf(f, max_records=len(expected) + 1), expected)
+
+
+def test_write_append_and_exist_ok():
+    recs = dmap.read_fitacf(TEST_FILES / "test.fitacf")
+    tempfile = TEST_FILES / "write_modes.fitacf"
+    tempfile.unlink(missing_ok=True)
+    try:
+        dmap.write(recs, tempfile, "fitacf", exist_ok=False)
+        with pytest.raises(IOError):
+            dmap.write(recs, tempfile, "fitacf", exist_ok=False)
+        assert_records_equal(dmap.read_fitacf(tempfile), recs)
+
+        # Records are appended by default
+        dmap.write(recs, tempfile, "fitacf")
+        assert_records_equal(dmap.read_fitacf(tempfile), recs + recs)
+
+        dmap.write(recs, tempfile, "fitacf", append=False)
+        assert_records_equal(dmap.read_fitacf(tempfile), recs)
+    finally:
+        tempfile.unlink(missing_ok=True)
+
+
+def test_write_names_bad_record():
+    recs = dmap.read_fitacf(TEST_FILES / "test.fitacf")
+    recs[1]["stid"] = "not a station id"
+    tempfile = TEST_FILES / "bad_record.fitacf"
+    with pytest.raises(ValueError, match=r"Records with errors: \[1\]"):
+        dmap.write(recs, tempfile, "fitacf", exist_ok=False)
+    assert not tempfile.exists()
+
+
+def test_bad_records_error_attributes():
+    recs = dmap.read_rawacf(TEST_FILES / "test.rawacf")
+    recs[1]["stid"] = "not a station id"
+    tempfile = TEST_FILES / "bad_records.rawacf"
+    with pytest.raises(dmap.BadRecordsError) as exc:
+        dmap.write_rawacf(recs, tempfile, exist_ok=False)
+    assert isinstance(exc.value, ValueError)
+    assert exc.value.records == [1]
+    assert exc.value.message == "Unable to convert STRING not a station id to SHORT"
+    assert not tempfile.exists()
+
+
+def test_record_timestamp():
+    rec = dmap.read_fitacf(TEST_FILES / "test.fitacf")[0]
+    assert dmap.record_timestamp(rec) == dmap.record_time(rec)
+    assert dmap.record_timestamp(rec).startswith("2022-11-07T18:01:00")
@@ -1,14 +1,44 @@
-use dmap::formats::dmap::{GenericRecord, Record};
-use dmap::formats::fitacf::FitacfRecord;
+use bzip2::read::MultiBzDecoder;
+use dmap::diff::{diff_files, FieldDiff};
+use dmap::error::DmapError;
+use dmap::filter::{
+    dedup_records, filter_by_time, parse_origin_time, record_end_time, record_time,
+    record_timestamp,
+};
+use dmap::formats::dmap::{
+    Compression, DmapWriter, GenericRecord, ReadOptions, Record, RecordIter, ValidationMode,
+    WriteMode, WriteOptions,
+};
+use dmap::formats::fitacf::{strip_xcf, FitacfRecord};
 use dmap::formats::grid::GridRecord;
 use dmap::formats::iqdat::IqdatRecord;
 use dmap::formats::map::MapRecord;
-use dmap::formats::rawacf::RawacfRecord;
+use dmap::formats::rawacf::{acfd_complex, rawacf_fields, to_complex, xcfd_complex, RawacfRecord};
 use dmap::formats::snd::SndRecord;
+use dmap::formats::FileFormat;
+use dmap::index::{manifest, DmapIndex};
+use dmap::types::{
+    channel_to_letter, extract_scalar, extract_vector, field_types, letter_to_channel,
+    normalize_channel, DmapField, DmapScalar, DmapType, DmapVec, Endianness, Type,
+};
+use dmap::{
+    concat_files, count_records, file_info, fitacf_to_csv, parse_one, present_optional_fields,
+    read_all, read_any, read_dmap, read_file_unchecked, read_file_with, read_files,
+    read_fitacf_beams, read_fitacf_channel, record_hashes, records_to_bytes, rewrite,
+    schema_report, sniff_format, split_by_interval, time_bounds, try_write_dmap, try_write_fitacf,
+    try_write_map, try_write_rawacf, uncompressed_size, write_dmap, write_fitacf, write_grid,
+    write_iqdat, write_map, write_rawacf, write_records_multistream, write_records_opts,
+    write_records_to, write_records_with, write_snd, TypeMismatch,
+};
+use indexmap::IndexMap;
 use itertools::izip;
-use std::fs::remove_file;
+use numpy::ndarray::{array, ArrayD, Ix2, IxDyn, ShapeBuilder};
+use std::fs::{copy, create_dir_all, read, read_to_string, remove_dir_all, remove_file, File};
+use std::io::{Cursor, Read, Write};
 use std::path::PathBuf;
-use dmap::{write_iqdat, write_rawacf, write_fitacf, write_grid, write_map, write_snd, write_dmap};
+use std::sync::Mutex;
+use time::macros::datetime;
+use time::OffsetDateTime;
 
 #[test]
 fn read_write_generic() {
@@ -27,14 +57,14 @@ fn read_write_generic() {
     }
 
     // Write to a regular file, and then read back in and compare contents
-    _ = write_dmap(data.clone(), &tempfile).expect("Unable to write tmp.rawacf");
+    write_dmap(data.clone(), &tempfile).expect("Unable to write tmp.rawacf");
     let new_recs = GenericRecord::read_file(&tempfile).expect("Cannot read tmp.rawacf");
     for (ref read_rec, ref written_rec) in izip!(data.iter(), new_recs.iter()) {
         assert_eq!(read_rec, written_rec)
     }
 
     // Write to a zipped file, and then read back in and compare contents
-    _ = write_dmap(data.clone(), &temp_bz2).expect("Unable to write tmp.rawacf.bz2");
+    write_dmap(data.clone(), &temp_bz2).expect("Unable to write tmp.rawacf.bz2");
     let new_recs = GenericRecord::read_file(&temp_bz2).expect("Cannot read tmp.rawacf.bz2");
     for (ref read_rec, ref written_rec) in izip!(data.iter(), new_recs.iter()) {
         assert_eq!(read_rec, written_rec)
@@ -62,14 +92,14 @@ fn read_write_iqdat() {
     }
 
     // Write to a regular file, and then read back in and compare contents
-    _ = write_iqdat(data.clone(), &tempfile).expect("Unable to write tmp.iqdat");
+    write_iqdat(data.clone(), &tempfile).expect("Unable to write tmp.iqdat");
     let new_recs = IqdatRecord::read_file(&tempfile).expect("Cannot read tmp.iqdat");
     for (ref read_rec, ref written_rec) in izip!(data.iter(), new_recs.iter()) {
         assert_eq!(read_rec, written_rec)
     }
 
     // Write to a zipped file, and then read back in and compare contents
-    _ = write_iqdat(data.clone(), &temp_bz2).expect("Unable to write tmp.iqdat.bz2");
+    write_iqdat(data.clone(), &temp_bz2).expect("Unable to write tmp.iqdat.bz2");
     let new_recs = IqdatRecord::read_file(&temp_bz2).expect("Cannot read tmp.iqdat.bz2");
     for (ref read_rec, ref written_rec) in izip!(data.iter(), new_recs.iter()) {
         assert_eq!(read_rec, written_rec)
@@ -97,14 +127,14 @@ fn read_write_rawacf() {
     }
 
     // Write to a regular file, and then read back in and compare contents
-    _ = write_rawacf(data.clone(), &tempfile).expect("Unable to write tmp.rawacf");
+    write_rawacf(data.clone(), &tempfile).expect("Unable to write tmp.rawacf");
     let new_recs = RawacfRecord::read_file(&tempfile).expect("Cannot read tmp.rawacf");
     for (ref read_rec, ref written_rec) in izip!(data.iter(), new_recs.iter()) {
         assert_eq!(read_rec, written_rec)
     }
 
     // Write to a zipped file, and then read back in and compare contents
-    _ = write_rawacf(data.clone(), &temp_bz2).expect("Unable to write tmp.rawacf.bz2");
+    write_rawacf(data.clone(), &temp_bz2).expect("Unable to write tmp.rawacf.bz2");
     let new_recs = RawacfRecord::read_file(&temp_bz2).expect("Cannot read tmp.rawacf.bz2");
     for (ref read_rec, ref written_rec) in izip!(data.iter(), new_recs.iter()) {
         assert_eq!(read_rec, written_rec)
@@ -132,14 +162,14 @@ fn read_write_fitacf() {
     }
 
     // Write to a regular file, and then read back in and compare contents
-    _ = write_fitacf(data.clone(), &tempfile).expect("Unable to write tmp.fitacf");
+    write_fitacf(data.clone(), &tempfile).expect("Unable to write tmp.fitacf");
     let new_recs = FitacfRecord::read_file(&tempfile).expect("Cannot read tmp.fitacf");
     for (ref read_rec, ref written_rec) in izip!(data.iter(), new_recs.iter()) {
         assert_eq!(read_rec, written_rec)
     }
 
     // Write to a zipped file, and then read back in and compare contents
-    _ = write_fitacf(data.clone(), &temp_bz2).expect("Unable to write tmp.fitacf.bz2");
+    write_fitacf(data.clone(), &temp_bz2).expect("Unable to write tmp.fitacf.bz2");
     let new_recs = FitacfRecord::read_file(&temp_bz2).expect("Cannot read tmp.fitacf.bz2");
     for (ref read_rec, ref written_rec) in izip!(data.iter(), new_recs.iter()) {
         assert_eq!(read_rec, written_rec)
@@ -167,14 +197,14 @@ fn read_write_grid() {
     }
 
     // Write to a regular file, and then read back in and compare contents
-    _ = write_grid(data.clone(), &tempfile).expect("Unable to write tmp.grid");
+    write_grid(data.clone(), &tempfile).expect("Unable to write tmp.grid");
     let new_recs = GridRecord::read_file(&tempfile).expect("Cannot read tmp.grid");
     for (ref read_rec, ref written_rec) in izip!(data.iter(), new_recs.iter()) {
         assert_eq!(read_rec, written_rec)
     }
 
     // Write to a zipped file, and then read back in and compare contents
-    _ = write_grid(data.clone(), &temp_bz2).expect("Unable to write tmp.grid.bz2");
+    write_grid(data.clone(), &temp_bz2).expect("Unable to write tmp.grid.bz2");
     let new_recs = GridRecord::read_file(&temp_bz2).expect("Cannot read tmp.grid.bz2");
     for (ref read_rec, ref written_rec) in izip!(data.iter(), new_recs.iter()) {
         assert_eq!(read_rec, written_rec)
@@ -202,14 +232,14 @@ fn read_write_map() {
     }
 
     // Write to a regular file, and then read back in and compare contents
-    _ = write_map(data.clone(), &tempfile).expect("Unable to write tmp.map");
+    write_map(data.clone(), &tempfile).expect("Unable to write tmp.map");
     let new_recs = MapRecord::read_file(&tempfile).expect("Cannot read tmp.map");
     for (ref read_rec, ref written_rec) in izip!(data.iter(), new_recs.iter()) {
         assert_eq!(read_rec, written_rec)
     }
 
     // Write to a zipped file, and then read back in and compare contents
-    _ = write_map(data.clone(), &temp_bz2).expect("Unable to write tmp.map.bz2");
+    write_map(data.clone(), &temp_bz2).expect("Unable to write tmp.map.bz2");
     let new_recs = MapRecord::read_file(&temp_bz2).expect("Cannot read tmp.map.bz2");
     for (ref read_rec, ref written_rec) in izip!(data.iter(), new_recs.iter()) {
         assert_eq!(read_rec, written_rec)
@@ -237,14 +267,14 @@ fn read_write_snd() {
     }
 
     // Write to a regular file, and then read back in and compare contents
-    _ = write_snd(data.clone(), &tempfile).expect("Unable to write tmp.snd");
+    write_snd(data.clone(), &tempfile).expect("Unable to write tmp.snd");
     let new_recs = SndRecord::read_file(&tempfile).expect("Cannot read tmp.snd");
     for (ref read_rec, ref written_rec) in izip!(data.iter(), new_recs.iter()) {
         assert_eq!(read_rec, written_rec)
     }
 
     // Write to a zipped file, and then read back in and compare contents
-    _ = write_snd(data.clone(), &temp_bz2).expect("Unable to write tmp.snd.bz2");
+    write_snd(data.clone(), &temp_bz2).expect("Unable to write tmp.snd.bz2");
     let new_recs = SndRecord::read_file(&temp_bz2).expect("Cannot read tmp.snd.bz2");
     for (ref read_rec, ref written_rec) in izip!(data.iter(), new_recs.iter()) {
         assert_eq!(read_rec, written_rec)
@@ -254,3 +284,3172 @@ fn read_write_snd() {
     remove_file(&tempfile).expect("Unable to delete tmp.snd");
     remove_file(&temp_bz2).expect("Unable to delete tmp.snd.bz2");
 }
+
+#[test]
+fn rst_snd_round_trip() {
+    // test.snd was produced by RST with xcf set, so it has the optional elevation fields
+    let path = PathBuf::from("tests/test_files/test.snd");
+    let tempfile = PathBuf::from("tests/test_files/rst_round_trip.snd");
+    let data = SndRecord::read_file(&path).expect("Unable to read test.snd");
+    assert!(!data.is_empty());
+    for rec in data.iter() {
+        assert_eq!(rec.get(&"xcf".to_string()), Some(&DmapField::from(1_i16)));
+        for key in ["x_qflg", "phi0", "phi0_e"] {
+            assert!(rec.get(&key.to_string()).is_some(), "Missing {key}");
+        }
+    }
+
+    // Rewriting the records gives back the file as written by RST
+    let _ = remove_file(&tempfile);
+    write_snd(data, &tempfile).expect("Unable to write rst_round_trip.snd");
+    let bytes = read(&tempfile).expect("Unable to read rst_round_trip.snd");
+    remove_file(&tempfile).expect("Unable to delete rst_round_trip.snd");
+    assert_eq!(bytes, read(&path).expect("Unable to read test.snd"));
+}
+
+#[test]
+fn cast_vector_types() {
+    let shorts = DmapVec::Short(array![[1_i16, -2, 3], [4, 5, -6]].into_dyn());
+
+    // Widening keeps the values and the shape
+    let ints = shorts
+        .cast_as(&Type::Int)
+        .expect("Unable to cast SHORT to INT");
+    assert_eq!(
+        ints,
+        DmapVec::Int(array![[1_i32, -2, 3], [4, 5, -6]].into_dyn())
+    );
+    let doubles = shorts
+        .cast_as(&Type::Double)
+        .expect("Unable to cast SHORT to DOUBLE");
+    assert_eq!(
+        doubles,
+        DmapVec::Double(array![[1.0, -2.0, 3.0], [4.0, 5.0, -6.0]].into_dyn())
+    );
+
+    // Narrowing is allowed only when every element fits
+    assert_eq!(
+        ints.cast_as(&Type::Short)
+            .expect("Unable to cast INT to SHORT"),
+        shorts
+    );
+    let big_ints = DmapVec::Int(array![1, 70000].into_dyn());
+    assert!(big_ints.cast_as(&Type::Short).is_err());
+    assert!(shorts.cast_as(&Type::Uchar).is_err());
+    let fractional = DmapVec::Float(array![1.0_f32, 2.5].into_dyn());
+    assert!(fractional.cast_as(&Type::Int).is_err());
+    assert!(shorts.cast_as(&Type::String).is_err());
+
+    // cast_to takes the target type by value
+    assert_eq!(
+        shorts
+            .cast_to(Type::Int)
+            .expect("Unable to cast SHORT to INT"),
+        ints
+    );
+    assert!(big_ints.cast_to(Type::Short).is_err());
+
+    // Elements convert in the same way as scalars: integers must be exact, while doubles are
+    // rounded to the nearest float unless they overflow
+    let doubles = DmapVec::Double(array![0.1, -2.5].into_dyn());
+    assert_eq!(
+        doubles
+            .cast_as(&Type::Float)
+            .expect("Unable to cast DOUBLE to FLOAT"),
+        DmapVec::Float(array![0.1_f32, -2.5].into_dyn())
+    );
+    let cases = [
+        (
+            DmapVec::Int(array![1, 16777217].into_dyn()),
+            DmapScalar::Int(16777217),
+            Type::Float,
+        ),
+        (
+            DmapVec::Double(array![1.0, 1e300].into_dyn()),
+            DmapScalar::Double(1e300),
+            Type::Float,
+        ),
+        (
+            DmapVec::Long(array![1, i64::MAX].into_dyn()),
+            DmapScalar::Long(i64::MAX),
+            Type::Double,
+        ),
+    ];
+    for (vector, scalar, target) in cases.iter() {
+        assert!(vector.cast_as(target).is_err(), "{scalar} to {target}");
+        assert!(scalar.cast_as(target).is_err(), "{scalar} to {target}");
+    }
+}
+
+#[test]
+fn cast_scalar_types() {
+    let numeric_types = [
+        Type::Char,
+        Type::Short,
+        Type::Int,
+        Type::Long,
+        Type::Uchar,
+        Type::Ushort,
+        Type::Uint,
+        Type::Ulong,
+        Type::Float,
+        Type::Double,
+    ];
+    let fives = [
+        DmapScalar::Char(5),
+        DmapScalar::Short(5),
+        DmapScalar::Int(5),
+        DmapScalar::Long(5),
+        DmapScalar::Uchar(5),
+        DmapScalar::Ushort(5),
+        DmapScalar::Uint(5),
+        DmapScalar::Ulong(5),
+        DmapScalar::Float(5.0),
+        DmapScalar::Double(5.0),
+    ];
+
+    // Every numeric type converts to every other when the value fits
+    for source in fives.iter() {
+        for (target, expected) in numeric_types.iter().zip(fives.iter()) {
+            assert_eq!(
+                source.cast_as(target).unwrap(),
+                *expected,
+                "{source} to {target}"
+            );
+        }
+        assert!(source.cast_as(&Type::String).is_err(), "{source} to STRING");
+    }
+
+    // Strings only convert to strings
+    let string = DmapScalar::String("5".to_string());
+    assert_eq!(string.cast_as(&Type::String).unwrap(), string);
+    for target in numeric_types.iter() {
+        assert!(string.cast_as(target).is_err(), "STRING to {target}");
+    }
+
+    // Narrowing fails rather than truncating
+    let out_of_range = [
+        (DmapScalar::Int(70000), Type::Short),
+        (DmapScalar::Short(300), Type::Char),
+        (DmapScalar::Short(-1), Type::Uchar),
+        (DmapScalar::Char(-1), Type::Ulong),
+        (DmapScalar::Long(-1), Type::Uint),
+        (DmapScalar::Uchar(255), Type::Char),
+        (DmapScalar::Ushort(65535), Type::Short),
+        (DmapScalar::Uint(u32::MAX), Type::Int),
+        (DmapScalar::Ulong(u64::MAX), Type::Long),
+        (DmapScalar::Long(i64::MAX), Type::Double),
+        (DmapScalar::Int(16777217), Type::Float),
+        (DmapScalar::Float(2.5), Type::Int),
+        (DmapScalar::Float(f32::NAN), Type::Short),
+        (DmapScalar::Double(1e10), Type::Int),
+        (DmapScalar::Double(1e300), Type::Float),
+    ];
+    for (source, target) in out_of_range.iter() {
+        assert!(source.cast_as(target).is_err(), "{source} to {target}");
+    }
+
+    // Widening keeps the value
+    assert_eq!(
+        DmapScalar::Char(-128).cast_as(&Type::Long).unwrap(),
+        DmapScalar::Long(-128)
+    );
+    assert_eq!(
+        DmapScalar::Ulong(u64::MAX).cast_as(&Type::Ulong).unwrap(),
+        DmapScalar::Ulong(u64::MAX)
+    );
+    assert_eq!(
+        DmapScalar::Float(0.5).cast_as(&Type::Double).unwrap(),
+        DmapScalar::Double(0.5)
+    );
+    assert_eq!(
+        DmapScalar::Double(0.1).cast_as(&Type::Float).unwrap(),
+        DmapScalar::Float(0.1)
+    );
+}
+
+#[test]
+fn coerce_vector_types() {
+    let path = PathBuf::from("tests/test_files/test.fitacf");
+    let data = FitacfRecord::read_file(&path).expect("Unable to read test.fitacf");
+    let (rec, slist) = data
+        .iter()
+        .find_map(|rec| match rec.get(&"slist".to_string()) {
+            Some(DmapField::Vector(x)) => Some((rec, x.clone())),
+            _ => None,
+        })
+        .expect("No record with slist");
+
+    // A user-supplied slist of Ints is accepted as Shorts
+    let mut fields = rec.data().clone();
+    let ints = slist
+        .cast_as(&Type::Int)
+        .expect("Unable to cast slist to INT");
+    fields.insert("slist".to_string(), DmapField::Vector(ints));
+    let coerced = FitacfRecord::try_from(&mut fields).expect("Unable to coerce slist");
+    assert_eq!(&coerced, rec);
+
+    // but not if a value would overflow
+    let mut fields = rec.data().clone();
+    let mut big = vec![70000; slist.shape()[0]];
+    big[0] = 1;
+    let big = DmapField::from(ArrayD::from_shape_vec(slist.shape(), big).unwrap());
+    fields.insert("slist".to_string(), big);
+    assert!(FitacfRecord::try_from(&mut fields).is_err());
+}
+
+#[test]
+fn reject_inconsistent_dimensions() {
+    let path = PathBuf::from("tests/test_files/test.fitacf");
+    let data = FitacfRecord::read_file(&path).expect("Unable to read test.fitacf");
+    let rec = data
+        .iter()
+        .find(|rec| matches!(rec.get(&"v".to_string()), Some(DmapField::Vector(x)) if x.shape()[0] > 1))
+        .expect("No record with v");
+
+    // Drop the last element of v_e, so that it is shorter than v
+    let mut fields = rec.data().clone();
+    let v_e = match fields.get("v_e") {
+        Some(DmapField::Vector(DmapVec::Float(x))) => x.iter().cloned().collect::<Vec<f32>>(),
+        _ => panic!("v_e is not a FLOAT vector"),
+    };
+    let short = ArrayD::from_shape_vec(vec![v_e.len() - 1], v_e[1..].to_vec()).unwrap();
+    fields.insert("v_e".to_string(), DmapField::from(short));
+    match FitacfRecord::try_from(&mut fields) {
+        Err(DmapError::InvalidRecord(msg)) => {
+            assert!(msg.contains("v_e"), "{msg}")
+        }
+        x => panic!("Expected inconsistent dimensions, got {x:?}"),
+    }
+
+    // RAWACF cross-correlations must match the auto-correlations
+    let path = PathBuf::from("tests/test_files/test.rawacf");
+    let data = RawacfRecord::read_file(&path).expect("Unable to read test.rawacf");
+    let mut fields = data[0].data().clone();
+    let acfd = match fields.get("acfd") {
+        Some(DmapField::Vector(x)) => x.clone(),
+        _ => panic!("acfd is not a vector"),
+    };
+    assert!(RawacfRecord::try_from(&mut fields.clone()).is_ok());
+    let short = ArrayD::from_elem(vec![acfd.shape()[0] - 1, acfd.shape()[1], 2], 0.0_f32);
+    fields.insert("xcfd".to_string(), DmapField::from(short));
+    assert!(RawacfRecord::try_from(&mut fields).is_err());
+}
+
+#[test]
+fn reject_truncated_range_vector() {
+    let path = PathBuf::from("tests/test_files/test.fitacf");
+    let data = FitacfRecord::read_file(&path).expect("Unable to read test.fitacf");
+    let rec = data
+        .iter()
+        .find(|rec| rec.slist().map_or(false, |x| x.len() > 1))
+        .expect("No record with slist");
+
+    let mut fields = rec.data().clone();
+    let p_l: Vec<f32> = rec.p_l().expect("No p_l").iter().cloned().collect();
+    let short = ArrayD::from_shape_vec(vec![p_l.len() - 1], p_l[1..].to_vec()).unwrap();
+    fields.insert("p_l".to_string(), DmapField::from(short));
+    match FitacfRecord::new(&mut fields) {
+        Err(DmapError::InvalidRecord(msg)) => {
+            assert!(msg.starts_with("Field p_l has dimensions"), "{msg}")
+        }
+        x => panic!("Expected mismatched p_l, got {x:?}"),
+    }
+}
+
+#[test]
+fn empty_slist_round_trip() {
+    let path = PathBuf::from("tests/test_files/test.fitacf");
+    let data = FitacfRecord::read_file(&path).expect("Unable to read test.fitacf");
+    let rec = data
+        .iter()
+        .find(|rec| rec.slist().is_ok())
+        .expect("No record with slist");
+    let num_gates = rec.slist().unwrap().shape().to_vec();
+
+    // A beam with no scatter has an empty slist and no range-indexed vectors
+    let mut fields = rec.data().clone();
+    fields.retain(|_, x| !matches!(x, DmapField::Vector(v) if v.shape() == num_gates.as_slice()));
+    fields.insert(
+        "slist".to_string(),
+        DmapField::Vector(DmapVec::Short(ArrayD::zeros(vec![0]))),
+    );
+    let mut with_range_vector = fields.clone();
+    with_range_vector.insert(
+        "p_l".to_string(),
+        DmapField::Vector(DmapVec::Float(ArrayD::zeros(vec![0]))),
+    );
+    assert!(FitacfRecord::new(&mut with_range_vector).is_err());
+
+    let empty = FitacfRecord::new(&mut fields).expect("Unable to create record");
+    assert_eq!(empty.slist().unwrap().len(), 0);
+    assert!(empty.csv_rows().is_empty());
+
+    let bytes = empty.to_bytes().expect("Unable to convert record to bytes");
+    let new_recs = FitacfRecord::read_records(Cursor::new(bytes)).expect("Unable to parse bytes");
+    assert_eq!(new_recs, vec![empty]);
+}
+
+#[test]
+fn read_any_detects_compression() {
+    let path = PathBuf::from("tests/test_files/test.fitacf");
+    let path_bz2 = PathBuf::from("tests/test_files/test.fitacf.bz2");
+    let tempfile = PathBuf::from("tests/test_files/compressed_no_ext.fitacf");
+    copy(&path_bz2, &tempfile).expect("Unable to copy test.fitacf.bz2");
+
+    let data = read_dmap(path.clone()).expect("Unable to read test.fitacf");
+    let plain_recs = read_any(path).expect("Unable to read test.fitacf");
+    let zipped_recs = read_any(tempfile.clone()).expect("Unable to read compressed_no_ext.fitacf");
+    assert_eq!(data, plain_recs);
+    assert_eq!(data, zipped_recs);
+
+    remove_file(&tempfile).expect("Unable to delete compressed_no_ext.fitacf");
+}
+
+#[test]
+fn read_all_detects_format() {
+    for ext in ["iqdat", "rawacf", "fitacf", "grid", "map", "snd"] {
+        let path = PathBuf::from(format!("tests/test_files/test.{ext}.bz2"));
+        let (format, recs) = read_all(path.clone()).expect("Unable to read file");
+        assert_eq!(format.to_string(), ext);
+        assert!(!recs.is_empty());
+        assert_eq!(recs, read_any(path).expect("Unable to read file"));
+    }
+}
+
+#[test]
+fn field_bytes_round_trip() {
+    // Scalars are the type key followed by the little-endian payload, strings null-terminated
+    assert_eq!(DmapField::from(5_i16).as_bytes(), vec![2, 5, 0]);
+    assert_eq!(
+        DmapField::from("ab".to_string()).as_bytes(),
+        vec![9, b'a', b'b', 0]
+    );
+    // Vectors are the type key, number of dimensions, dimensions in column-major order, then data
+    let vector = DmapField::from(array![[1_i8, 2, 3], [4, 5, 6]].into_dyn());
+    assert_eq!(
+        vector.as_bytes(),
+        vec![1, 2, 0, 0, 0, 3, 0, 0, 0, 2, 0, 0, 0, 1, 2, 3, 4, 5, 6]
+    );
+
+    let mut data: IndexMap<String, DmapField> = IndexMap::new();
+    data.insert("char".to_string(), DmapField::from(-1_i8));
+    data.insert("short".to_string(), DmapField::from(-2_i16));
+    data.insert("int".to_string(), DmapField::from(-3_i32));
+    data.insert("long".to_string(), DmapField::from(-4_i64));
+    data.insert("uchar".to_string(), DmapField::from(1_u8));
+    data.insert("ushort".to_string(), DmapField::from(2_u16));
+    data.insert("uint".to_string(), DmapField::from(3_u32));
+    data.insert("ulong".to_string(), DmapField::from(4_u64));
+    data.insert("float".to_string(), DmapField::from(1.5_f32));
+    data.insert("double".to_string(), DmapField::from(2.5_f64));
+    data.insert(
+        "string".to_string(),
+        DmapField::from("a string".to_string()),
+    );
+    data.insert("empty".to_string(), DmapField::from(String::new()));
+    data.insert(
+        "char_vec".to_string(),
+        DmapField::from(array![-1_i8, 1].into_dyn()),
+    );
+    data.insert(
+        "short_vec".to_string(),
+        DmapField::from(array![-2_i16, 2].into_dyn()),
+    );
+    data.insert(
+        "int_vec".to_string(),
+        DmapField::from(array![-3_i32, 3].into_dyn()),
+    );
+    data.insert(
+        "long_vec".to_string(),
+        DmapField::from(array![-4_i64, 4].into_dyn()),
+    );
+    data.insert(
+        "uchar_vec".to_string(),
+        DmapField::from(array![1_u8, 2].into_dyn()),
+    );
+    data.insert(
+        "ushort_vec".to_string(),
+        DmapField::from(array![2_u16, 3].into_dyn()),
+    );
+    data.insert(
+        "uint_vec".to_string(),
+        DmapField::from(array![3_u32, 4].into_dyn()),
+    );
+    data.insert(
+        "ulong_vec".to_string(),
+        DmapField::from(array![4_u64, 5].into_dyn()),
+    );
+    data.insert(
+        "float_vec".to_string(),
+        DmapField::from(array![[1.5_f32], [-1.5]].into_dyn()),
+    );
+    data.insert(
+        "double_vec".to_string(),
+        DmapField::from(array![[[2.5_f64]]].into_dyn()),
+    );
+    let rec = GenericRecord { data };
+
+    let bytes = rec.to_bytes().expect("Unable to convert record to bytes");
+    let new_recs = GenericRecord::read_records(Cursor::new(bytes)).expect("Unable to parse bytes");
+    assert_eq!(new_recs, vec![rec]);
+}
+
+#[test]
+fn map_kp_round_trip() {
+    let path = PathBuf::from("tests/test_files/test.map");
+    let tempfile = PathBuf::from("tests/test_files/kp.map");
+    let legacy_tempfile = PathBuf::from("tests/test_files/legacy_kp.map");
+    let kp = "IMF.Kp".to_string();
+
+    // IMF.Kp survives a write-then-read
+    let mut data = MapRecord::read_file(&path).expect("Unable to read test.map");
+    data[0].data.insert(kp.clone(), DmapField::from(2.5_f64));
+    write_map(data.clone(), &tempfile).expect("Unable to write kp.map");
+    let new_recs = MapRecord::read_file(&tempfile).expect("Cannot read kp.map");
+    assert_eq!(new_recs[0].get(&kp), Some(&DmapField::from(2.5_f64)));
+    assert_eq!(new_recs, data);
+
+    // The legacy IMT.Kp spelling is renamed to IMF.Kp on read
+    let mut generic_recs = GenericRecord::read_file(&path).expect("Unable to read test.map");
+    generic_recs[0]
+        .data
+        .insert("IMT.Kp".to_string(), DmapField::from(2.5_f64));
+    write_dmap(generic_recs, &legacy_tempfile).expect("Unable to write legacy_kp.map");
+    let new_recs = MapRecord::read_file(&legacy_tempfile).expect("Cannot read legacy_kp.map");
+    assert_eq!(new_recs[0].get(&kp), Some(&DmapField::from(2.5_f64)));
+    assert_eq!(new_recs[0].get(&"IMT.Kp".to_string()), None);
+    let new_recs = read_file_with::<MapRecord>(&legacy_tempfile, &ReadOptions::default())
+        .expect("Cannot read legacy_kp.map");
+    assert_eq!(new_recs[0].get(&kp), Some(&DmapField::from(2.5_f64)));
+
+    // and when a dictionary using it is written as MAP
+    remove_file(&tempfile).expect("Unable to delete kp.map");
+    let mut dicts: Vec<IndexMap<String, DmapField>> = GenericRecord::read_file(&path)
+        .expect("Unable to read test.map")
+        .into_iter()
+        .map(|rec| rec.inner())
+        .collect();
+    dicts[0].insert("IMT.Kp".to_string(), DmapField::from(2.5_f64));
+    try_write_map(dicts, &tempfile).expect("Unable to write kp.map");
+    let new_recs = MapRecord::read_file(&tempfile).expect("Cannot read kp.map");
+    assert_eq!(new_recs[0].get(&kp), Some(&DmapField::from(2.5_f64)));
+    assert_eq!(new_recs[0].get(&"IMT.Kp".to_string()), None);
+
+    remove_file(&tempfile).expect("Unable to delete kp.map");
+    remove_file(&legacy_tempfile).expect("Unable to delete legacy_kp.map");
+}
+
+#[test]
+fn read_from_bytes() {
+    let path = PathBuf::from("tests/test_files/test.rawacf");
+    let data = RawacfRecord::read_file(&path).expect("Unable to read test.rawacf");
+    let bytes = read(&path).expect("Unable to read test.rawacf");
+    let new_recs = RawacfRecord::read_records_from_bytes(bytes).expect("Unable to parse bytes");
+    assert_eq!(data, new_recs);
+}
+
+#[test]
+fn read_with_offsets() {
+    let path = PathBuf::from("tests/test_files/test.fitacf");
+    let path_bz2 = PathBuf::from("tests/test_files/test.fitacf.bz2");
+    let data = FitacfRecord::read_file(&path).expect("Unable to read test.fitacf");
+    let indexed_recs = FitacfRecord::read_file_indexed(&path).expect("Unable to read test.fitacf");
+    let zipped_recs =
+        FitacfRecord::read_file_indexed(&path_bz2).expect("Cannot read test.fitacf.bz2");
+    assert_eq!(indexed_recs, zipped_recs);
+
+    // Each offset is the previous offset plus the size of the previous record
+    let mut expected_offset = 0;
+    for ((offset, rec), orig_rec) in izip!(indexed_recs, data) {
+        assert_eq!(offset, expected_offset);
+        assert_eq!(rec, orig_rec);
+        expected_offset += rec
+            .to_bytes()
+            .expect("Unable to convert record to bytes")
+            .len();
+    }
+}
+
+#[test]
+fn random_access_record() {
+    let path = PathBuf::from("tests/test_files/test.fitacf");
+    let tempfile = PathBuf::from("tests/test_files/ten_records.fitacf");
+
+    // Build a 10-record file where every record has a distinct scan number
+    let data = FitacfRecord::read_file(&path).expect("Unable to read test.fitacf");
+    let mut recs: Vec<FitacfRecord> = vec![];
+    for i in 0..10 {
+        let mut rec = data[i % data.len()].clone();
+        rec.data
+            .insert("scan".to_string(), DmapField::from(i as i16));
+        recs.push(rec);
+    }
+    write_fitacf(recs.clone(), &tempfile).expect("Unable to write ten_records.fitacf");
+
+    let mut file = File::open(&tempfile).expect("Unable to open ten_records.fitacf");
+    let index = DmapIndex::build(&mut file).expect("Unable to index ten_records.fitacf");
+    assert_eq!(index.len(), 10);
+    let all_recs = FitacfRecord::read_file(&tempfile).expect("Unable to read ten_records.fitacf");
+    let rec: FitacfRecord = index
+        .read_record(&mut file, 3)
+        .expect("Unable to read record 3");
+    assert_eq!(rec, all_recs[3]);
+    assert_eq!(rec, recs[3]);
+    assert!(index.read_record::<FitacfRecord>(&mut file, 10).is_err());
+
+    remove_file(&tempfile).expect("Unable to delete ten_records.fitacf");
+}
+
+#[test]
+fn index_truncated_file() {
+    let path = PathBuf::from("tests/test_files/test.fitacf");
+    let tempfile = PathBuf::from("tests/test_files/truncated_index.fitacf");
+    let bytes = read(&path).expect("Unable to read test.fitacf");
+    let first_size = i32::from_le_bytes(bytes[4..8].try_into().unwrap()) as u64;
+
+    // A partial header after the last record
+    let mut partial_header = bytes.clone();
+    partial_header.extend(&bytes[..6]);
+    File::create(&tempfile)
+        .and_then(|mut f| f.write_all(&partial_header))
+        .expect("Unable to write truncated_index.fitacf");
+    let mut file = File::open(&tempfile).expect("Unable to open truncated_index.fitacf");
+    match DmapIndex::build(&mut file) {
+        Err(DmapError::CorruptStream(msg)) => assert_eq!(msg, "Record header is truncated"),
+        x => panic!("Expected CorruptStream, got {x:?}"),
+    }
+
+    // A final record which is cut short
+    File::create(&tempfile)
+        .and_then(|mut f| f.write_all(&bytes[..bytes.len() - 10]))
+        .expect("Unable to write truncated_index.fitacf");
+    let mut file = File::open(&tempfile).expect("Unable to open truncated_index.fitacf");
+    match DmapIndex::build(&mut file) {
+        Err(DmapError::TruncatedFile {
+            expected,
+            available,
+            byte,
+        }) => {
+            assert_eq!(byte, first_size);
+            assert_eq!(expected, bytes.len() - first_size as usize);
+            assert_eq!(available, expected - 10);
+        }
+        x => panic!("Expected TruncatedFile, got {x:?}"),
+    }
+
+    remove_file(&tempfile).expect("Unable to delete truncated_index.fitacf");
+}
+
+#[test]
+fn read_write_rawacf_gz() {
+    let path = PathBuf::from("tests/test_files/test.rawacf");
+    let temp_gz = PathBuf::from("tests/test_files/tmp.rawacf.gz");
+
+    // Write to a gzipped file, and then read back in and compare contents
+    let data = RawacfRecord::read_file(&path).expect("Unable to read test.rawacf");
+    write_rawacf(data.clone(), &temp_gz).expect("Unable to write tmp.rawacf.gz");
+    let new_recs = RawacfRecord::read_file(&temp_gz).expect("Cannot read tmp.rawacf.gz");
+    for (ref read_rec, ref written_rec) in izip!(data.iter(), new_recs.iter()) {
+        assert_eq!(read_rec, written_rec)
+    }
+
+    // The compression is also detected from the file contents
+    let generic_recs = read_any(temp_gz.clone()).expect("Cannot read tmp.rawacf.gz");
+    assert_eq!(
+        generic_recs,
+        read_dmap(path).expect("Unable to read test.rawacf")
+    );
+
+    // Clean up the temp file
+    remove_file(&temp_gz).expect("Unable to delete tmp.rawacf.gz");
+}
+
+#[test]
+fn read_write_fitacf_zst() {
+    let path = PathBuf::from("tests/test_files/test.fitacf");
+    let temp_zst = PathBuf::from("tests/test_files/tmp.fitacf.zst");
+    let temp_fast = PathBuf::from("tests/test_files/tmp_fast.fitacf.zst");
+
+    // Write to a zstd-compressed file, and then read back in and compare contents
+    let data = FitacfRecord::read_file(&path).expect("Unable to read test.fitacf");
+    write_fitacf(data.clone(), &temp_zst).expect("Unable to write tmp.fitacf.zst");
+    let new_recs = FitacfRecord::read_file(&temp_zst).expect("Cannot read tmp.fitacf.zst");
+    assert_eq!(data, new_recs);
+
+    // The compression level can be overridden, but must be valid for the algorithm
+    write_records_with(data.clone(), &temp_fast, 1).expect("Unable to write tmp_fast.fitacf.zst");
+    let fast_recs = FitacfRecord::read_file(&temp_fast).expect("Cannot read tmp_fast.fitacf.zst");
+    assert_eq!(data, fast_recs);
+    remove_file(&temp_fast).expect("Unable to delete tmp_fast.fitacf.zst");
+    assert!(write_records_with(data.clone(), &temp_fast, 23).is_err());
+    assert!(!temp_fast.exists());
+
+    // The compression is also detected from the file contents
+    let generic_recs = read_any(temp_zst.clone()).expect("Cannot read tmp.fitacf.zst");
+    assert_eq!(
+        generic_recs,
+        read_dmap(path).expect("Unable to read test.fitacf")
+    );
+
+    // Clean up the temp files
+    remove_file(&temp_zst).expect("Unable to delete tmp.fitacf.zst");
+}
+
+#[test]
+fn write_to_buffer() {
+    let path = PathBuf::from("tests/test_files/test.grid");
+    let data = GridRecord::read_file(&path).expect("Unable to read test.grid");
+
+    // Writing to an in-memory buffer gives the same bytes as the uncompressed file
+    let mut buffer: Vec<u8> = vec![];
+    write_records_to(data.clone(), &mut buffer).expect("Unable to write to buffer");
+    assert_eq!(buffer, read(&path).expect("Unable to read test.grid"));
+    let new_recs = GridRecord::read_records_from_bytes(buffer).expect("Unable to parse buffer");
+    assert_eq!(data, new_recs);
+}
+
+#[test]
+fn sniff_test_files() {
+    let formats = [
+        ("iqdat", FileFormat::Iqdat),
+        ("rawacf", FileFormat::Rawacf),
+        ("fitacf", FileFormat::Fitacf),
+        ("grid", FileFormat::Grid),
+        ("map", FileFormat::Map),
+        ("snd", FileFormat::Snd),
+    ];
+    for (ext, format) in formats {
+        let path = PathBuf::from(format!("tests/test_files/test.{ext}"));
+        let path_bz2 = PathBuf::from(format!("tests/test_files/test.{ext}.bz2"));
+        assert_eq!(sniff_format(&path).expect("Unable to sniff format"), format);
+        assert_eq!(
+            sniff_format(&path_bz2).expect("Unable to sniff format"),
+            format
+        );
+        assert_eq!(format.to_string(), ext);
+    }
+
+    // Records without any signature fields are generic DMAP
+    let mut data: IndexMap<String, DmapField> = IndexMap::new();
+    data.insert("stid".to_string(), DmapField::from(65_i16));
+    let rec = GenericRecord::new(&mut data).expect("Unable to make record");
+    assert_eq!(FileFormat::classify(&rec), FileFormat::Dmap);
+}
+
+#[test]
+fn typed_accessors() {
+    let path = PathBuf::from("tests/test_files/test.rawacf");
+    let mut data = RawacfRecord::read_file(&path).expect("Unable to read test.rawacf");
+
+    // Present fields are returned with their DMAP type
+    let rec = &data[0];
+    let stid: i16 = rec.get_as("stid").expect("Unable to get stid");
+    assert_eq!(rec.stid().expect("Unable to get stid"), stid);
+    assert_eq!(Some(&DmapField::from(stid)), rec.get(&"stid".to_string()));
+    assert_eq!(rec.acfd().expect("Unable to get acfd").ndim(), 3);
+    assert!(rec.origin_command().is_ok());
+
+    // Fields of the wrong type are errors, not conversions
+    assert!(rec.get_as::<i32>("stid").is_err());
+    assert!(rec.get_as::<&ArrayD<f64>>("acfd").is_err());
+    assert!(rec.get_as::<f32>("acfd").is_err());
+
+    // Missing optional fields are errors
+    data[0].data.shift_remove("xcfd");
+    assert!(data[0].xcfd().is_err());
+    assert!(data[0].get_as::<i16>("not_a_field").is_err());
+}
+
+#[test]
+fn filter_records_by_time() {
+    let path = PathBuf::from("tests/test_files/test.fitacf");
+    let data = FitacfRecord::read_file(&path).expect("Unable to read test.fitacf");
+    let times: Vec<OffsetDateTime> = data
+        .iter()
+        .map(|rec| record_time(rec.data()).expect("Unable to get time"))
+        .collect();
+    assert!(times[0] < times[1]);
+
+    // A window around the first beam sounding only
+    let (start, end) = (times[0], times[1]);
+    let filtered = filter_by_time(data.clone(), start, end);
+    assert_eq!(filtered, vec![data[0].clone()]);
+
+    // Filtering as the file is read gives the same result
+    let filtered_on_read = FitacfRecord::read_file_filtered(
+        &path,
+        |rec| matches!(record_time(rec), Ok(t) if start <= t && t < end),
+    )
+    .expect("Unable to read test.fitacf");
+    assert_eq!(filtered_on_read, filtered);
+
+    // Grid and map records use the start time of the record
+    let path = PathBuf::from("tests/test_files/test.grid");
+    let data = GridRecord::read_file(&path).expect("Unable to read test.grid");
+    let (start, end) = (
+        datetime!(1970-01-01 0:00 UTC),
+        datetime!(2100-01-01 0:00 UTC),
+    );
+    assert_eq!(filter_by_time(data.clone(), start, end), data);
+    let end = datetime!(1970-01-01 0:00:01 UTC);
+    assert!(filter_by_time(data, start, end).is_empty());
+}
+
+#[test]
+fn filter_fitacf_beams_and_channel() {
+    let path = PathBuf::from("tests/test_files/test.fitacf");
+    let data = FitacfRecord::read_file(&path).expect("Unable to read test.fitacf");
+    let beam = data[0].bmnum().expect("Unable to get bmnum");
+    let channel = data[0].channel().expect("Unable to get channel");
+    let expected: Vec<FitacfRecord> = data
+        .iter()
+        .filter(|rec| rec.bmnum().unwrap() == beam)
+        .cloned()
+        .collect();
+
+    let recs = read_fitacf_beams(path.clone(), &[beam]).expect("Unable to read test.fitacf");
+    assert_eq!(recs, expected);
+    let recs = read_fitacf_channel(path.clone(), channel).expect("Unable to read test.fitacf");
+    assert!(recs.contains(&data[0]));
+
+    // No beams, or a beam not in the file, gives no records rather than an error
+    let recs = read_fitacf_beams(path.clone(), &[]).expect("Unable to read test.fitacf");
+    assert!(recs.is_empty());
+    let recs = read_fitacf_beams(path.clone(), &[-1]).expect("Unable to read test.fitacf");
+    assert!(recs.is_empty());
+    let recs = read_fitacf_channel(path, -1).expect("Unable to read test.fitacf");
+    assert!(recs.is_empty());
+}
+
+#[test]
+fn concat_rawacf_files() {
+    let path = PathBuf::from("tests/test_files/test.rawacf");
+    let path_bz2 = PathBuf::from("tests/test_files/test.rawacf.bz2");
+    let tempfile = PathBuf::from("tests/test_files/concat.rawacf");
+
+    // Records are written in input order, and compressed inputs are decompressed
+    concat_files::<RawacfRecord>(&[path.clone(), path_bz2], &tempfile)
+        .expect("Unable to concatenate files");
+    let data = RawacfRecord::read_file(&path).expect("Unable to read test.rawacf");
+    let concat_recs = RawacfRecord::read_file(&tempfile).expect("Unable to read concat.rawacf");
+    assert_eq!(concat_recs, [data.clone(), data].concat());
+    remove_file(&tempfile).expect("Unable to delete concat.rawacf");
+
+    // A file of a different type names the file and writes nothing
+    let fitacf = PathBuf::from("tests/test_files/test.fitacf");
+    let err = concat_files::<RawacfRecord>(&[path, fitacf], &tempfile).unwrap_err();
+    assert!(err.to_string().contains("test.fitacf"));
+    assert!(!tempfile.exists());
+}
+
+#[test]
+fn split_file_by_hour() {
+    // Make a file with records at 00:10, 00:50 and 01:05
+    let path = PathBuf::from("tests/test_files/test.fitacf");
+    let tempfile = PathBuf::from("tests/test_files/two_hours.fitacf");
+    let outdir = PathBuf::from("tests/test_files/split_by_hour");
+    let data = FitacfRecord::read_file(&path).expect("Unable to read test.fitacf");
+    let recs: Vec<FitacfRecord> = [(0_i16, 10_i16), (0, 50), (1, 5)]
+        .into_iter()
+        .map(|(hour, minute)| {
+            let mut fields = data[0].clone().inner();
+            fields.insert("time.hr".to_string(), DmapField::from(hour));
+            fields.insert("time.mt".to_string(), DmapField::from(minute));
+            FitacfRecord::new(&mut fields).expect("Unable to make record")
+        })
+        .collect();
+    write_fitacf(recs.clone(), &tempfile).expect("Unable to write two_hours.fitacf");
+
+    create_dir_all(&outdir).expect("Unable to create split_by_hour");
+    let outfiles = split_by_interval::<FitacfRecord>(&tempfile, &outdir, 60)
+        .expect("Unable to split two_hours.fitacf");
+    assert_eq!(outfiles.len(), 2);
+    let date = record_time(recs[0].data()).unwrap().date();
+    let expected_name = format!(
+        "{:04}{:02}{:02}.0010.00.two_hours.fitacf",
+        date.year(),
+        date.month() as u8,
+        date.day()
+    );
+    assert_eq!(
+        outfiles[0].file_name().unwrap().to_str(),
+        Some(expected_name.as_str())
+    );
+    let first_hour = FitacfRecord::read_file(&outfiles[0]).expect("Unable to read split file");
+    let second_hour = FitacfRecord::read_file(&outfiles[1]).expect("Unable to read split file");
+    assert_eq!(first_hour, recs[..2]);
+    assert_eq!(second_hour, recs[2..]);
+
+    // Splitting again replaces the outputs rather than appending to them
+    let again = split_by_interval::<FitacfRecord>(&tempfile, &outdir, 60)
+        .expect("Unable to split two_hours.fitacf");
+    assert_eq!(again, outfiles);
+    let first_hour = FitacfRecord::read_file(&outfiles[0]).expect("Unable to read split file");
+    assert_eq!(first_hour, recs[..2]);
+
+    // Records without time fields cannot be split
+    let grid = PathBuf::from("tests/test_files/test.grid");
+    assert!(split_by_interval::<GenericRecord>(&grid, &outdir, 60).is_ok());
+    let mut fields = data[0].clone().inner();
+    fields.shift_remove("time.yr");
+    let no_time = GenericRecord::new(&mut fields).expect("Unable to make record");
+    let no_time_file = PathBuf::from("tests/test_files/no_time.dmap");
+    write_dmap(vec![no_time], &no_time_file).expect("Unable to write no_time.dmap");
+    assert!(split_by_interval::<GenericRecord>(&no_time_file, &outdir, 60).is_err());
+
+    remove_file(&tempfile).expect("Unable to delete two_hours.fitacf");
+    remove_file(&no_time_file).expect("Unable to delete no_time.dmap");
+    remove_dir_all(&outdir).expect("Unable to delete split_by_hour");
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn map_json_round_trip() {
+    let path = PathBuf::from("tests/test_files/test.map");
+    let data = MapRecord::read_file(&path).expect("Unable to read test.map");
+
+    let json = data[0].to_json().expect("Unable to serialize record");
+    let rec = MapRecord::from_json(&json).expect("Unable to deserialize record");
+    assert_eq!(rec, data[0]);
+
+    // Fields are tagged with their type, and vectors carry their shape
+    let json = GenericRecord::new(&mut IndexMap::from([
+        ("a".to_string(), DmapField::from(1_i16)),
+        (
+            "b".to_string(),
+            DmapField::from(array![[f32::NAN, 2.0]].into_dyn()),
+        ),
+    ]))
+    .and_then(|rec| rec.to_json())
+    .expect("Unable to serialize record");
+    assert_eq!(
+        json,
+        r#"{"a":{"type":"SHORT","value":1},"b":{"type":"FLOAT","shape":[1,2],"data":["NaN",2.0]}}"#
+    );
+}
+
+#[cfg(feature = "arrow")]
+#[test]
+fn read_fitacf_columns() {
+    use arrow_array::Array;
+    use dmap::columnar::read_fitacf_arrow;
+    use dmap::formats::fitacf::fitacf_fields;
+
+    let path = PathBuf::from("tests/test_files/test.fitacf");
+    let data = FitacfRecord::read_file(&path).expect("Unable to read test.fitacf");
+    let batch = read_fitacf_arrow(&path).expect("Unable to read test.fitacf into columns");
+    assert_eq!(batch.num_rows(), data.len());
+
+    let fields = fitacf_fields();
+    let names: Vec<&str> = fields
+        .scalars_required
+        .iter()
+        .chain(&fields.scalars_optional)
+        .chain(&fields.vectors_required)
+        .chain(&fields.vectors_optional)
+        .map(|(name, _)| *name)
+        .collect();
+    let schema = batch.schema();
+    let columns: Vec<&str> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+    assert_eq!(columns, names);
+
+    // Optional fields are null in the rows of records which lack them
+    let missing = data
+        .iter()
+        .filter(|rec| rec.get(&"slist".to_string()).is_none())
+        .count();
+    let slist = batch.column_by_name("slist").expect("No slist column");
+    assert_eq!(slist.null_count(), missing);
+    assert_eq!(batch.column_by_name("stid").unwrap().null_count(), 0);
+}
+
+#[cfg(feature = "arrow")]
+#[test]
+fn write_fitacf_columns() {
+    use dmap::columnar::{read_fitacf_arrow, write_fitacf_arrow};
+
+    let path = PathBuf::from("tests/test_files/test.fitacf");
+    let tempfile = PathBuf::from("tests/test_files/temp_arrow.fitacf");
+    let batch = read_fitacf_arrow(&path).expect("Unable to read test.fitacf into columns");
+    write_fitacf_arrow(&batch, &tempfile).expect("Unable to write columns");
+    let diffs = diff_files::<FitacfRecord>(&path, &tempfile, None).expect("Unable to diff files");
+    assert_eq!(diffs, vec![]);
+    remove_file(&tempfile).expect("Unable to delete temp_arrow.fitacf");
+
+    // Required columns must be present
+    let (stid, _) = batch.schema().column_with_name("stid").unwrap();
+    let indices: Vec<usize> = (0..batch.num_columns()).filter(|&i| i != stid).collect();
+    let partial = batch.project(&indices).unwrap();
+    let err = write_fitacf_arrow(&partial, &tempfile).unwrap_err();
+    assert!(err.to_string().contains("stid"), "{err}");
+    assert!(!tempfile.exists());
+}
+
+#[test]
+fn fitacf_csv_export() {
+    let path = PathBuf::from("tests/test_files/test.fitacf");
+    let fixture = PathBuf::from("tests/test_files/test.fitacf.csv");
+    let tempfile = PathBuf::from("tests/test_files/tmp.fitacf.csv");
+
+    // The header and first range gate of the first record match the fixture
+    fitacf_to_csv(&path, &tempfile).expect("Unable to convert test.fitacf");
+    let csv = read_to_string(&tempfile).expect("Unable to read tmp.fitacf.csv");
+    let expected = read_to_string(&fixture).expect("Unable to read test.fitacf.csv");
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines[..2], expected.lines().collect::<Vec<&str>>());
+
+    // One row per range gate, plus the header
+    let data = FitacfRecord::read_file(&path).expect("Unable to read test.fitacf");
+    let num_gates: usize = data
+        .iter()
+        .map(|rec| rec.slist().map_or(0, |x| x.len()))
+        .sum();
+    assert_eq!(lines.len(), num_gates + 1);
+
+    // Absent optional vectors are empty cells
+    let mut fields = data[0].clone().inner();
+    fields.shift_remove("elv");
+    let rec = FitacfRecord::new(&mut fields).expect("Unable to make record");
+    let elv_column = FitacfRecord::csv_header()
+        .split(',')
+        .position(|x| x == "elv")
+        .unwrap();
+    assert_eq!(rec.csv_rows()[0].split(',').nth(elv_column), Some(""));
+
+    remove_file(&tempfile).expect("Unable to delete tmp.fitacf.csv");
+}
+
+#[test]
+fn iterate_records_lazily() {
+    let path = PathBuf::from("tests/test_files/test.fitacf");
+    let tempfile = PathBuf::from("tests/test_files/lazy.fitacf.bz2");
+    let data = FitacfRecord::read_file(&path).expect("Unable to read test.fitacf");
+    let many_recs = [data.clone(), data.clone(), data.clone()].concat();
+    write_fitacf(many_recs.clone(), &tempfile).expect("Unable to write lazy.fitacf.bz2");
+
+    // Only the first two records are read from the compressed file
+    let first_two: Vec<FitacfRecord> = FitacfRecord::iter_records(&tempfile)
+        .expect("Unable to open lazy.fitacf.bz2")
+        .take(2)
+        .collect::<Result<_, _>>()
+        .expect("Unable to read records");
+    assert_eq!(first_two, data);
+
+    // Consuming everything gives the same records as reading the whole file
+    let all: Vec<FitacfRecord> = FitacfRecord::iter_records(&tempfile)
+        .expect("Unable to open lazy.fitacf.bz2")
+        .collect::<Result<_, _>>()
+        .expect("Unable to read records");
+    assert_eq!(all, many_recs);
+
+    // A truncated final record is an error, after which iteration stops
+    let bytes = read(&path).expect("Unable to read test.fitacf");
+    let truncated = bytes[..bytes.len() - 10].to_vec();
+    let results: Vec<_> = RecordIter::<FitacfRecord>::new(Cursor::new(truncated)).collect();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].as_ref().ok(), Some(&data[0]));
+    assert!(results[1].is_err());
+
+    remove_file(&tempfile).expect("Unable to delete lazy.fitacf.bz2");
+}
+
+#[test]
+fn read_file_matches_buffered_read() {
+    // With the `mmap` feature, `read_file` maps uncompressed files rather than buffering them
+    for ext in ["iqdat", "rawacf", "fitacf", "grid", "map", "snd"] {
+        let path = PathBuf::from(format!("tests/test_files/test.{ext}"));
+        let mapped = GenericRecord::read_file(&path).expect("Unable to read file");
+        let file = File::open(&path).expect("Unable to open file");
+        let buffered = GenericRecord::read_records(file).expect("Unable to read file");
+        assert_eq!(mapped, buffered);
+
+        let mut bytes: Vec<u8> = vec![];
+        write_records_to(mapped, &mut bytes).expect("Unable to write records");
+        assert_eq!(bytes, read(&path).expect("Unable to read file"));
+    }
+}
+
+#[test]
+fn read_many_files() {
+    let infiles: Vec<PathBuf> = [
+        "test.rawacf",
+        "test.rawacf.bz2",
+        "missing.rawacf",
+        "test.fitacf",
+    ]
+    .iter()
+    .map(|name| PathBuf::from(format!("tests/test_files/{name}")))
+    .collect();
+    let results = read_files::<RawacfRecord>(infiles.clone());
+    assert_eq!(results.len(), infiles.len());
+
+    // Each file's result matches reading it alone, and bad files don't affect the others
+    for (infile, result) in izip!(infiles.iter(), results.iter()) {
+        match RawacfRecord::read_file(infile) {
+            Ok(recs) => assert_eq!(result.as_ref().ok(), Some(&recs)),
+            Err(_) => assert!(result.is_err()),
+        }
+    }
+    assert!(results[0].is_ok());
+    assert!(results[2].is_err());
+    assert!(results[3].is_err());
+}
+
+#[test]
+fn read_big_endian_snd() {
+    let path = PathBuf::from("tests/test_files/test.snd");
+    let big_endian = PathBuf::from("tests/test_files/test_big_endian.snd");
+
+    let bytes = read(&big_endian).expect("Unable to read test_big_endian.snd");
+    assert_eq!(
+        Endianness::detect(bytes[..4].try_into().unwrap()),
+        Some(Endianness::Big)
+    );
+    assert_eq!(Endianness::detect([1, 0, 1, 0]), Some(Endianness::Little));
+    assert_eq!(Endianness::detect([0, 0, 0, 0]), None);
+
+    // The byte-swapped file reads identically to the little-endian version
+    let data = SndRecord::read_file(&path).expect("Unable to read test.snd");
+    let swapped = SndRecord::read_file(&big_endian).expect("Cannot read test_big_endian.snd");
+    assert_eq!(data, swapped);
+    let iterated: Vec<SndRecord> = SndRecord::iter_records(&big_endian)
+        .expect("Cannot open test_big_endian.snd")
+        .collect::<Result<_, _>>()
+        .expect("Cannot iterate over test_big_endian.snd");
+    assert_eq!(data, iterated);
+    assert_eq!(
+        sniff_format(&big_endian).expect("Cannot sniff test_big_endian.snd"),
+        FileFormat::Snd
+    );
+
+    // Records are always written little-endian
+    let tempfile = PathBuf::from("tests/test_files/tmp_big_endian.snd");
+    write_snd(swapped, &tempfile).expect("Unable to write tmp_big_endian.snd");
+    assert_eq!(
+        read(&path).expect("Unable to read test.snd"),
+        read(&tempfile).expect("Unable to read tmp_big_endian.snd")
+    );
+    remove_file(&tempfile).expect("Unable to delete tmp_big_endian.snd");
+}
+
+/// Checks that parsing records borrowed from one buffer matches parsing them one at a time.
+fn check_borrowed_parse<T: for<'a> Record<'a> + PartialEq>(path: &str) {
+    let bytes = read(path).expect("Unable to read file");
+    let borrowed = T::read_records_from_bytes(bytes).expect("Unable to parse records");
+    let iterated: Vec<T> = T::iter_records(&PathBuf::from(path))
+        .expect("Unable to open file")
+        .collect::<Result<_, _>>()
+        .expect("Unable to iterate over records");
+    assert!(!borrowed.is_empty());
+    assert!(borrowed == iterated, "{path}");
+}
+
+#[test]
+fn parse_borrowed_records() {
+    check_borrowed_parse::<IqdatRecord>("tests/test_files/test.iqdat");
+    check_borrowed_parse::<RawacfRecord>("tests/test_files/test.rawacf");
+    check_borrowed_parse::<FitacfRecord>("tests/test_files/test.fitacf");
+    check_borrowed_parse::<GridRecord>("tests/test_files/test.grid");
+    check_borrowed_parse::<MapRecord>("tests/test_files/test.map");
+    check_borrowed_parse::<SndRecord>("tests/test_files/test.snd");
+    check_borrowed_parse::<SndRecord>("tests/test_files/test_big_endian.snd");
+}
+
+#[test]
+fn parse_large_record_in_parallel() {
+    // Large enough for the vector fields to be parsed in parallel
+    let mut fields = IndexMap::from([("stid".to_string(), DmapField::from(65_i16))]);
+    for i in 0..8 {
+        let data: Vec<f32> = (0..50_000).map(|x| (x * i) as f32).collect();
+        fields.insert(
+            format!("vector{i}"),
+            DmapField::from(ArrayD::from_shape_vec(vec![50_000], data).unwrap()),
+        );
+    }
+    let ints: Vec<i32> = (0..100_000).collect();
+    fields.insert(
+        "ints".to_string(),
+        DmapField::from(ArrayD::from_shape_vec(vec![500, 200], ints).unwrap()),
+    );
+    let rec = GenericRecord::new(&mut fields).expect("Unable to create record");
+    let bytes = rec.to_bytes().expect("Unable to convert record to bytes");
+    assert!(bytes.len() > 1 << 20);
+
+    let mut cursor = Cursor::new(bytes.as_slice());
+    let parsed = parse_one(&mut cursor).expect("Unable to parse record");
+    assert_eq!(parsed, rec);
+    assert!(parsed.data.keys().eq(fields.keys()));
+    assert_eq!(cursor.position() as usize, bytes.len());
+
+    // A vector which runs past the end of the record is still an error
+    let mut truncated = bytes.clone();
+    truncated.truncate(bytes.len() - 4);
+    assert!(parse_one(&mut Cursor::new(truncated.as_slice())).is_err());
+}
+
+#[test]
+fn stream_records_to_writer() {
+    let recs: Vec<GenericRecord> = (0..1000_i32)
+        .map(|i| {
+            let mut fields = IndexMap::from([
+                ("index".to_string(), DmapField::from(i)),
+                ("name".to_string(), DmapField::from(format!("record {i}"))),
+            ]);
+            let data: Vec<f32> = (0..100).map(|x| (x * i) as f32).collect();
+            fields.insert(
+                "data".to_string(),
+                DmapField::from(ArrayD::from_shape_vec(vec![10, 10], data).unwrap()),
+            );
+            GenericRecord::new(&mut fields).expect("Unable to create record")
+        })
+        .collect();
+
+    let mut writer = DmapWriter::new(vec![]);
+    for rec in recs.iter() {
+        writer.write_record(rec).expect("Unable to write record");
+    }
+    let bytes = writer.finish().expect("Unable to finish writing");
+    let new_recs = GenericRecord::read_records(Cursor::new(bytes)).expect("Unable to parse bytes");
+    assert_eq!(new_recs, recs);
+
+    for ext in ["", ".bz2", ".gz", ".zst"] {
+        let tempfile = PathBuf::from(format!("tests/test_files/stream.dmap{ext}"));
+        let mut writer =
+            DmapWriter::create(&tempfile, &WriteOptions::default()).expect("Unable to create file");
+        for rec in recs.iter() {
+            writer.write_record(rec).expect("Unable to write record");
+        }
+        writer.finish().expect("Unable to finish writing");
+        let new_recs = GenericRecord::read_file(&tempfile).expect("Unable to read file");
+        assert_eq!(new_recs, recs);
+        remove_file(&tempfile).expect("Unable to delete file");
+    }
+}
+
+#[test]
+fn write_with_modes() {
+    let path = PathBuf::from("tests/test_files/test.rawacf");
+    let recs = RawacfRecord::read_file(&path).expect("Unable to read test.rawacf");
+    let append = WriteOptions::default();
+    let overwrite = WriteOptions {
+        mode: WriteMode::Overwrite,
+        ..Default::default()
+    };
+    let create_new = WriteOptions {
+        mode: WriteMode::CreateNew,
+        ..Default::default()
+    };
+
+    for ext in ["", ".bz2", ".gz", ".zst"] {
+        let tempfile = PathBuf::from(format!("tests/test_files/modes.rawacf{ext}"));
+        write_records_opts(recs.clone(), &tempfile, &create_new).expect("Unable to create file");
+        assert!(matches!(
+            write_records_opts(recs.clone(), &tempfile, &create_new),
+            Err(DmapError::Io(_))
+        ));
+        write_records_opts(recs.clone(), &tempfile, &overwrite).expect("Unable to overwrite file");
+        assert_eq!(
+            RawacfRecord::read_file(&tempfile).expect("Unable to read file"),
+            recs
+        );
+
+        write_records_opts(recs.clone(), &tempfile, &append).expect("Unable to append to file");
+        let new_recs = RawacfRecord::read_file(&tempfile).expect("Unable to read file");
+        assert_eq!(new_recs.len(), 2 * recs.len());
+        remove_file(&tempfile).expect("Unable to delete file");
+    }
+}
+
+#[test]
+fn write_twice_to_same_file() {
+    let path = PathBuf::from("tests/test_files/test.rawacf");
+    let recs = RawacfRecord::read_file(&path).expect("Unable to read test.rawacf");
+    for ext in ["", ".bz2"] {
+        let tempfile = PathBuf::from(format!("tests/test_files/twice.rawacf{ext}"));
+        write_rawacf(recs.clone(), &tempfile).expect("Unable to write file");
+        write_rawacf(recs.clone(), &tempfile).expect("Unable to write file again");
+        let new_recs = RawacfRecord::read_file(&tempfile).expect("Unable to read file");
+        assert_eq!(new_recs.len(), 2 * recs.len());
+        assert_eq!(new_recs[..recs.len()], recs[..]);
+        assert_eq!(new_recs[recs.len()..], recs[..]);
+        remove_file(&tempfile).expect("Unable to delete file");
+    }
+}
+
+#[test]
+fn reject_wrong_code() {
+    let file = read("tests/test_files/test.snd").expect("Unable to read test.snd");
+    let size = i32::from_le_bytes(file[4..8].try_into().unwrap()) as usize;
+    let mut bytes = file[..size].to_vec();
+    assert!(GenericRecord::parse_record(&mut Cursor::new(bytes.as_slice())).is_ok());
+
+    bytes[..4].copy_from_slice(&12345_i32.to_le_bytes());
+    let err = GenericRecord::parse_record(&mut Cursor::new(bytes.as_slice()))
+        .expect_err("Record with wrong code was parsed");
+    assert_eq!(
+        err.to_string(),
+        "Record 0 at byte 0: Invalid code 12345, expected 65537"
+    );
+
+    // The error locates a later record within the file
+    let mut file = file;
+    file[size..size + 4].copy_from_slice(&12345_i32.to_le_bytes());
+    match GenericRecord::read_records(Cursor::new(file)) {
+        Err(DmapError::BadRecords(recs, e)) => {
+            assert_eq!(recs, vec![1]);
+            assert_eq!(
+                e.to_string(),
+                format!("Record 1 at byte {size}: Invalid code 12345, expected 65537")
+            );
+        }
+        x => panic!("Expected invalid code error, got {x:?}"),
+    }
+}
+
+#[test]
+fn reject_overstated_size() {
+    let mut bytes = read("tests/test_files/test.snd").expect("Unable to read test.snd");
+    let size = i32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+    bytes.truncate(size);
+    bytes[4..8].copy_from_slice(&(size as i32 + 4).to_le_bytes());
+    let err = GenericRecord::parse_record(&mut Cursor::new(bytes.as_slice()))
+        .expect_err("Record with overstated size was parsed");
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "Record at byte 0 is truncated: its size is {} bytes but only {size} remain",
+            size + 4
+        )
+    );
+    assert!(GenericRecord::read_records(Cursor::new(bytes)).is_err());
+}
+
+#[test]
+fn reject_wrong_field_counts() {
+    let file = read("tests/test_files/test.snd").expect("Unable to read test.snd");
+    let size = i32::from_le_bytes(file[4..8].try_into().unwrap()) as usize;
+    let bytes = file[..size].to_vec();
+    let num_scalars = i32::from_le_bytes(bytes[8..12].try_into().unwrap());
+    let num_vectors = i32::from_le_bytes(bytes[12..16].try_into().unwrap());
+
+    let parse_with_counts = |scalars: i32, vectors: i32| {
+        let mut corrupted = bytes.clone();
+        corrupted[8..12].copy_from_slice(&scalars.to_le_bytes());
+        corrupted[12..16].copy_from_slice(&vectors.to_le_bytes());
+        GenericRecord::parse_record(&mut Cursor::new(corrupted.as_slice()))
+            .expect_err("Record with wrong counts was parsed")
+            .to_string()
+    };
+    let prefix = "Record 0 at byte 0: Wrong number of fields";
+    assert_eq!(
+        parse_with_counts(num_scalars - 1, num_vectors),
+        format!(
+            "{prefix}: expected {} scalars, found {num_scalars}",
+            num_scalars - 1
+        )
+    );
+    assert_eq!(
+        parse_with_counts(num_scalars + 1, num_vectors),
+        format!(
+            "{prefix}: expected {} scalars, found {num_scalars}",
+            num_scalars + 1
+        )
+    );
+    assert_eq!(
+        parse_with_counts(num_scalars, num_vectors - 1),
+        format!(
+            "{prefix}: expected {} vectors, found {num_vectors}",
+            num_vectors - 1
+        )
+    );
+    assert_eq!(
+        parse_with_counts(num_scalars, num_vectors + 1),
+        format!(
+            "{prefix}: expected {} vectors, found {num_vectors}",
+            num_vectors + 1
+        )
+    );
+
+    // The error locates a later record within the file
+    let mut file = file;
+    let num_scalars = i32::from_le_bytes(file[size + 8..size + 12].try_into().unwrap());
+    file[size + 8..size + 12].copy_from_slice(&(num_scalars + 1).to_le_bytes());
+    match GenericRecord::read_records(Cursor::new(file)) {
+        Err(DmapError::BadRecords(recs, e)) => {
+            assert_eq!(recs, vec![1]);
+            assert_eq!(
+                e.to_string(),
+                format!(
+                    "Record 1 at byte {size}: Wrong number of fields: expected {} scalars, found {num_scalars}",
+                    num_scalars + 1
+                )
+            );
+        }
+        x => panic!("Expected wrong field counts error, got {x:?}"),
+    }
+}
+
+#[test]
+fn reject_unknown_type_key() {
+    let mut bytes = read("tests/test_files/test.snd").expect("Unable to read test.snd");
+    let size = i32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+    bytes.truncate(size);
+
+    // The first scalar starts after the 16-byte header, and its type key follows its name
+    let name_len = bytes[16..].iter().position(|&x| x == 0).unwrap();
+    let name = String::from_utf8(bytes[16..16 + name_len].to_vec()).unwrap();
+    bytes[16 + name_len + 1] = 99;
+
+    let err = GenericRecord::parse_record(&mut Cursor::new(bytes.as_slice()))
+        .expect_err("Record with unknown type key was parsed")
+        .to_string();
+    assert_eq!(
+        err,
+        format!("Record 0, field '{name}' at byte 16: Invalid type key 99")
+    );
+}
+
+#[test]
+fn read_64_bit_fields() {
+    let mut data = IndexMap::new();
+    data.insert("count".to_string(), DmapField::from(i64::MAX));
+    data.insert("total".to_string(), DmapField::from(u64::MAX));
+    let ids = array![1_i64 << 40, -1].into_dyn();
+    data.insert("ids".to_string(), DmapField::from(ids));
+    let sizes = array![u64::MAX, 0].into_dyn();
+    data.insert("sizes".to_string(), DmapField::from(sizes));
+    let rec = GenericRecord::new(&mut data).expect("Unable to create record");
+    let bytes = rec.to_bytes().expect("Unable to convert record");
+    let recs = GenericRecord::read_records_from_bytes(bytes).expect("Unable to parse record");
+    assert_eq!(recs, vec![rec]);
+    assert_eq!(
+        recs[0].get_as::<i64>("count").expect("count is not LONG"),
+        i64::MAX
+    );
+
+    // A known field stored as a LONG is only accepted by a typed reader with an override
+    let path = PathBuf::from("tests/test_files/test.rawacf");
+    let tempfile = PathBuf::from("tests/test_files/long.rawacf");
+    let expected = RawacfRecord::read_file(&path).expect("Unable to read test.rawacf");
+    let mut long_recs = GenericRecord::read_file(&path).expect("Unable to read test.rawacf");
+    for rec in long_recs.iter_mut() {
+        let time_us = rec.get_as::<i32>("time.us").expect("time.us is not INT");
+        rec.data
+            .insert("time.us".to_string(), DmapField::from(time_us as i64));
+    }
+    write_dmap(long_recs, &tempfile).expect("Unable to write long.rawacf");
+
+    let err = RawacfRecord::read_file(&tempfile).expect_err("LONG time.us was accepted");
+    assert!(err.to_string().contains("time.us"), "{err}");
+    let opts = ReadOptions {
+        type_overrides: [("time.us".to_string(), Type::Long)].into_iter().collect(),
+        ..Default::default()
+    };
+    let data: Vec<RawacfRecord> =
+        read_file_with(&tempfile, &opts).expect("Unable to read long.rawacf");
+    assert_eq!(data, expected);
+
+    remove_file(&tempfile).expect("Unable to delete long.rawacf");
+}
+
+#[test]
+fn edit_generic_record() {
+    let path = PathBuf::from("tests/test_files/test.rawacf");
+    let mut rec = GenericRecord::read_file(&path).expect("Unable to read test.rawacf")[0].clone();
+
+    assert!(rec.contains_key("xcfd"));
+    assert!(matches!(rec.remove("xcfd"), Some(DmapField::Vector(_))));
+    assert!(!rec.contains_key("xcfd"));
+    assert_eq!(rec.remove("xcfd"), None);
+
+    rec.insert("count".to_string(), DmapField::from(5_i32));
+    rec.insert("stid".to_string(), DmapField::from(100_i16));
+    assert_eq!(rec.keys().last().map(|x| x.as_str()), Some("count"));
+
+    let bytes = rec.to_bytes().expect("Unable to convert record");
+    let recs = GenericRecord::read_records_from_bytes(bytes).expect("Unable to parse record");
+    assert_eq!(recs, vec![rec]);
+    assert_eq!(recs[0].get_as::<i32>("count").expect("count is not INT"), 5);
+    assert_eq!(
+        recs[0].get_as::<i16>("stid").expect("stid is not SHORT"),
+        100
+    );
+}
+
+#[test]
+fn strip_xcf_fields() {
+    let path = PathBuf::from("tests/test_files/test.fitacf");
+    let data = FitacfRecord::read_file(&path).expect("Unable to read test.fitacf");
+    for rec in data {
+        assert!(rec.data.contains_key("x_v") && rec.data.contains_key("elv"));
+        let mut stripped = rec.data.clone();
+        strip_xcf(&mut stripped);
+        assert!(stripped
+            .keys()
+            .all(|k| !k.starts_with("x_") && !k.starts_with("phi0") && !k.starts_with("elv")));
+        assert!(stripped.contains_key("v") && stripped.contains_key("xcf"));
+
+        let stripped = FitacfRecord::new(&mut stripped).expect("Stripped record is invalid");
+        let stripped_size = stripped.to_bytes().expect("Unable to convert record").len();
+        assert!(stripped_size < rec.to_bytes().expect("Unable to convert record").len());
+    }
+}
+
+#[test]
+fn count_optional_fields() {
+    let path = PathBuf::from("tests/test_files/test.rawacf");
+    let data = GenericRecord::read_file(&path).expect("Unable to read test.rawacf");
+    let with_xcfd = data.iter().filter(|rec| rec.contains_key("xcfd")).count();
+    assert!(with_xcfd > 0);
+
+    let counts = present_optional_fields::<RawacfRecord>(&path).expect("Unable to count fields");
+    assert_eq!(counts.len(), 3);
+    assert_eq!(counts["xcfd"], with_xcfd);
+    assert_eq!(counts["ifmode"], 0);
+
+    let counts = present_optional_fields::<GenericRecord>(&path).expect("Unable to count fields");
+    assert!(counts.is_empty());
+}
+
+#[test]
+fn try_write_reports_bad_records() {
+    let path = PathBuf::from("tests/test_files/test.rawacf");
+    let tempfile = PathBuf::from("tests/test_files/bad.rawacf");
+    let mut recs: Vec<IndexMap<String, DmapField>> = GenericRecord::read_file(&path)
+        .expect("Unable to read test.rawacf")
+        .into_iter()
+        .map(|rec| rec.inner())
+        .collect();
+    assert!(recs.len() > 1);
+    recs[1].insert("combf".to_string(), DmapField::from(5_i16));
+
+    match try_write_rawacf(recs, &tempfile) {
+        Err(DmapError::BadRecords(indices, _)) => assert_eq!(indices, vec![1]),
+        x => panic!("Expected BadRecords, got {x:?}"),
+    }
+    assert!(!tempfile.exists());
+}
+
+#[test]
+fn to_bytes_into_shared_buffer() {
+    let fitacf = FitacfRecord::read_file(&PathBuf::from("tests/test_files/test.fitacf"))
+        .expect("Unable to read test.fitacf");
+    let generic = GenericRecord::read_file(&PathBuf::from("tests/test_files/test.fitacf"))
+        .expect("Unable to read test.fitacf");
+
+    // Records appended to one buffer match their individual `to_bytes` back to back
+    let mut buf = vec![];
+    let mut expected = vec![];
+    for rec in fitacf.iter() {
+        rec.to_bytes_into(&mut buf)
+            .expect("Unable to convert record");
+        expected.extend(rec.to_bytes().expect("Unable to convert record"));
+    }
+    assert_eq!(buf, expected);
+
+    buf.clear();
+    generic[0]
+        .to_bytes_into(&mut buf)
+        .expect("Unable to convert record");
+    assert_eq!(
+        buf,
+        generic[0].to_bytes().expect("Unable to convert record")
+    );
+
+    // An invalid record leaves the buffer untouched
+    let mut invalid = fitacf[0].clone();
+    invalid.data.shift_remove("stid");
+    assert!(invalid.to_bytes_into(&mut buf).is_err());
+    assert_eq!(
+        buf,
+        generic[0].to_bytes().expect("Unable to convert record")
+    );
+}
+
+#[test]
+fn read_truncated_final_record() {
+    let path = PathBuf::from("tests/test_files/test.rawacf");
+    let tempfile = PathBuf::from("tests/test_files/truncated_last.rawacf");
+    let bytes = read(&path).expect("Unable to read test.rawacf");
+    let first_size = i32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+    let second_size =
+        i32::from_le_bytes(bytes[first_size + 4..first_size + 8].try_into().unwrap()) as usize;
+
+    // Cut the last record off in the middle of its data
+    let available = second_size / 2;
+    File::create(&tempfile)
+        .and_then(|mut f| f.write_all(&bytes[..first_size + available]))
+        .expect("Unable to write truncated_last.rawacf");
+    let expected_err = DmapError::TruncatedFile {
+        expected: second_size,
+        available,
+        byte: first_size as u64,
+    }
+    .to_string();
+
+    // Reading all at once, lazily and counting records all report the truncated record
+    for err in [
+        RawacfRecord::read_file(&tempfile).expect_err("Truncated file was read"),
+        RawacfRecord::iter_records(&tempfile)
+            .expect("Unable to open truncated_last.rawacf")
+            .nth(1)
+            .expect("No second record")
+            .expect_err("Truncated record was read"),
+        count_records(&tempfile).expect_err("Truncated file was counted"),
+    ] {
+        assert!(matches!(err, DmapError::TruncatedFile { .. }));
+        assert_eq!(err.to_string(), expected_err);
+    }
+
+    // A corrupt interior record is reported as a bad record rather than a truncated file
+    let mut corrupt = bytes.clone();
+    corrupt[first_size + 8] = 0xff;
+    match RawacfRecord::read_records(Cursor::new(corrupt)) {
+        Err(DmapError::BadRecords(recs, _)) => assert_eq!(recs, vec![1]),
+        x => panic!("Unexpected result {x:?}"),
+    }
+
+    remove_file(&tempfile).expect("Unable to delete truncated_last.rawacf");
+}
+
+#[test]
+fn hash_records() {
+    let path = PathBuf::from("tests/test_files/test.fitacf");
+    let tempfile = PathBuf::from("tests/test_files/hashed.fitacf");
+    let hashes = record_hashes::<FitacfRecord>(&path).expect("Unable to hash test.fitacf");
+    let mut data = FitacfRecord::read_file(&path).expect("Unable to read test.fitacf");
+    assert_eq!(hashes.len(), data.len());
+
+    // An identical file has identical hashes
+    write_fitacf(data.clone(), &tempfile).expect("Unable to write hashed.fitacf");
+    assert_eq!(
+        record_hashes::<FitacfRecord>(&tempfile).expect("Unable to hash file"),
+        hashes
+    );
+
+    // Changing one scalar changes only the hash of its record
+    data[1]
+        .data
+        .insert("bmnum".to_string(), DmapField::from(99_i16));
+    remove_file(&tempfile).expect("Unable to delete hashed.fitacf");
+    write_fitacf(data, &tempfile).expect("Unable to write hashed.fitacf");
+    let changed = record_hashes::<FitacfRecord>(&tempfile).expect("Unable to hash file");
+    assert_eq!(changed[0], hashes[0]);
+    assert_ne!(changed[1], hashes[1]);
+
+    remove_file(&tempfile).expect("Unable to delete hashed.fitacf");
+}
+
+#[test]
+fn list_missing_required_fields() {
+    let path = PathBuf::from("tests/test_files/test.rawacf");
+    let mut data = RawacfRecord::read_file(&path).expect("Unable to read test.rawacf");
+    let mut fields = data.remove(0).inner();
+    assert!(RawacfRecord::missing_required_fields(&fields, rawacf_fields()).is_empty());
+
+    // All missing fields are reported, in the order of the format, while optional fields are not
+    for name in ["slist", "stid", "pwr0", "xcfd"] {
+        fields.shift_remove(name);
+    }
+    assert_eq!(
+        RawacfRecord::missing_required_fields(&fields, rawacf_fields()),
+        vec!["stid", "pwr0", "slist"]
+    );
+}
+
+#[test]
+fn normalize_channels() {
+    assert_eq!(channel_to_letter(1).expect("Channel 1 has no letter"), 'a');
+    assert_eq!(channel_to_letter(2).expect("Channel 2 has no letter"), 'b');
+    assert_eq!(letter_to_channel('a').expect("Unknown letter a"), 1);
+    assert_eq!(letter_to_channel('B').expect("Unknown letter B"), 2);
+    for channel in [i16::MIN, -1, 0, 3] {
+        assert!(channel_to_letter(channel).is_err());
+    }
+    assert!(letter_to_channel('c').is_err());
+
+    // Letters stored as strings or chars, and other integer types, become SHORT
+    for (stored, expected) in [
+        (DmapField::from("b".to_string()), 2_i16),
+        (DmapField::from(b'a' as i8), 1),
+        (DmapField::from(2_i32), 2),
+        (DmapField::from(0_u8), 0),
+    ] {
+        let mut rec = IndexMap::from([("channel".to_string(), stored)]);
+        normalize_channel(&mut rec).expect("Unable to normalize channel");
+        assert_eq!(rec["channel"], DmapField::from(expected));
+    }
+    for stored in [
+        DmapField::from("c".to_string()),
+        DmapField::from("ab".to_string()),
+    ] {
+        let mut rec = IndexMap::from([("channel".to_string(), stored)]);
+        assert!(normalize_channel(&mut rec).is_err());
+    }
+
+    // Records without a channel are unchanged
+    let mut rec = IndexMap::from([("stid".to_string(), DmapField::from(65_i16))]);
+    normalize_channel(&mut rec).expect("Unable to normalize record without a channel");
+    assert_eq!(rec.len(), 1);
+}
+
+#[test]
+fn approx_eq_records() {
+    let path = PathBuf::from("tests/test_files/test.fitacf");
+    let data = FitacfRecord::read_file(&path).expect("Unable to read test.fitacf");
+    let original = &data[0];
+    let mut perturbed = original.clone();
+    match perturbed.data.get_mut("p_l") {
+        Some(DmapField::Vector(DmapVec::Float(x))) => x.mapv_inplace(|v| v + 1e-7),
+        x => panic!("Unexpected p_l {x:?}"),
+    }
+    assert_ne!(&perturbed, original);
+    assert!(perturbed.approx_eq(original, 1e-6, 1e-6));
+    assert!(original.approx_eq(&perturbed, 0.0, 1e-6));
+    assert!(!perturbed.approx_eq(original, 0.0, 0.0));
+
+    // Scalars are compared exactly
+    let mut changed = original.clone();
+    changed
+        .data
+        .insert("noise.sky".to_string(), DmapField::from(1e-9_f32));
+    assert!(!changed.approx_eq(original, 1e-6, 1.0));
+
+    // Missing fields are never equal
+    let mut missing = original.clone();
+    missing.data.shift_remove("p_l");
+    assert!(!missing.approx_eq(original, 1.0, 1.0));
+}
+
+#[test]
+fn build_manifest() {
+    for ext in ["iqdat", "rawacf", "fitacf", "grid", "map", "snd"] {
+        let path = PathBuf::from(format!("tests/test_files/test.{ext}"));
+        let entries = manifest::<GenericRecord>(&path).expect("Unable to build manifest");
+        assert_eq!(
+            entries.len(),
+            count_records(&path).expect("Unable to count records")
+        );
+        assert!(entries.iter().all(|x| x.time.is_some()));
+    }
+
+    // Offsets and scalars match those of the records
+    let path = PathBuf::from("tests/test_files/test.fitacf.bz2");
+    let entries = manifest::<FitacfRecord>(&path).expect("Unable to build manifest");
+    let data = FitacfRecord::read_file(&path).expect("Unable to read test.fitacf.bz2");
+    let first_size = data[0].to_bytes().expect("Unable to convert record").len() as u64;
+    assert_eq!(
+        entries.iter().map(|x| x.byte_offset).collect::<Vec<_>>(),
+        vec![0, first_size]
+    );
+    for (i, (entry, rec)) in entries.iter().zip(data.iter()).enumerate() {
+        assert_eq!(entry.index, i);
+        assert_eq!(entry.bmnum, rec.bmnum().ok());
+        assert_eq!(entry.time, record_time(rec.data()).ok());
+    }
+
+    // Grid records have no beam, and a vector of stations rather than a single station
+    let path = PathBuf::from("tests/test_files/test.grid");
+    let entries = manifest::<GridRecord>(&path).expect("Unable to build manifest");
+    assert!(entries
+        .iter()
+        .all(|x| x.bmnum.is_none() && x.stid.is_none()));
+}
+
+#[test]
+fn read_in_thread_pool() {
+    let path = PathBuf::from("tests/test_files/test.rawacf");
+    let tempfile = PathBuf::from("tests/test_files/pool.rawacf");
+    let data = RawacfRecord::read_file(&path).expect("Unable to read test.rawacf");
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(2)
+        .build()
+        .expect("Unable to build thread pool");
+    let in_pool = RawacfRecord::read_file_in_pool(&path, &pool).expect("Unable to read in pool");
+    assert_eq!(in_pool, data);
+    assert_eq!(pool.install(rayon::current_num_threads), 2);
+
+    let opts = ReadOptions {
+        threads: Some(2),
+        ..Default::default()
+    };
+    let with_opts: Vec<RawacfRecord> =
+        read_file_with(&path, &opts).expect("Unable to read with 2 threads");
+    assert_eq!(with_opts, data);
+
+    let opts = WriteOptions {
+        mode: WriteMode::Overwrite,
+        threads: Some(2),
+        ..Default::default()
+    };
+    write_records_opts(data.clone(), &tempfile, &opts).expect("Unable to write pool.rawacf");
+    assert_eq!(
+        RawacfRecord::read_file(&tempfile).expect("Unable to read pool.rawacf"),
+        data
+    );
+    remove_file(&tempfile).expect("Unable to delete pool.rawacf");
+}
+
+#[test]
+fn vector_axis_order() {
+    let path = PathBuf::from("tests/test_files/test.rawacf");
+    let data = RawacfRecord::read_file(&path).expect("Unable to read test.rawacf");
+    for rec in data.iter() {
+        let mplgs = rec.mplgs().expect("No mplgs") as usize;
+        let num_ranges = rec.slist().expect("No slist").len();
+        let acfd = rec.acfd().expect("No acfd");
+        assert_eq!(acfd.shape(), [num_ranges, mplgs, 2]);
+        assert_eq!(rec.ltab().expect("No ltab").shape(), [mplgs + 1, 2]);
+
+        // The lag-0 power is the real part of lag 0, whose imaginary part is zero
+        let pwr0 = rec.pwr0().expect("No pwr0");
+        for (i, &range) in rec.slist().expect("No slist").iter().enumerate() {
+            assert_eq!(acfd[[i, 0, 0]], pwr0[[range as usize]]);
+            assert_eq!(acfd[[i, 0, 1]], 0.0);
+        }
+    }
+}
+
+#[test]
+fn complex_acf_view() {
+    let path = PathBuf::from("tests/test_files/test.rawacf");
+    let data = RawacfRecord::read_file(&path).expect("Unable to read test.rawacf");
+    let acfd = data[0].acfd().expect("No acfd");
+    let complex = acfd_complex(&data[0]).expect("Unable to convert acfd");
+    assert_eq!(complex.shape(), &acfd.shape()[..2]);
+    for ((i, j), x) in complex
+        .view()
+        .into_dimensionality::<Ix2>()
+        .expect("acfd is not 3D")
+        .indexed_iter()
+    {
+        assert_eq!(x.re, acfd[[i, j, 0]]);
+        assert_eq!(x.im, acfd[[i, j, 1]]);
+    }
+    if data[0].xcfd().is_ok() {
+        assert_eq!(
+            xcfd_complex(&data[0])
+                .expect("Unable to convert xcfd")
+                .shape(),
+            complex.shape()
+        );
+    }
+
+    // The last dimension must hold a real and imaginary part
+    let pwr0 = data[0].pwr0().expect("No pwr0");
+    assert!(to_complex(pwr0).is_err());
+}
+
+#[test]
+fn dump_cli() {
+    let dump = |args: &[&str]| {
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_dmap"))
+            .arg("dump")
+            .args(args)
+            .output()
+            .expect("Unable to run dmap");
+        (
+            output.status.code(),
+            String::from_utf8(output.stdout).expect("Output not UTF-8"),
+        )
+    };
+
+    let (code, out) = dump(&["tests/test_files/test.rawacf.bz2"]);
+    assert_eq!(code, Some(0));
+    assert!(out.starts_with("Format: rawacf\nRecord 0\n"));
+    assert!(out.contains("scalar stid SHORT"));
+    assert!(out.contains("vector acfd FLOAT [100, 22, 2]"));
+    assert!(out.contains("Record 1\n"));
+
+    let (code, out) = dump(&["--record", "1", "--full", "tests/test_files/test.fitacf"]);
+    assert_eq!(code, Some(0));
+    assert!(!out.contains("Record 0\n"));
+    assert!(out.contains("Record 1\n"));
+    let pwr0 = out
+        .lines()
+        .skip_while(|line| !line.starts_with("vector pwr0 "))
+        .nth(1)
+        .expect("No pwr0 data");
+    assert!(pwr0.starts_with('['));
+
+    let (code, _) = dump(&["--record", "99", "tests/test_files/test.rawacf"]);
+    assert_eq!(code, Some(1));
+    let (code, _) = dump(&[]);
+    assert_eq!(code, Some(2));
+}
+
+#[test]
+fn convert_and_cat_cli() {
+    let run = |args: &[&str]| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_dmap"))
+            .args(args)
+            .output()
+            .expect("Unable to run dmap")
+            .status
+            .code()
+    };
+    let dir = PathBuf::from("tests/test_files");
+
+    let compressed = dir.join("cli_convert.rawacf.bz2");
+    let _ = remove_file(&compressed);
+    assert_eq!(
+        run(&[
+            "convert",
+            "tests/test_files/test.rawacf",
+            compressed.to_str().unwrap()
+        ]),
+        Some(0)
+    );
+    assert_eq!(
+        RawacfRecord::read_file(&compressed).unwrap(),
+        RawacfRecord::read_file(&PathBuf::from("tests/test_files/test.rawacf")).unwrap()
+    );
+    // The output already exists
+    assert_eq!(
+        run(&[
+            "convert",
+            "tests/test_files/test.rawacf",
+            compressed.to_str().unwrap()
+        ]),
+        Some(1)
+    );
+    remove_file(&compressed).expect("Unable to delete cli_convert.rawacf.bz2");
+
+    let mismatched = dir.join("cli_convert.fitacf");
+    let _ = remove_file(&mismatched);
+    assert_eq!(
+        run(&[
+            "convert",
+            "tests/test_files/test.rawacf",
+            mismatched.to_str().unwrap()
+        ]),
+        Some(1)
+    );
+    assert!(!mismatched.exists());
+
+    let merged = dir.join("cli_cat.fitacf");
+    let _ = remove_file(&merged);
+    assert_eq!(
+        run(&[
+            "cat",
+            "tests/test_files/test.fitacf",
+            "tests/test_files/test.fitacf.bz2",
+            "-o",
+            merged.to_str().unwrap()
+        ]),
+        Some(0)
+    );
+    let recs = FitacfRecord::read_file(&PathBuf::from("tests/test_files/test.fitacf")).unwrap();
+    let merged_recs = FitacfRecord::read_file(&merged).unwrap();
+    assert_eq!(merged_recs.len(), 2 * recs.len());
+    assert_eq!(merged_recs[recs.len()..], recs[..]);
+    remove_file(&merged).expect("Unable to delete cli_cat.fitacf");
+
+    assert_eq!(
+        run(&[
+            "cat",
+            "tests/test_files/test.fitacf",
+            "tests/test_files/test.rawacf",
+            "-o",
+            merged.to_str().unwrap()
+        ]),
+        Some(1)
+    );
+    assert!(!merged.exists());
+    assert_eq!(run(&["cat", "tests/test_files/test.fitacf"]), Some(2));
+}
+
+#[test]
+fn file_time_bounds() {
+    for name in [
+        "test.iqdat",
+        "test.rawacf",
+        "test.fitacf",
+        "test.snd",
+        "test.grid",
+        "test.map",
+    ] {
+        for path in [
+            format!("tests/test_files/{name}"),
+            format!("tests/test_files/{name}.bz2"),
+        ] {
+            let path = PathBuf::from(path);
+            let recs = read_any(path.clone()).expect("Unable to read file");
+            let start = recs
+                .iter()
+                .map(|rec| record_time(rec.data()).unwrap())
+                .min();
+            let end = recs
+                .iter()
+                .map(|rec| record_end_time(rec.data()).unwrap())
+                .max();
+            let (first, last) = time_bounds(&path).expect("Unable to get time bounds");
+            assert_eq!(Some(first), start, "{name}");
+            assert_eq!(Some(last), end, "{name}");
+        }
+    }
+}
+
+/// A logger which keeps every message, for checking what is logged.
+struct CapturingLogger(Mutex<Vec<String>>);
+
+impl log::Log for CapturingLogger {
+    fn enabled(&self, _: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        self.0.lock().unwrap().push(record.args().to_string());
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: CapturingLogger = CapturingLogger(Mutex::new(Vec::new()));
+
+#[test]
+fn log_parsed_records() {
+    // Other tests may have installed the logger already
+    let _ = log::set_logger(&LOGGER);
+    log::set_max_level(log::LevelFilter::Debug);
+
+    let recs = FitacfRecord::read_file(&PathBuf::from("tests/test_files/test.fitacf"))
+        .expect("Unable to read file");
+    let expected = format!("Parsed {} records from ", recs.len());
+    assert!(LOGGER
+        .0
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|msg| msg.starts_with(&expected)));
+}
+
+#[test]
+fn write_listed_fields() {
+    let mut rec = GenericRecord {
+        data: IndexMap::new(),
+    };
+    rec.insert("a".to_string(), DmapField::from(1i16));
+    rec.insert(
+        "v".to_string(),
+        DmapField::from(array![1.0f32, 2.0].into_dyn()),
+    );
+    rec.insert("b".to_string(), DmapField::from(2i32));
+    rec.insert("unused".to_string(), DmapField::from(3i16));
+
+    let bytes = rec
+        .write_fields(&["v", "b", "a"])
+        .expect("Unable to write fields");
+    let mut expected: Vec<u8> = vec![];
+    expected.extend(65537i32.to_le_bytes());
+    expected.extend(47i32.to_le_bytes());
+    expected.extend(2i32.to_le_bytes());
+    expected.extend(1i32.to_le_bytes());
+    // Scalars in listed order, then vectors
+    expected.extend(b"b\0\x03");
+    expected.extend(2i32.to_le_bytes());
+    expected.extend(b"a\0\x02");
+    expected.extend(1i16.to_le_bytes());
+    expected.extend(b"v\0\x04");
+    expected.extend(1i32.to_le_bytes());
+    expected.extend(2i32.to_le_bytes());
+    expected.extend(1.0f32.to_le_bytes());
+    expected.extend(2.0f32.to_le_bytes());
+    assert_eq!(bytes, expected);
+
+    let read = GenericRecord::read_records_from_bytes(bytes).expect("Unable to read record");
+    assert_eq!(read[0].keys(), vec!["b", "a", "v"]);
+
+    assert!(rec.write_fields(&["a", "missing"]).is_err());
+    assert!(rec.write_fields(&["a", "a"]).is_err());
+}
+
+#[test]
+fn reject_duplicate_field() {
+    let mut bytes: Vec<u8> = vec![];
+    bytes.extend(65537i32.to_le_bytes());
+    bytes.extend(49i32.to_le_bytes());
+    bytes.extend(2i32.to_le_bytes());
+    bytes.extend(1i32.to_le_bytes());
+    for bmnum in [1i16, 3] {
+        bytes.extend(b"bmnum\0\x02");
+        bytes.extend(bmnum.to_le_bytes());
+    }
+    bytes.extend(b"v\0\x04");
+    bytes.extend(1i32.to_le_bytes());
+    bytes.extend(1i32.to_le_bytes());
+    bytes.extend(1.0f32.to_le_bytes());
+
+    match GenericRecord::parse_record(&mut Cursor::new(bytes.as_slice())) {
+        Err(e @ DmapError::InvalidField { .. }) => assert_eq!(
+            e.to_string(),
+            "Record 0, field 'bmnum' at byte 25: Duplicate field"
+        ),
+        x => panic!("Expected duplicate field error, got {x:?}"),
+    }
+    // Replacing the second bmnum with another name makes the record valid
+    let mut valid = bytes.clone();
+    valid[25..30].copy_from_slice(b"bmnux");
+    assert!(GenericRecord::parse_record(&mut Cursor::new(valid.as_slice())).is_ok());
+
+    // The error locates the duplicate within the file
+    let mut file = valid.clone();
+    file.extend(&bytes);
+    match GenericRecord::read_records(Cursor::new(file)) {
+        Err(DmapError::BadRecords(recs, e)) => {
+            assert_eq!(recs, vec![1]);
+            assert_eq!(
+                e.to_string(),
+                format!(
+                    "Record 1, field 'bmnum' at byte {}: Duplicate field",
+                    valid.len() + 25
+                )
+            );
+        }
+        x => panic!("Expected duplicate field error, got {x:?}"),
+    }
+}
+
+#[test]
+fn snd_optional_elevation_fields() {
+    let recs = SndRecord::read_file(&PathBuf::from("tests/test_files/test.snd"))
+        .expect("Unable to read test.snd");
+    let elevation = ["x_qflg", "phi0", "phi0_e"];
+    let opts = WriteOptions {
+        mode: WriteMode::Overwrite,
+        ..Default::default()
+    };
+
+    // Without the elevation fields, as written by RST when xcf is not set
+    let mut stripped: Vec<SndRecord> = recs
+        .iter()
+        .map(|rec| {
+            let mut data = rec.data().clone();
+            for name in elevation {
+                data.shift_remove(name);
+            }
+            data.insert("xcf".to_string(), DmapField::from(0i16));
+            SndRecord::try_from(&mut data).expect("Record without elevation fields is invalid")
+        })
+        .collect();
+    let tempfile = PathBuf::from("tests/test_files/snd_no_elevation.snd");
+    write_records_opts(stripped.clone(), &tempfile, &opts).expect("Unable to write records");
+    assert_eq!(
+        SndRecord::read_file(&tempfile).expect("Unable to read records"),
+        stripped
+    );
+
+    // With the elevation fields
+    write_records_opts(recs.clone(), &tempfile, &opts).expect("Unable to write records");
+    assert_eq!(
+        SndRecord::read_file(&tempfile).expect("Unable to read records"),
+        recs
+    );
+    remove_file(&tempfile).expect("Unable to delete file");
+
+    // An elevation field must have one entry per range gate in slist
+    let num_ranges = recs[0].slist().expect("No slist").len();
+    let mut data = stripped.swap_remove(0).inner();
+    data.insert(
+        "phi0".to_string(),
+        DmapField::from(ArrayD::<f32>::zeros(vec![num_ranges + 1])),
+    );
+    assert!(SndRecord::try_from(&mut data).is_err());
+}
+
+#[test]
+fn serialize_records_to_bytes() {
+    let recs = RawacfRecord::read_file(&PathBuf::from("tests/test_files/test.rawacf"))
+        .expect("Unable to read file");
+    for (ext, compression) in [
+        ("", Compression::None),
+        (".bz2", Compression::Bz2),
+        (".gz", Compression::Gz),
+        (".zst", Compression::Zstd),
+    ] {
+        let tempfile = PathBuf::from(format!("tests/test_files/to_bytes.rawacf{ext}"));
+        let _ = remove_file(&tempfile);
+        write_rawacf(recs.clone(), &tempfile).expect("Unable to write file");
+        let bytes = records_to_bytes(recs.clone(), compression).expect("Unable to convert records");
+        assert_eq!(
+            bytes,
+            read(&tempfile).expect("Unable to read file"),
+            "{compression}"
+        );
+        remove_file(&tempfile).expect("Unable to delete file");
+    }
+    assert_eq!("bz2".parse::<Compression>().unwrap(), Compression::Bz2);
+    assert!("bzip2".parse::<Compression>().is_err());
+}
+
+#[test]
+fn file_metadata() {
+    let formats = [
+        ("iqdat", FileFormat::Iqdat, Some("iqdata")),
+        ("rawacf", FileFormat::Rawacf, Some("rawacf")),
+        ("fitacf", FileFormat::Fitacf, Some("fitacf")),
+        ("grid", FileFormat::Grid, None),
+        ("map", FileFormat::Map, None),
+        ("snd", FileFormat::Snd, Some("snd")),
+    ];
+    for (ext, format, revision_prefix) in formats {
+        let path = PathBuf::from(format!("tests/test_files/test.{ext}"));
+        let recs = read_any(path.clone()).expect("Unable to read file");
+        let revision = revision_prefix.map(|prefix| {
+            let part = |name: &str| match recs[0].get(&format!("{prefix}.revision.{name}")) {
+                Some(DmapField::Scalar(x)) => {
+                    i32::try_from(x.clone()).expect("Revision is not an integer")
+                }
+                x => panic!("Unexpected revision field {x:?}"),
+            };
+            (part("major"), part("minor"))
+        });
+        for compressed in [false, true] {
+            let infile = if compressed {
+                PathBuf::from(format!("{}.bz2", path.display()))
+            } else {
+                path.clone()
+            };
+            let info = file_info(&infile).expect("Unable to get file info");
+            assert_eq!(info.format, format);
+            assert_eq!(info.num_records, recs.len());
+            assert_eq!(info.compressed, compressed);
+            assert_eq!(
+                info.uncompressed_bytes,
+                std::fs::metadata(&path).unwrap().len()
+            );
+            assert_eq!(info.endianness, Endianness::Little);
+            assert_eq!(info.time_bounds, Some(time_bounds(&infile).unwrap()));
+            assert_eq!(info.revision, revision, "{ext}");
+        }
+    }
+    let info = file_info(&PathBuf::from("tests/test_files/test_big_endian.snd"))
+        .expect("Unable to get file info");
+    assert_eq!(info.endianness, Endianness::Big);
+    assert_eq!(info.format, FileFormat::Snd);
+}
+
+#[test]
+fn write_multistream() {
+    // Two blocks, the second holding fewer records than the first
+    let mut recs = FitacfRecord::read_file(&PathBuf::from("tests/test_files/test.fitacf"))
+        .expect("Unable to read file");
+    recs.extend(recs.clone());
+    recs.pop();
+    let block_records = 2;
+    let tempfile = PathBuf::from("tests/test_files/multistream.fitacf.bz2");
+    let _ = remove_file(&tempfile);
+    write_records_multistream(recs.clone(), &tempfile, block_records)
+        .expect("Unable to write file");
+
+    // Each bzip2 stream starts with a header followed by the magic number of its first block
+    let bytes = read(&tempfile).expect("Unable to read file");
+    let num_streams = bytes.windows(10).filter(|x| x == b"BZh91AY&SY").count();
+    assert_eq!(num_streams, 2);
+    assert_eq!(
+        FitacfRecord::read_file(&tempfile).expect("Unable to read records"),
+        recs
+    );
+    assert_eq!(count_records(&tempfile).unwrap(), recs.len());
+    remove_file(&tempfile).expect("Unable to delete file");
+
+    let plain = PathBuf::from("tests/test_files/multistream.fitacf");
+    assert!(write_records_multistream(recs.clone(), &plain, block_records).is_err());
+    assert!(write_records_multistream(recs, &tempfile, 0).is_err());
+    assert!(!plain.exists() && !tempfile.exists());
+}
+
+#[test]
+fn rewrite_file() {
+    let original = PathBuf::from("tests/test_files/test.fitacf");
+    let decompressed = PathBuf::from("tests/test_files/rewrite.fitacf");
+    rewrite::<FitacfRecord>(
+        &PathBuf::from("tests/test_files/test.fitacf.bz2"),
+        &decompressed,
+    )
+    .expect("Unable to rewrite file");
+    assert_eq!(
+        FitacfRecord::read_file(&decompressed).unwrap(),
+        FitacfRecord::read_file(&original).unwrap()
+    );
+
+    // Rewriting in place replaces the file rather than appending to it
+    let bytes = read(&decompressed).unwrap();
+    rewrite::<FitacfRecord>(&decompressed, &decompressed).expect("Unable to rewrite file");
+    assert_eq!(read(&decompressed).unwrap(), bytes);
+
+    let recompressed = PathBuf::from("tests/test_files/rewrite.fitacf.gz");
+    rewrite::<FitacfRecord>(&decompressed, &recompressed).expect("Unable to rewrite file");
+    assert_eq!(sniff_format(&recompressed).unwrap(), FileFormat::Fitacf);
+    assert_eq!(
+        FitacfRecord::read_file(&recompressed).unwrap(),
+        FitacfRecord::read_file(&original).unwrap()
+    );
+    remove_file(&decompressed).expect("Unable to delete rewrite.fitacf");
+    remove_file(&recompressed).expect("Unable to delete rewrite.fitacf.gz");
+
+    assert!(rewrite::<RawacfRecord>(&original, &decompressed).is_err());
+    assert!(!decompressed.exists());
+}
+
+#[test]
+fn report_schema() {
+    let path = PathBuf::from("tests/test_files/test.rawacf");
+    let tempfile = PathBuf::from("tests/test_files/schema_report.rawacf");
+    let _ = remove_file(&tempfile);
+    let mut recs: Vec<IndexMap<String, DmapField>> = GenericRecord::read_file(&path)
+        .expect("Unable to read test.rawacf")
+        .into_iter()
+        .map(|rec| rec.inner())
+        .collect();
+    assert!(recs.len() > 1);
+    for rec in recs.iter_mut() {
+        rec.shift_remove("mplgexs");
+        rec.shift_remove("ifmode");
+        let acfd = rec["acfd"].clone();
+        rec.entry("xcfd".to_string()).or_insert(acfd);
+    }
+    recs[0].insert("bmnum".to_string(), DmapField::from(3_i32));
+    recs[1].insert("extra".to_string(), DmapField::from(1_i8));
+    let num_recs = recs.len();
+    let recs = recs
+        .iter_mut()
+        .map(|rec| GenericRecord::new(rec).expect("Unable to create record"))
+        .collect();
+    write_dmap(recs, &tempfile).expect("Unable to write file");
+
+    let report = schema_report::<RawacfRecord>(&tempfile).expect("Unable to build report");
+    assert_eq!(report.num_records, num_recs);
+    assert!(report.required.values().all(|&n| n == num_recs));
+    assert_eq!(report.optional["xcfd"], num_recs);
+    assert_eq!(report.optional["mplgexs"], 0);
+    assert_eq!(report.optional["ifmode"], 0);
+    assert_eq!(report.unknown.len(), 1);
+    assert_eq!(report.unknown["extra"], 1);
+    assert_eq!(
+        report.mismatches,
+        vec![TypeMismatch {
+            name: "bmnum".to_string(),
+            expected: "scalar SHORT".to_string(),
+            found: "scalar INT".to_string(),
+            num_records: 1,
+        }]
+    );
+    assert!(RawacfRecord::read_file(&tempfile).is_err());
+
+    let report = schema_report::<GenericRecord>(&tempfile).expect("Unable to build report");
+    assert!(report.required.is_empty() && report.optional.is_empty());
+    assert_eq!(report.unknown["extra"], 1);
+    assert!(report.mismatches.is_empty());
+    remove_file(&tempfile).expect("Unable to delete file");
+}
+
+#[test]
+fn read_boxed_reader() {
+    let path = PathBuf::from("tests/test_files/test.fitacf");
+    let data = FitacfRecord::read_file(&path).expect("Unable to read test.fitacf");
+    let bytes = read(&path).expect("Unable to read test.fitacf");
+
+    let reader: Box<dyn Read> = Box::new(Cursor::new(bytes.clone()));
+    let recs = FitacfRecord::read_records_boxed(reader).expect("Unable to read boxed reader");
+    assert_eq!(recs, data);
+
+    // Readers chosen at runtime can be composed before boxing
+    let compressed = read(PathBuf::from("tests/test_files/test.fitacf.bz2"))
+        .expect("Unable to read test.fitacf.bz2");
+    let reader: Box<dyn Read> = Box::new(MultiBzDecoder::new(Cursor::new(compressed)));
+    let recs = GenericRecord::read_records_boxed(reader).expect("Unable to read boxed reader");
+    assert_eq!(recs.len(), data.len());
+
+    let reader: Box<dyn Read> = Box::new(Cursor::new(bytes[..bytes.len() - 1].to_vec()));
+    assert!(FitacfRecord::read_records_boxed(reader).is_err());
+}
+
+#[test]
+fn three_dimensional_vector_layout() {
+    // Element [i, j, k] is 100i + 10j + k, so the last axis varies fastest
+    let cube = ArrayD::from_shape_fn(IxDyn(&[2, 3, 4]), |x| {
+        (100 * x[0] + 10 * x[1] + x[2]) as i32
+    });
+    let mut fields = IndexMap::from([
+        ("bmnum".to_string(), DmapField::from(3_i16)),
+        ("cube".to_string(), DmapField::from(cube.clone())),
+    ]);
+    let bytes = GenericRecord::new(&mut fields)
+        .expect("Unable to create record")
+        .to_bytes()
+        .expect("Unable to convert record");
+
+    // As written by RST, the dimensions are stored fastest-varying first and the data follows
+    // the memory order of the C array
+    let start = bytes
+        .windows(5)
+        .position(|x| x == b"cube\0")
+        .expect("Field name not found")
+        + 6;
+    let ints: Vec<i32> = bytes[start..]
+        .chunks_exact(4)
+        .map(|x| i32::from_le_bytes(x.try_into().unwrap()))
+        .collect();
+    assert_eq!(ints[..4], [3, 4, 3, 2]);
+    assert_eq!(ints[4..], cube.iter().copied().collect::<Vec<i32>>());
+    assert_eq!(ints[4..9], [0, 1, 2, 3, 10]);
+    assert_eq!(ints[4 + 12], 100);
+
+    // A column-major array is written in the same logical order
+    let fortran = ArrayD::from_shape_fn(IxDyn(&[2, 3, 4]).f(), |x| cube[x.clone()]);
+    let mut fields = IndexMap::from([
+        ("bmnum".to_string(), DmapField::from(3_i16)),
+        ("cube".to_string(), DmapField::from(fortran)),
+    ]);
+    let fortran_bytes = GenericRecord::new(&mut fields)
+        .expect("Unable to create record")
+        .to_bytes()
+        .expect("Unable to convert record");
+    assert_eq!(fortran_bytes, bytes);
+
+    let recs = GenericRecord::read_records_from_bytes(bytes).expect("Unable to read record");
+    let read_cube: ArrayD<i32> = recs[0]
+        .get(&"cube".to_string())
+        .expect("Field not found")
+        .clone()
+        .try_into()
+        .expect("Field is not an int vector");
+    assert_eq!(read_cube.shape(), [2, 3, 4]);
+    assert_eq!(read_cube[[0, 0, 0]], 0);
+    assert_eq!(read_cube[[0, 0, 3]], 3);
+    assert_eq!(read_cube[[0, 2, 1]], 21);
+    assert_eq!(read_cube[[1, 0, 0]], 100);
+    assert_eq!(read_cube[[1, 2, 3]], 123);
+    assert_eq!(read_cube, cube);
+}
+
+#[test]
+fn reject_mixed_format_batch() {
+    let tempfile = PathBuf::from("tests/test_files/mixed_batch.fitacf");
+    let _ = remove_file(&tempfile);
+    let mut recs: Vec<IndexMap<String, DmapField>> =
+        GenericRecord::read_file(&PathBuf::from("tests/test_files/test.fitacf"))
+            .expect("Unable to read test.fitacf")
+            .into_iter()
+            .map(|rec| rec.inner())
+            .collect();
+    let rawacf = GenericRecord::read_file(&PathBuf::from("tests/test_files/test.rawacf"))
+        .expect("Unable to read test.rawacf")
+        .remove(0)
+        .inner();
+    recs.insert(1, rawacf);
+    let num_recs = recs.len();
+
+    match try_write_fitacf(recs.clone(), &tempfile) {
+        Err(DmapError::BadRecords(indices, e)) => {
+            assert_eq!(indices, vec![1]);
+            let msg = e.to_string();
+            assert!(msg.contains("Record 1 has different fields"), "{msg}");
+            assert!(
+                msg.contains("\"xcfd\"") && msg.contains("\"noise.sky\""),
+                "{msg}"
+            );
+        }
+        x => panic!("Expected BadRecords, got {x:?}"),
+    }
+    assert!(!tempfile.exists());
+
+    // Optional fields may differ between records of the same format
+    recs.remove(1);
+    recs[0].shift_remove("elv");
+    try_write_fitacf(recs.clone(), &tempfile).expect("Unable to write fitacf records");
+    assert_eq!(
+        FitacfRecord::read_file(&tempfile)
+            .expect("Unable to read file")
+            .len(),
+        num_recs - 1
+    );
+    remove_file(&tempfile).expect("Unable to delete file");
+
+    // Generic records have no optional fields, so every field must match the first record
+    let mut recs: Vec<IndexMap<String, DmapField>> =
+        GenericRecord::read_file(&PathBuf::from("tests/test_files/test.fitacf"))
+            .expect("Unable to read test.fitacf")
+            .into_iter()
+            .map(|rec| rec.inner())
+            .collect();
+    let rawacf = GenericRecord::read_file(&PathBuf::from("tests/test_files/test.rawacf"))
+        .expect("Unable to read test.rawacf")
+        .remove(0)
+        .inner();
+    recs.push(rawacf);
+    let last = recs.len() - 1;
+    match try_write_dmap(recs.clone(), &tempfile) {
+        Err(DmapError::BadRecords(indices, e)) => {
+            assert_eq!(indices, vec![last]);
+            let msg = e.to_string();
+            assert!(
+                msg.contains(&format!("Record {last} has different fields")),
+                "{msg}"
+            );
+        }
+        x => panic!("Expected BadRecords, got {x:?}"),
+    }
+    assert!(!tempfile.exists());
+
+    recs.pop();
+    try_write_dmap(recs.clone(), &tempfile).expect("Unable to write generic records");
+    assert_eq!(
+        GenericRecord::read_file(&tempfile)
+            .expect("Unable to read file")
+            .len(),
+        recs.len()
+    );
+    remove_file(&tempfile).expect("Unable to delete file");
+}
+
+#[test]
+fn record_field_types() {
+    let path = PathBuf::from("tests/test_files/test.rawacf");
+    let data = GenericRecord::read_file(&path).expect("Unable to read test.rawacf");
+    let types = field_types(data[0].data());
+    assert_eq!(types.len(), data[0].data().len());
+    assert_eq!(types["stid"], "SHORT");
+    assert_eq!(types["origin.time"], "STRING");
+    assert_eq!(types["acfd"], "FLOAT");
+    assert_eq!(types["ptab"], "SHORT");
+
+    let fields = IndexMap::from([
+        ("stid".to_string(), DmapField::from(65_i8)),
+        (
+            "v".to_string(),
+            DmapField::from(array![1.0_f64, 2.0].into_dyn()),
+        ),
+    ]);
+    let types = field_types(&fields);
+    assert_eq!(types["stid"], "CHAR");
+    assert_eq!(types["v"], "DOUBLE");
+}
+
+#[test]
+fn parse_record_times() {
+    let path = PathBuf::from("tests/test_files/test.rawacf");
+    let data = RawacfRecord::read_file(&path).expect("Unable to read test.rawacf");
+    let origin = parse_origin_time(data[0].data()).expect("Unable to parse origin.time");
+    assert_eq!(origin, datetime!(2021-06-07 18:01:00 UTC));
+
+    let path = PathBuf::from("tests/test_files/test.fitacf");
+    let data = FitacfRecord::read_file(&path).expect("Unable to read test.fitacf");
+    let origin = parse_origin_time(data[0].data()).expect("Unable to parse origin.time");
+    assert_eq!(origin, datetime!(2022-12-12 22:06:12 UTC));
+    for rec in data.iter() {
+        let time = record_time(rec.data()).expect("Unable to get record time");
+        assert_eq!(time.year(), rec.time_yr().unwrap() as i32);
+        assert_eq!(time.second(), rec.time_sc().unwrap() as u8);
+        assert_eq!(time.microsecond(), rec.time_us().unwrap() as u32);
+        assert_eq!(record_timestamp(rec.data()).ok(), Some(time));
+    }
+
+    // Grid and map records use their start and end fields, including fractional seconds
+    let path = PathBuf::from("tests/test_files/test.grid");
+    let grid = GenericRecord::read_file(&path).expect("Unable to read test.grid");
+    let start = record_time(grid[0].data()).expect("Unable to get record time");
+    let end = record_end_time(grid[0].data()).expect("Unable to get record time");
+    assert!(start < end);
+    let mut fields = grid[0].data().clone();
+    fields.insert("start.second".to_string(), DmapField::from(1.25_f64));
+    let time = record_time(&fields).expect("Unable to get record time");
+    assert_eq!((time.second(), time.millisecond()), (1, 250));
+    fields.insert("start.second".to_string(), DmapField::from(60.0_f64));
+    assert!(matches!(
+        record_time(&fields),
+        Err(DmapError::InvalidScalar(_))
+    ));
+
+    let mut fields = data[0].data().clone();
+    fields.insert("time.us".to_string(), DmapField::from(1_000_000_i32));
+    assert!(matches!(
+        record_time(&fields),
+        Err(DmapError::InvalidScalar(_))
+    ));
+    fields.insert("time.us".to_string(), DmapField::from(0_i32));
+    fields.insert("time.mo".to_string(), DmapField::from("June".to_string()));
+    assert!(matches!(
+        record_time(&fields),
+        Err(DmapError::InvalidScalar(_))
+    ));
+    fields.insert("time.mo".to_string(), DmapField::from(13_i16));
+    assert!(matches!(
+        record_time(&fields),
+        Err(DmapError::InvalidScalar(_))
+    ));
+    fields.shift_remove("time.mo");
+    assert!(matches!(
+        record_time(&fields),
+        Err(DmapError::InvalidRecord(_))
+    ));
+    fields.insert(
+        "origin.time".to_string(),
+        DmapField::from("2021-06-07".to_string()),
+    );
+    assert!(matches!(
+        parse_origin_time(&fields),
+        Err(DmapError::InvalidScalar(_))
+    ));
+}
+
+#[test]
+fn read_truncated_snd_lax() {
+    let path = PathBuf::from("tests/test_files/test.snd");
+    let data = SndRecord::read_file_indexed(&path).expect("Unable to read test.snd");
+    let (last_offset, _) = data.last().expect("No records in test.snd");
+    let (recs, bad_byte) = SndRecord::read_file_lax(&path).expect("Unable to read test.snd");
+    assert_eq!(recs.len(), data.len());
+    assert_eq!(bad_byte, None);
+
+    // Cut the last record short, as if the file was truncated while being written
+    let mut bytes = read(&path).expect("Unable to read test.snd");
+    bytes.truncate(last_offset + 100);
+    let tempfile = PathBuf::from("tests/test_files/truncated.snd");
+    File::create(&tempfile)
+        .and_then(|mut f| f.write_all(&bytes))
+        .expect("Unable to write truncated.snd");
+    let (recs, bad_byte) = SndRecord::read_file_lax(&tempfile).expect("Unable to read file");
+    remove_file(&tempfile).expect("Unable to delete file");
+    assert_eq!(bad_byte, Some(*last_offset));
+    let expected: Vec<SndRecord> = data[..data.len() - 1]
+        .iter()
+        .map(|(_, rec)| rec.clone())
+        .collect();
+    assert_eq!(recs, expected);
+}
+
+#[test]
+fn read_unchecked_matches_checked() {
+    let path = PathBuf::from("tests/test_files/test.fitacf");
+    let checked = FitacfRecord::read_file(&path).expect("Unable to read test.fitacf");
+    let unchecked: Vec<FitacfRecord> =
+        read_file_unchecked(&path).expect("Unable to read test.fitacf");
+    assert_eq!(unchecked, checked);
+
+    let path = PathBuf::from("tests/test_files/test.rawacf.bz2");
+    let checked = RawacfRecord::read_file(&path).expect("Unable to read test.rawacf.bz2");
+    let unchecked: Vec<RawacfRecord> =
+        read_file_unchecked(&path).expect("Unable to read test.rawacf.bz2");
+    assert_eq!(unchecked, checked);
+}
+
+#[test]
+fn string_fields_round_trip() {
+    let path = PathBuf::from("tests/test_files/test.fitacf");
+    let mut rec = GenericRecord::read_file(&path).expect("Unable to read test.fitacf")[0].clone();
+    let strings = [
+        String::new(),
+        "x".repeat(4096),
+        "make_fit -new -fitacf-version 3.0 20190831.0000.00.sas.rawacf".to_string(),
+    ];
+    for s in strings {
+        rec.data
+            .insert("origin.command".to_string(), DmapField::from(s.clone()));
+        rec.data
+            .insert("combf".to_string(), DmapField::from(s.clone()));
+        let mut buffer: Vec<u8> = vec![];
+        write_records_to(vec![rec.clone()], &mut buffer).expect("Unable to write to buffer");
+        let new_recs =
+            GenericRecord::read_records_from_bytes(buffer).expect("Unable to parse buffer");
+        assert_eq!(new_recs, vec![rec.clone()]);
+        assert_eq!(new_recs[0].get_as::<&str>("origin.command").unwrap(), s);
+    }
+
+    // Strings are read up to the first null terminator, which must be present
+    assert_eq!(String::from_bytes(b"a b\0").unwrap(), "a b");
+    assert_eq!(String::from_bytes(b"\0").unwrap(), "");
+    assert_eq!(String::from_bytes(b"a\0\0").unwrap(), "a");
+    assert!(String::from_bytes(b"abc").is_err());
+
+    // A string field with no terminator before the end of its record, or which is not valid
+    // UTF-8, is rejected when parsed from a byte stream
+    let mut fields = IndexMap::from([
+        ("bmnum".to_string(), DmapField::from(3_i16)),
+        ("origin.command".to_string(), DmapField::from("abc")),
+        (
+            "v".to_string(),
+            DmapField::from(array![1.0_f32, 2.0].into_dyn()),
+        ),
+    ]);
+    let rec = GenericRecord::new(&mut fields).expect("Unable to create record");
+    let bytes = rec.to_bytes().expect("Unable to convert record");
+    let start = bytes
+        .windows(4)
+        .position(|x| x == b"abc\0")
+        .expect("String not found");
+    let parsed = GenericRecord::read_records_from_bytes(bytes.clone()).expect("Unable to parse");
+    assert_eq!(parsed, vec![rec]);
+
+    let mut unterminated = bytes[..start + 3].to_vec();
+    let size = unterminated.len() as i32;
+    unterminated[4..8].copy_from_slice(&size.to_le_bytes());
+    let err = GenericRecord::read_records_from_bytes(unterminated)
+        .expect_err("Unterminated string was accepted");
+    assert!(
+        err.to_string().contains("String is improperly terminated"),
+        "{err}"
+    );
+
+    let mut invalid = bytes.clone();
+    invalid[start] = 0xff;
+    let err = GenericRecord::read_records_from_bytes(invalid)
+        .expect_err("Invalid UTF-8 string was accepted");
+    assert!(
+        err.to_string().contains("Cannot convert bytes to String"),
+        "{err}"
+    );
+}
+
+#[test]
+fn validate_truncated_file() {
+    let path = PathBuf::from("tests/test_files/test.fitacf");
+    let tempfile = PathBuf::from("tests/test_files/truncated.fitacf");
+    assert_eq!(
+        FitacfRecord::validate_file(&path).expect("test.fitacf is invalid"),
+        2
+    );
+
+    // The error identifies the truncated second record by its starting byte
+    let bytes = read(&path).expect("Unable to read test.fitacf");
+    let first_size = i32::from_le_bytes(bytes[4..8].try_into().unwrap());
+    let second_size = i32::from_le_bytes(
+        bytes[first_size as usize + 4..first_size as usize + 8]
+            .try_into()
+            .unwrap(),
+    );
+    File::create(&tempfile)
+        .and_then(|mut f| f.write_all(&bytes[..bytes.len() - 10]))
+        .expect("Unable to write truncated.fitacf");
+    let err = FitacfRecord::validate_file(&tempfile).expect_err("Truncated file is valid");
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "Record at byte {first_size} is truncated: its size is {second_size} bytes but only {} remain",
+            bytes.len() - 10 - first_size as usize
+        )
+    );
+    remove_file(&tempfile).expect("Unable to delete truncated.fitacf");
+}
+
+#[test]
+fn count_records_in_test_files() {
+    for ext in ["iqdat", "rawacf", "fitacf", "grid", "map", "snd"] {
+        for name in [format!("test.{ext}"), format!("test.{ext}.bz2")] {
+            let path = PathBuf::from(format!("tests/test_files/{name}"));
+            let expected = GenericRecord::read_file(&path)
+                .expect("Unable to read file")
+                .len();
+            assert_eq!(
+                count_records(&path).expect("Unable to count records"),
+                expected
+            );
+        }
+    }
+
+    // A truncated final record is an error
+    let path = PathBuf::from("tests/test_files/test.rawacf");
+    let tempfile = PathBuf::from("tests/test_files/truncated.rawacf");
+    let bytes = read(&path).expect("Unable to read test.rawacf");
+    File::create(&tempfile)
+        .and_then(|mut f| f.write_all(&bytes[..bytes.len() - 1]))
+        .expect("Unable to write truncated.rawacf");
+    assert!(count_records(&tempfile).is_err());
+    remove_file(&tempfile).expect("Unable to delete truncated.rawacf");
+}
+
+#[test]
+fn uncompressed_size_of_test_files() {
+    for ext in ["rawacf", "fitacf", "map"] {
+        let path = PathBuf::from(format!("tests/test_files/test.{ext}"));
+        let path_bz2 = PathBuf::from(format!("tests/test_files/test.{ext}.bz2"));
+        let mut decompressed = vec![];
+        MultiBzDecoder::new(File::open(&path_bz2).expect("Unable to open file"))
+            .read_to_end(&mut decompressed)
+            .expect("Unable to decompress file");
+        let size = read(&path).expect("Unable to read file").len() as u64;
+        assert_eq!(
+            uncompressed_size(&path_bz2).expect("Unable to get size"),
+            decompressed.len() as u64
+        );
+        assert_eq!(uncompressed_size(&path).expect("Unable to get size"), size);
+    }
+}
+
+#[test]
+fn read_limited_records() {
+    let path = PathBuf::from("tests/test_files/test.fitacf");
+    let data = FitacfRecord::read_file(&path).expect("Unable to read test.fitacf");
+    assert!(data.len() > 1);
+
+    let first = FitacfRecord::read_file_limited(&path, 1).expect("Unable to read test.fitacf");
+    assert_eq!(first, data[..1]);
+    let none = FitacfRecord::read_file_limited(&path, 0).expect("Unable to read test.fitacf");
+    assert!(none.is_empty());
+    let all =
+        FitacfRecord::read_file_limited(&path, data.len() + 1).expect("Unable to read test.fitacf");
+    assert_eq!(all, data);
+
+    // As done for Python file objects, which are read into memory first
+    let bytes = read(&path).expect("Unable to read test.fitacf");
+    let first: Vec<FitacfRecord> = RecordIter::new(Cursor::new(bytes))
+        .take(1)
+        .collect::<Result<_, _>>()
+        .expect("Unable to parse records");
+    assert_eq!(first, data[..1]);
+}
+
+#[test]
+fn build_and_write_rawacf() {
+    let tempfile = PathBuf::from("tests/test_files/built.rawacf");
+
+    // Required fields which are not set are all listed
+    let err = RawacfRecord::builder()
+        .stid(65)
+        .build()
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("\"cp\"") && err.contains("\"acfd\"") && !err.contains("\"stid\""));
+
+    let rec = RawacfRecord::builder()
+        .radar_revision_major(1)
+        .radar_revision_minor(0)
+        .origin_code(0)
+        .origin_time("Thu Jan  1 00:00:00 1970")
+        .origin_command("test")
+        .cp(153)
+        .stid(65)
+        .time_yr(1970)
+        .time_mo(1)
+        .time_dy(1)
+        .time_hr(0)
+        .time_mt(0)
+        .time_sc(0)
+        .time_us(0)
+        .txpow(9000)
+        .nave(20)
+        .atten(0)
+        .lagfr(1200)
+        .smsep(300)
+        .ercod(0)
+        .stat_agc(0)
+        .stat_lopwr(0)
+        .noise_search(1.0)
+        .noise_mean(1.0)
+        .channel(0)
+        .bmnum(7)
+        .bmazm(0.0)
+        .scan(1)
+        .offset(0)
+        .rxrise(100)
+        .intt_sc(3)
+        .intt_us(0)
+        .txpl(300)
+        .mpinc(1500)
+        .mppul(8)
+        .mplgs(2)
+        .nrang(2)
+        .frang(180)
+        .rsep(45)
+        .xcf(0)
+        .tfreq(10500)
+        .mxpwr(1073741824)
+        .lvmax(20000)
+        .combf("test")
+        .rawacf_revision_major(1)
+        .rawacf_revision_minor(0)
+        .thr(0.0)
+        .ptab(array![0_i16, 1].into_dyn())
+        .ltab(array![[0_i16, 0], [0, 1]].into_dyn())
+        .pwr0(array![1.0_f32, 2.0].into_dyn())
+        .slist(array![0_i16, 1].into_dyn())
+        .acfd(ArrayD::zeros(vec![2, 2, 2]))
+        .build()
+        .expect("Unable to build record");
+    assert_eq!(rec.stid().expect("Unable to get stid"), 65);
+
+    write_rawacf(vec![rec.clone()], &tempfile).expect("Unable to write built.rawacf");
+    let data = RawacfRecord::read_file(&tempfile).expect("Unable to read built.rawacf");
+    assert_eq!(data, vec![rec]);
+
+    remove_file(&tempfile).expect("Unable to delete built.rawacf");
+}
+
+#[test]
+fn write_preserving_field_order() {
+    let path = PathBuf::from("tests/test_files/test.rawacf");
+    let tempfile = PathBuf::from("tests/test_files/ordered.rawacf");
+    let bytes = read(&path).expect("Unable to read test.rawacf");
+    let mut data = RawacfRecord::read_file(&path).expect("Unable to read test.rawacf");
+
+    // The fields are written in the order they were read, reproducing the file exactly
+    let opts = WriteOptions {
+        mode: WriteMode::Overwrite,
+        preserve_order: true,
+        ..Default::default()
+    };
+    write_records_opts(data.clone(), &tempfile, &opts).expect("Unable to write ordered.rawacf");
+    assert_eq!(
+        read(&tempfile).expect("Unable to read ordered.rawacf"),
+        bytes
+    );
+
+    // The canonical order does not depend on the order of the fields in the record
+    let canonical = data[0].to_bytes().expect("Unable to convert record");
+    data[0].data.reverse();
+    assert_eq!(
+        data[0].to_bytes().expect("Unable to convert record"),
+        canonical
+    );
+    assert_ne!(
+        data[0]
+            .to_bytes_in_order()
+            .expect("Unable to convert record"),
+        canonical
+    );
+
+    remove_file(&tempfile).expect("Unable to delete ordered.rawacf");
+}
+
+#[test]
+fn recover_from_corrupt_record() {
+    let path = PathBuf::from("tests/test_files/test.fitacf");
+    let data = FitacfRecord::read_file(&path).expect("Unable to read test.fitacf");
+    let bytes = read(&path).expect("Unable to read test.fitacf");
+    let first_size = i32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+
+    // Give the first scalar of a copy of the first record an invalid type key
+    let mut corrupt = bytes[..first_size].to_vec();
+    let key_pos = 16 + corrupt[16..].iter().position(|&x| x == 0).unwrap() + 1;
+    corrupt[key_pos] = 127;
+    let buffer = [&bytes[..first_size], &corrupt, &bytes[first_size..]].concat();
+
+    let (recs, errors) = FitacfRecord::read_records_recover(Cursor::new(buffer.clone()));
+    assert_eq!(recs, data);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].0, 1);
+
+    // The strict reader still fails
+    assert!(FitacfRecord::read_records(Cursor::new(buffer)).is_err());
+}
+
+#[test]
+fn locate_invalid_field() {
+    let path = PathBuf::from("tests/test_files/test.fitacf");
+    let bytes = read(&path).expect("Unable to read test.fitacf");
+    let first_size = i32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+
+    // Give the first scalar of the second record an invalid type key
+    let mut buffer = bytes.clone();
+    let name_start = first_size + 16;
+    let name_end = name_start + buffer[name_start..].iter().position(|&x| x == 0).unwrap();
+    let name = String::from_utf8(buffer[name_start..name_end].to_vec()).unwrap();
+    buffer[name_end + 1] = 127;
+
+    let check = |e: &DmapError| match e {
+        DmapError::InvalidField {
+            record,
+            field,
+            byte,
+            ..
+        } => {
+            assert_eq!(*record, 1);
+            assert_eq!(field, &name);
+            assert_eq!(*byte, name_start as u64);
+            assert_eq!(
+                e.to_string(),
+                format!("Record 1, field '{name}' at byte {name_start}: Invalid type key 127")
+            );
+        }
+        e => panic!("Unexpected error {e}"),
+    };
+    let (_, errors) = FitacfRecord::read_records_recover(Cursor::new(buffer.clone()));
+    check(&errors[0].1);
+    match FitacfRecord::read_records(Cursor::new(buffer.clone())) {
+        Err(DmapError::BadRecords(recs, e)) => {
+            assert_eq!(recs, vec![1]);
+            check(&e);
+        }
+        x => panic!("Unexpected result {x:?}"),
+    }
+    let mut records = RecordIter::<FitacfRecord>::new(Cursor::new(buffer));
+    assert!(records.next().unwrap().is_ok());
+    check(&records.next().unwrap().unwrap_err());
+}
+
+#[test]
+fn copy_shared_header() {
+    let rawacf = RawacfRecord::read_file(&PathBuf::from("tests/test_files/test.rawacf"))
+        .expect("Unable to read test.rawacf");
+    let fitacf = FitacfRecord::read_file(&PathBuf::from("tests/test_files/test.fitacf"))
+        .expect("Unable to read test.fitacf");
+
+    let header = rawacf[0].shared_header();
+    for key in [
+        "stid",
+        "time.yr",
+        "time.us",
+        "bmnum",
+        "cp",
+        "channel",
+        "origin.command",
+    ] {
+        assert_eq!(header.get(key), rawacf[0].get(&key.to_string()), "{key}");
+    }
+    for key in [
+        "rawacf.revision.major",
+        "thr",
+        "mplgs",
+        "acfd",
+        "ptab",
+        "slist",
+    ] {
+        assert!(!header.contains_key(key), "{key}");
+    }
+
+    // The header combined with the FITACF-specific fields of a FITACF record makes a valid record
+    let fitacf_header = fitacf[0].shared_header();
+    assert_eq!(
+        header.keys().collect::<Vec<_>>(),
+        fitacf_header.keys().collect::<Vec<_>>()
+    );
+    let mut fields = header.clone();
+    fields.extend(
+        fitacf[0]
+            .data
+            .iter()
+            .filter(|(k, _)| !fitacf_header.contains_key(*k))
+            .map(|(k, v)| (k.clone(), v.clone())),
+    );
+    let rec = FitacfRecord::new(&mut fields).expect("Unable to make FITACF record");
+    assert_eq!(rec.stid().unwrap(), rawacf[0].stid().unwrap());
+}
+
+#[test]
+fn read_with_type_overrides() {
+    let path = PathBuf::from("tests/test_files/test.rawacf");
+    let tempfile = PathBuf::from("tests/test_files/int_tfreq.rawacf");
+    let data = RawacfRecord::read_file(&path).expect("Unable to read test.rawacf");
+
+    // Write a file with tfreq stored as an Int rather than a Short
+    let mut generic_recs = GenericRecord::read_file(&path).expect("Unable to read test.rawacf");
+    for rec in generic_recs.iter_mut() {
+        let tfreq = rec.get_as::<i16>("tfreq").expect("Missing tfreq");
+        rec.data
+            .insert("tfreq".to_string(), DmapField::from(tfreq as i32));
+    }
+    write_dmap(generic_recs, &tempfile).expect("Unable to write int_tfreq.rawacf");
+    assert!(RawacfRecord::read_file(&tempfile).is_err());
+
+    let mut opts = ReadOptions::default();
+    opts.type_overrides.insert("tfreq".to_string(), Type::Int);
+    let recs: Vec<RawacfRecord> =
+        read_file_with(&tempfile, &opts).expect("Unable to read with overrides");
+    assert_eq!(recs, data);
+    assert_eq!(recs[0].tfreq().unwrap(), data[0].tfreq().unwrap());
+
+    // Overrides for other fields or types do not relax the check
+    for (name, data_type) in [("tfreq", Type::Long), ("cp", Type::Int)] {
+        let mut opts = ReadOptions::default();
+        opts.type_overrides.insert(name.to_string(), data_type);
+        assert!(read_file_with::<RawacfRecord>(&tempfile, &opts).is_err());
+    }
+    remove_file(&tempfile).expect("Unable to delete int_tfreq.rawacf");
+}
+
+#[test]
+fn extract_columns() {
+    let path = PathBuf::from("tests/test_files/test.fitacf");
+    let recs: Vec<IndexMap<String, DmapField>> = FitacfRecord::read_file(&path)
+        .expect("Unable to read test.fitacf")
+        .into_iter()
+        .map(|rec| rec.inner())
+        .collect();
+
+    let bmnum: Vec<i16> = extract_scalar(&recs, "bmnum").expect("Unable to extract bmnum");
+    let expected: Vec<i16> = recs
+        .iter()
+        .map(|rec| rec["bmnum"].clone().try_into().unwrap())
+        .collect();
+    assert_eq!(bmnum, expected);
+    let v: Vec<ArrayD<f32>> = extract_vector(&recs, "v").expect("Unable to extract v");
+    assert_eq!(v.len(), recs.len());
+    assert_eq!(DmapField::from(v[1].clone()), recs[1]["v"]);
+
+    // Errors name the first record with a missing or mistyped field
+    let mut bad_recs = recs.clone();
+    bad_recs[1].swap_remove("bmnum");
+    let err = extract_scalar::<i16>(&bad_recs, "bmnum").expect_err("Missing field extracted");
+    assert_eq!(err.to_string(), "Record 1: Field bmnum not in record");
+    let err = extract_scalar::<i32>(&recs, "bmnum").expect_err("Wrong type extracted");
+    assert_eq!(
+        err.to_string(),
+        "Record 0: Field bmnum is a scalar of type SHORT, expected a scalar of type INT"
+    );
+    assert!(extract_vector::<f64>(&recs, "v").is_err());
+}
+
+#[test]
+fn summarize_rawacf_record() {
+    let path = PathBuf::from("tests/test_files/test.rawacf");
+    let rec = &RawacfRecord::read_file(&path).expect("Unable to read test.rawacf")[0];
+    let summary = rec.summary();
+    let lines: Vec<&str> = summary.lines().collect();
+    assert_eq!(lines.len(), rec.keys().len());
+
+    let acfd = rec.acfd().expect("Missing acfd");
+    let shape = acfd.shape();
+    assert!(lines.contains(&format!("vector acfd FLOAT {shape:?}").as_str()));
+    assert!(lines.contains(&format!("scalar stid SHORT {}", rec.stid().unwrap()).as_str()));
+    assert!(lines.contains(&format!("scalar combf STRING {}", rec.combf().unwrap()).as_str()));
+}
+
+#[test]
+fn diff_changed_scalar() {
+    let path = PathBuf::from("tests/test_files/test.fitacf");
+    let tempfile = PathBuf::from("tests/test_files/diff.fitacf");
+    assert_eq!(
+        diff_files::<FitacfRecord>(&path, &path, None).unwrap(),
+        vec![]
+    );
+
+    let mut recs = FitacfRecord::read_file(&path).expect("Unable to read test.fitacf");
+    let tfreq = recs[1].tfreq().unwrap();
+    let noise = recs[1].noise_sky().unwrap();
+    recs[1]
+        .data
+        .insert("tfreq".to_string(), DmapField::from(tfreq + 1));
+    recs[1]
+        .data
+        .insert("noise.sky".to_string(), DmapField::from(noise + 0.001));
+    write_fitacf(recs, &tempfile).expect("Unable to write diff.fitacf");
+
+    let diffs = diff_files::<FitacfRecord>(&path, &tempfile, None).expect("Unable to diff files");
+    assert_eq!(diffs.len(), 1);
+    assert_eq!(diffs[0].index, 1);
+    assert_eq!(
+        diffs[0].fields,
+        vec![
+            FieldDiff::Scalar {
+                name: "tfreq".to_string(),
+                first: DmapScalar::Short(tfreq),
+                second: DmapScalar::Short(tfreq + 1),
+            },
+            FieldDiff::Scalar {
+                name: "noise.sky".to_string(),
+                first: DmapScalar::Float(noise),
+                second: DmapScalar::Float(noise + 0.001),
+            },
+        ]
+    );
+
+    // The float difference is within the tolerance
+    let diffs = diff_files::<FitacfRecord>(&path, &tempfile, Some(0.01)).unwrap();
+    assert_eq!(
+        diffs[0].fields,
+        vec![FieldDiff::Scalar {
+            name: "tfreq".to_string(),
+            first: DmapScalar::Short(tfreq),
+            second: DmapScalar::Short(tfreq + 1),
+        }]
+    );
+    remove_file(&tempfile).expect("Unable to delete diff.fitacf");
+}
+
+#[test]
+fn dedup_duplicated_record() {
+    let path = PathBuf::from("tests/test_files/test.rawacf");
+    let tempfile = PathBuf::from("tests/test_files/duplicated.rawacf");
+    let recs = RawacfRecord::read_file(&path).expect("Unable to read test.rawacf");
+    let duplicated = vec![
+        recs[0].clone(),
+        recs[0].clone(),
+        recs[1].clone(),
+        recs[0].clone(),
+    ];
+    write_rawacf(duplicated, &tempfile).expect("Unable to write duplicated.rawacf");
+
+    let data: Vec<IndexMap<String, DmapField>> = RawacfRecord::read_file(&tempfile)
+        .expect("Unable to read duplicated.rawacf")
+        .into_iter()
+        .map(|rec| rec.inner())
+        .collect();
+    let (deduped, num_removed) = dedup_records(data).expect("Unable to dedup records");
+    assert_eq!(num_removed, 1);
+    let expected: Vec<IndexMap<String, DmapField>> = vec![
+        recs[0].clone().inner(),
+        recs[1].clone().inner(),
+        recs[0].clone().inner(),
+    ];
+    assert_eq!(deduped, expected);
+    remove_file(&tempfile).expect("Unable to delete duplicated.rawacf");
+}
+
+#[test]
+fn parse_embedded_records() {
+    let path = PathBuf::from("tests/test_files/test.grid");
+    let recs = GenericRecord::read_file(&path).expect("Unable to read test.grid");
+    let bytes = read(&path).expect("Unable to read test.grid");
+    let first_size = i32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+    let second_size =
+        i32::from_le_bytes(bytes[first_size + 4..first_size + 8].try_into().unwrap()) as usize;
+
+    // A custom header, followed by two records and then some trailing data
+    let header = b"CUSTOM HEADER\0".to_vec();
+    let buffer = [&header, &bytes[..first_size + second_size], &b"TRAILER"[..]].concat();
+    let mut cursor = Cursor::new(buffer.as_slice());
+    cursor.set_position(header.len() as u64);
+
+    let first = parse_one(&mut cursor).expect("Unable to parse first record");
+    assert_eq!(first, recs[0]);
+    assert_eq!(cursor.position() as usize, header.len() + first_size);
+    let second = parse_one(&mut cursor).expect("Unable to parse second record");
+    assert_eq!(second, recs[1]);
+    assert_eq!(
+        cursor.position() as usize,
+        header.len() + first_size + second_size
+    );
+}
+
+#[test]
+fn read_unknown_fields_by_mode() {
+    let path = PathBuf::from("tests/test_files/test.rawacf");
+    let tempfile = PathBuf::from("tests/test_files/unknown_field.rawacf");
+    let tempfile2 = PathBuf::from("tests/test_files/unknown_field_rewritten.rawacf");
+    let data = RawacfRecord::read_file(&path).expect("Unable to read test.rawacf");
+
+    let mut generic_recs = GenericRecord::read_file(&path).expect("Unable to read test.rawacf");
+    for rec in generic_recs.iter_mut() {
+        rec.data
+            .insert("new.field".to_string(), DmapField::from(7_i32));
+    }
+    write_dmap(generic_recs, &tempfile).expect("Unable to write unknown_field.rawacf");
+
+    let strict = ReadOptions::default();
+    assert_eq!(strict.validation, ValidationMode::Strict);
+    assert!(read_file_with::<RawacfRecord>(&tempfile, &strict).is_err());
+
+    let lenient = ReadOptions {
+        validation: ValidationMode::Lenient,
+        ..Default::default()
+    };
+    let recs: Vec<RawacfRecord> =
+        read_file_with(&tempfile, &lenient).expect("Unable to read leniently");
+    for (rec, expected) in izip!(recs.iter(), data.iter()) {
+        assert_eq!(rec.get_as::<i32>("new.field").unwrap(), 7);
+        let mut known = rec.data.clone();
+        known.shift_remove("new.field");
+        assert_eq!(known, expected.data);
+    }
+
+    // Unknown fields are dropped when writing
+    write_rawacf(recs, &tempfile2).expect("Unable to write unknown_field_rewritten.rawacf");
+    let rewritten =
+        RawacfRecord::read_file(&tempfile2).expect("Unable to read unknown_field_rewritten.rawacf");
+    assert_eq!(rewritten, data);
+    remove_file(&tempfile).expect("Unable to delete unknown_field.rawacf");
+    remove_file(&tempfile2).expect("Unable to delete unknown_field_rewritten.rawacf");
+}